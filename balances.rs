@@ -1,21 +1,25 @@
 #[cfg(not(feature = "enumerable"))]
 pub mod balance_manager {
     use crate::{data::Id, PSP34Error};
-    use ink::{primitives::AccountId, storage::Mapping};
-    use ink::prelude::string::String;
+    use ink::{
+        prelude::collections::BTreeMap,
+        primitives::AccountId,
+        storage::{Lazy, Mapping},
+    };
 
     #[ink::storage_item]
     #[derive(Default, Debug)]
     pub struct Balances {
         owned_tokens_count: Mapping<AccountId, u32>,
         total_supply: u128,
+        // Added after the first release, so they are kept in their own cells.
+        distinct_owners: Lazy<u32>,
+        // Set when storage holding tokens is migrated from a version which did not count
+        // the owners. They are not listed, so their count cannot be recovered.
+        owner_count_unknown: Lazy<bool>,
     }
 
     impl Balances {
-        pub fn new() -> Balances {
-            Default::default()
-        }
-
         pub fn balance_of(&self, owner: &AccountId) -> u32 {
             self.owned_tokens_count.get(owner).unwrap_or(0)
         }
@@ -23,61 +27,214 @@ pub mod balance_manager {
         pub fn increase_balance(
             &mut self,
             owner: &AccountId,
-            _id: &Id,
+            id: &Id,
             increase_supply: bool,
         ) -> Result<(), PSP34Error> {
-            let mut to_balance = self.balance_of(owner);
-            to_balance = to_balance
-                .checked_add(1)
-                .ok_or(PSP34Error::Custom(String::from(
-                    "Max PSP34 balance exceeded. Max balance limited to 2^32-1.",
-                )))?;
-            self.owned_tokens_count.insert(owner, &to_balance);
+            self.increase_balance_by(owner, core::slice::from_ref(id), increase_supply)
+        }
 
+        pub fn increase_balance_by(
+            &mut self,
+            owner: &AccountId,
+            ids: &[Id],
+            increase_supply: bool,
+        ) -> Result<(), PSP34Error> {
+            let balance = self.balance_of(owner);
+            let to_balance = u32::try_from(ids.len())
+                .ok()
+                .and_then(|count| balance.checked_add(count))
+                .ok_or(PSP34Error::MaxBalanceExceeded)?;
+            let mut total_supply = self.total_supply;
             if increase_supply {
-                self.total_supply =
-                    self.total_supply
-                        .checked_add(1)
-                        .ok_or(PSP34Error::Custom(String::from(
-                            "Max PSP34 supply exceeded. Max supply limited to 2^128-1.",
-                        )))?;
+                total_supply = total_supply
+                    .checked_add(ids.len() as u128)
+                    .ok_or(PSP34Error::MaxSupplyExceeded)?;
             }
 
+            self.owned_tokens_count.insert(owner, &to_balance);
+            self.total_supply = total_supply;
+            if balance == 0 && to_balance > 0 {
+                self.distinct_owners
+                    .set(&self.distinct_owners.get_or_default().saturating_add(1));
+            }
             Ok(())
         }
 
-        pub fn decrease_balance(&mut self, owner: &AccountId, _id: &Id, decrease_supply: bool) {
-            let from_balance = self.balance_of(owner);
-            if from_balance <= 1 {
+        /// Adds `ids` to the total supply without changing any balance.
+        pub fn increase_supply(&mut self, ids: &[Id]) -> Result<(), PSP34Error> {
+            self.total_supply = self
+                .total_supply
+                .checked_add(ids.len() as u128)
+                .ok_or(PSP34Error::MaxSupplyExceeded)?;
+            Ok(())
+        }
+
+        pub fn decrease_balance(&mut self, owner: &AccountId, id: &Id, decrease_supply: bool) {
+            self.decrease_balance_by(owner, core::slice::from_ref(id), decrease_supply)
+        }
+
+        pub fn decrease_balance_by(
+            &mut self,
+            owner: &AccountId,
+            ids: &[Id],
+            decrease_supply: bool,
+        ) {
+            let balance = self.balance_of(owner);
+            let from_balance = balance.saturating_sub(u32::try_from(ids.len()).unwrap_or(u32::MAX));
+            if from_balance == 0 {
                 self.owned_tokens_count.remove(owner);
+                if balance > 0 {
+                    self.distinct_owners
+                        .set(&self.distinct_owners.get_or_default().saturating_sub(1));
+                }
             } else {
-                self.owned_tokens_count.insert(owner, &(from_balance - 1));
+                self.owned_tokens_count.insert(owner, &from_balance);
             }
             if decrease_supply {
-                self.total_supply -= 1;
+                self.total_supply -= ids.len() as u128;
+                // Without any token there is no owner, so an unknown count is known again.
+                if self.total_supply == 0 && self.owner_count_unknown.get_or_default() {
+                    self.distinct_owners.set(&0);
+                    self.owner_count_unknown.set(&false);
+                }
+            }
+        }
+
+        /// Moves token `id` from `from` to `to` like `increase_balance` followed by
+        /// `decrease_balance`, but only in `cache`. Nothing is written until `flush`.
+        pub fn move_cached(
+            &self,
+            cache: &mut BalanceCache,
+            from: &AccountId,
+            to: &AccountId,
+            _id: &Id,
+        ) -> Result<(), PSP34Error> {
+            let (_, to_balance) = self.cached(cache, to);
+            *to_balance = to_balance
+                .checked_add(1)
+                .ok_or(PSP34Error::MaxBalanceExceeded)?;
+            let (_, from_balance) = self.cached(cache, from);
+            *from_balance = from_balance.saturating_sub(1);
+            Ok(())
+        }
+
+        /// Writes the balances changed in `cache` to storage.
+        pub fn flush(&mut self, cache: BalanceCache) {
+            let owners = self.distinct_owners.get_or_default();
+            let mut new_owners = owners;
+            for (owner, (initial, balance)) in cache.balances {
+                if balance == initial {
+                    continue;
+                }
+                if balance == 0 {
+                    self.owned_tokens_count.remove(owner);
+                    new_owners = new_owners.saturating_sub(1);
+                } else {
+                    self.owned_tokens_count.insert(owner, &balance);
+                    if initial == 0 {
+                        new_owners = new_owners.saturating_add(1);
+                    }
+                }
+            }
+            if new_owners != owners {
+                self.distinct_owners.set(&new_owners);
             }
         }
 
+        fn cached<'a>(&self, cache: &'a mut BalanceCache, owner: &AccountId) -> &'a mut (u32, u32) {
+            cache.balances.entry(*owner).or_insert_with(|| {
+                let balance = self.balance_of(owner);
+                (balance, balance)
+            })
+        }
+
         pub fn total_supply(&self) -> u128 {
             self.total_supply
         }
+
+        /// Returns the number of accounts owning at least one token, or `None` if it is
+        /// unknown after a migration.
+        pub fn owner_count(&self) -> Option<u32> {
+            if self.owner_count_unknown.get_or_default() {
+                return None;
+            }
+            Some(self.distinct_owners.get_or_default())
+        }
+
+        /// Marks the number of owners as unknown, for storage of a version which did not
+        /// count them. It stays unknown until no token is left.
+        pub fn forget_owner_count(&mut self) {
+            self.owner_count_unknown.set(&true);
+        }
+
+        #[cfg(test)]
+        pub fn set_balance(&mut self, owner: &AccountId, balance: u32) {
+            self.owned_tokens_count.insert(owner, &balance);
+        }
+
+        #[cfg(test)]
+        pub fn set_total_supply(&mut self, total_supply: u128) {
+            self.total_supply = total_supply;
+        }
+
+        #[cfg(all(test, feature = "footprint-tests"))]
+        pub fn largest_cell_size(&self, owner: &AccountId) -> usize {
+            use scale::Encode;
+            self.balance_of(owner)
+                .encoded_size()
+                .max(self.total_supply.encoded_size())
+        }
+    }
+
+    /// Balances of the accounts touched by a batch of transfers, each read from storage
+    /// once, updated in memory by `Balances::move_cached` and written back once by
+    /// `Balances::flush`.
+    #[derive(Default, Debug)]
+    pub struct BalanceCache {
+        // The balance of each account before the batch and the current one.
+        balances: BTreeMap<AccountId, (u32, u32)>,
+    }
+
+    impl BalanceCache {
+        /// Returns each touched account with its balance before the batch and the current one.
+        pub fn balances(&self) -> impl Iterator<Item = (AccountId, u32, u32)> + '_ {
+            self.balances
+                .iter()
+                .map(|(owner, (initial, balance))| (*owner, *initial, *balance))
+        }
     }
 }
 
 #[cfg(feature = "enumerable")]
 pub mod balance_manager {
     use crate::{data::Id, PSP34Error};
-    use ink::{prelude::vec::Vec, primitives::AccountId, storage::Mapping};
+    use ink::{
+        prelude::{collections::BTreeMap, vec::Vec},
+        primitives::AccountId,
+        storage::{Lazy, Mapping},
+    };
 
     #[ink::storage_item]
     #[derive(Default, Debug)]
     pub struct Balances {
         enumerable: Mapping<Option<AccountId>, Vec<Id>>,
+        // Added after the first release, so they are kept in their own cells.
+        // The holders are listed by index, `distinct_owners` being the length of the list.
+        holders: Mapping<u32, AccountId>,
+        holder_index: Mapping<AccountId, u32>,
+        distinct_owners: Lazy<u32>,
     }
 
     impl Balances {
-        pub fn new() -> Balances {
-            Default::default()
+        pub fn owner_by_index(&self, index: u128) -> Option<AccountId> {
+            self.holders.get(u32::try_from(index).ok()?)
+        }
+
+        pub fn holders(&self, offset: u32, limit: u32) -> Vec<AccountId> {
+            let end = offset.saturating_add(limit).min(self.holder_count());
+            (offset..end)
+                .filter_map(|index| self.holders.get(index))
+                .collect()
         }
 
         pub fn owners_token_by_index(
@@ -94,15 +251,40 @@ pub mod balance_manager {
                 .ok_or(PSP34Error::TokenNotExists)
         }
 
+        pub fn index_of_owned(&self, owner: &AccountId, id: &Id) -> Option<u128> {
+            self.enumerable
+                .get(Some(*owner))?
+                .iter()
+                .position(|value| value == id)
+                .map(|index| index as u128)
+        }
+
+        #[cfg(feature = "std")]
+        pub fn tokens_where(&self, pred: impl Fn(&Id) -> bool) -> Vec<Id> {
+            self.enumerable
+                .get(None::<AccountId>)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|id| pred(id))
+                .collect()
+        }
+
+        #[cfg(feature = "std")]
+        pub fn tokens_of_owner(&self, owner: &AccountId) -> Vec<Id> {
+            self.enumerable.get(Some(*owner)).unwrap_or_default()
+        }
+
         fn _get_value(&self, key: &Option<AccountId>, index: u128) -> Option<Id> {
+            // An index which does not fit in `usize` is out of bounds of any list.
+            let index = usize::try_from(index).ok()?;
             self.enumerable
                 .get(key)
-                .and_then(|values| values.get(usize::try_from(index).unwrap()).cloned())
+                .and_then(|values| values.get(index).cloned())
         }
 
-        fn _insert(&mut self, key: &Option<AccountId>, value: &Id) {
+        fn _insert(&mut self, key: &Option<AccountId>, new_values: &[Id]) {
             let mut values = self.enumerable.get(key).unwrap_or_default();
-            values.push(value.clone());
+            values.extend_from_slice(new_values);
             self.enumerable.insert(key, &values);
         }
 
@@ -118,13 +300,11 @@ pub mod balance_manager {
         fn _count(&self, key: &Option<AccountId>) -> u128 {
             self.enumerable
                 .get(key)
-                .map_or(0, |values| values.len())
-                .try_into()
-                .unwrap()
+                .map_or(0, |values| values.len() as u128)
         }
 
         pub fn balance_of(&self, owner: &AccountId) -> u32 {
-            self._count(&Some(*owner)) as u32
+            u32::try_from(self._count(&Some(*owner))).unwrap_or(u32::MAX)
         }
 
         pub fn increase_balance(
@@ -133,26 +313,263 @@ pub mod balance_manager {
             id: &Id,
             increase_supply: bool,
         ) -> Result<(), PSP34Error> {
-            self._insert(&Some(*owner), id);
+            self.increase_balance_by(owner, core::slice::from_ref(id), increase_supply)
+        }
+
+        pub fn increase_balance_by(
+            &mut self,
+            owner: &AccountId,
+            ids: &[Id],
+            increase_supply: bool,
+        ) -> Result<(), PSP34Error> {
+            if ids.is_empty() {
+                return Ok(());
+            }
+            if self.balance_of(owner) == 0 {
+                self.add_holder(owner);
+            }
+            self._insert(&Some(*owner), ids);
             if increase_supply {
-                self._insert(&None, id);
+                self._insert(&None, ids);
             }
 
             Ok(())
         }
 
-        pub fn decrease_balance(&mut self, owner: &AccountId, id: &Id, decrease_supply: bool) {              
-            self._remove(&Some(*owner), id);
-            if self.balance_of(owner) == 0 {
+        /// Adds `ids` to the list of all tokens without changing any balance.
+        pub fn increase_supply(&mut self, ids: &[Id]) -> Result<(), PSP34Error> {
+            self._insert(&None, ids);
+            Ok(())
+        }
+
+        pub fn decrease_balance(&mut self, owner: &AccountId, id: &Id, decrease_supply: bool) {
+            self.decrease_balance_by(owner, core::slice::from_ref(id), decrease_supply)
+        }
+
+        /// Removes `ids` from the tokens of `owner` like `decrease_balance` called with
+        /// each of them in turn, but reading and writing every list once.
+        pub fn decrease_balance_by(
+            &mut self,
+            owner: &AccountId,
+            ids: &[Id],
+            decrease_supply: bool,
+        ) {
+            if ids.is_empty() {
+                return;
+            }
+            let mut owned = self.enumerable.get(Some(*owner)).unwrap_or_default();
+            ids.iter().for_each(|id| Self::swap_remove(&mut owned, id));
+            if owned.is_empty() {
                 self.enumerable.remove(Some(owner));
+                self.remove_holder(owner);
+            } else {
+                self.enumerable.insert(Some(*owner), &owned);
             }
             if decrease_supply {
-                self._remove(&None, id);
+                let mut all = self.enumerable.get(None::<AccountId>).unwrap_or_default();
+                ids.iter().for_each(|id| Self::swap_remove(&mut all, id));
+                self.enumerable.insert(None::<AccountId>, &all);
+            }
+        }
+
+        /// Returns the last `count` tokens of `owner`, the last one first.
+        pub fn last_owned(&self, owner: &AccountId, count: usize) -> Vec<Id> {
+            let owned = self.enumerable.get(Some(*owner)).unwrap_or_default();
+            owned.into_iter().rev().take(count).collect()
+        }
+
+        /// Moves token `id` from `from` to `to` like `increase_balance` followed by
+        /// `decrease_balance`, but only in `cache`. Nothing is written until `flush`.
+        pub fn move_cached(
+            &self,
+            cache: &mut BalanceCache,
+            from: &AccountId,
+            to: &AccountId,
+            id: &Id,
+        ) -> Result<(), PSP34Error> {
+            if self.cached(cache, to).1.is_empty() {
+                cache.holder_changes.push((*to, true));
+            }
+            self.cached(cache, to).1.push(id.clone());
+
+            let (_, owned) = self.cached(cache, from);
+            Self::swap_remove(owned, id);
+            if owned.is_empty() {
+                cache.holder_changes.push((*from, false));
+            }
+            Ok(())
+        }
+
+        /// Writes the lists changed in `cache` to storage.
+        pub fn flush(&mut self, cache: BalanceCache) {
+            for (owner, (initial, owned)) in cache.owned {
+                if owned.is_empty() {
+                    if initial > 0 {
+                        self.enumerable.remove(Some(owner));
+                    }
+                } else {
+                    self.enumerable.insert(Some(owner), &owned);
+                }
+            }
+            // Replayed in order, so the holders end up listed like after single transfers.
+            for (holder, listed) in cache.holder_changes {
+                if listed {
+                    self.add_holder(&holder);
+                } else {
+                    self.remove_holder(&holder);
+                }
+            }
+        }
+
+        fn cached<'a>(
+            &self,
+            cache: &'a mut BalanceCache,
+            owner: &AccountId,
+        ) -> &'a mut (u32, Vec<Id>) {
+            cache.owned.entry(*owner).or_insert_with(|| {
+                let owned = self.enumerable.get(Some(*owner)).unwrap_or_default();
+                (owned.len() as u32, owned)
+            })
+        }
+
+        // Appends `owner` to the list of holders.
+        fn add_holder(&mut self, owner: &AccountId) {
+            let count = self.holder_count();
+            self.holders.insert(count, owner);
+            self.holder_index.insert(owner, &count);
+            self.distinct_owners.set(&count.saturating_add(1));
+        }
+
+        // Removes `owner` from the list of holders, moving the last holder to its index.
+        fn remove_holder(&mut self, owner: &AccountId) {
+            let Some(index) = self.holder_index.take(owner) else {
+                return;
+            };
+            let last = self.holder_count().saturating_sub(1);
+            if index != last {
+                if let Some(moved) = self.holders.get(last) {
+                    self.holders.insert(index, &moved);
+                    self.holder_index.insert(moved, &index);
+                }
+            }
+            self.holders.remove(last);
+            self.distinct_owners.set(&last);
+        }
+
+        fn swap_remove<T: PartialEq>(values: &mut Vec<T>, value: &T) {
+            if let Some(pos) = values.iter().position(|v| v == value) {
+                values.swap_remove(pos);
             }
         }
 
         pub fn total_supply(&self) -> u128 {
             self._count(&None)
         }
+
+        /// Returns the number of accounts owning at least one token, which is the length
+        /// of the list of holders, so it is always known.
+        pub fn owner_count(&self) -> Option<u32> {
+            Some(self.holder_count())
+        }
+
+        fn holder_count(&self) -> u32 {
+            self.distinct_owners.get_or_default()
+        }
+
+        /// Lists the owners of all tokens, looked up with `owner_of`, as holders.
+        /// The first release did not list the holders, so they have to be collected.
+        pub fn list_holders(&mut self, owner_of: impl Fn(&Id) -> Option<AccountId>) {
+            for id in self.enumerable.get(None::<AccountId>).unwrap_or_default() {
+                if let Some(owner) = owner_of(&id) {
+                    if !self.holder_index.contains(owner) {
+                        self.add_holder(&owner);
+                    }
+                }
+            }
+        }
+
+        #[cfg(all(test, feature = "footprint-tests"))]
+        pub fn largest_cell_size(&self, owner: &AccountId) -> usize {
+            use scale::Encode;
+            self.enumerable
+                .get(Some(*owner))
+                .encoded_size()
+                .max(self.enumerable.get(None::<AccountId>).encoded_size())
+        }
+    }
+
+    /// Token lists of the accounts touched by a batch of transfers, each read from storage
+    /// once, updated in memory by `Balances::move_cached` and written back once by
+    /// `Balances::flush`.
+    #[derive(Default, Debug)]
+    pub struct BalanceCache {
+        // The balance of each account before the batch and its current tokens.
+        owned: BTreeMap<AccountId, (u32, Vec<Id>)>,
+        // The accounts added to (`true`) or removed from the list of holders, in order.
+        holder_changes: Vec<(AccountId, bool)>,
+    }
+
+    impl BalanceCache {
+        /// Returns each touched account with its balance before the batch and the current one.
+        pub fn balances(&self) -> impl Iterator<Item = (AccountId, u32, u32)> + '_ {
+            self.owned
+                .iter()
+                .map(|(owner, (initial, owned))| (*owner, *initial, owned.len() as u32))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "footprint-tests"))]
+mod footprint_tests {
+    use super::balance_manager::Balances;
+    use crate::data::Id;
+    use ink::primitives::AccountId;
+
+    /// Maximal size of a single storage value accepted by `pallet-contracts`,
+    /// which is also the size of ink!'s static buffer used to decode it.
+    const MAX_STORAGE_VALUE_SIZE: usize = 16 * 1024;
+    const COLLECTION_SIZE: u128 = 10_000;
+
+    // Mints `size` tokens to a single owner, panicking with a clear message as soon as
+    // a mint fails, traps or leaves a storage cell beyond the limit.
+    fn mint_collection(size: u128) {
+        let mut balances = Balances::default();
+        let owner = AccountId::from([1; 32]);
+        let mut largest = 0;
+        for i in 0..size {
+            // The off-chain engine panics, just like the chain traps, when a storage
+            // value does not fit into the buffer.
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                balances
+                    .increase_balance(&owner, &Id::U128(i), true)
+                    .map(|()| balances.largest_cell_size(&owner))
+            }));
+            match result {
+                Ok(Ok(size)) => largest = largest.max(size),
+                Ok(Err(e)) => panic!("Minting token {} failed: {:?}", i, e),
+                Err(_) => panic!(
+                    "Minting token {} trapped, the largest storage cell took {} bytes before.",
+                    i, largest
+                ),
+            }
+            assert!(
+                largest <= MAX_STORAGE_VALUE_SIZE,
+                "After minting {} tokens the largest storage cell takes {} bytes, \
+                 exceeding the {} bytes limit. Further calls would trap.",
+                i + 1,
+                largest,
+                MAX_STORAGE_VALUE_SIZE
+            );
+        }
+    }
+
+    #[ink::test]
+    #[cfg_attr(
+        feature = "enumerable",
+        ignore = "the enumerable layout keeps all tokens in a single cell and traps at 564 \
+                  `Id::U128` tokens, until its storage is redesigned"
+    )]
+    fn large_collection_fits_in_storage_cells() {
+        mint_collection(COLLECTION_SIZE);
     }
 }
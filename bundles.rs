@@ -0,0 +1,135 @@
+use crate::{
+    data::{EventList, Id, PSP34Data, PSP34Event, TokenHold},
+    PSP34Error,
+};
+use ink::{
+    prelude::{string::String, vec::Vec},
+    primitives::AccountId,
+    storage::{Lazy, Mapping},
+};
+
+/// Maximal number of components of a single bundle.
+pub const MAX_BUNDLE_SIZE: usize = 16;
+
+/// Bundles of tokens of a `PSP34Data`, traded as a single token.
+///
+/// Bundling moves the components to the custody of the contract's account, where they
+/// are held with `TokenHold::Bundled`, and mints a bundle token with an `Id::U128` id
+/// counted down from `u128::MAX`, so it does not collide with sequentially minted ids.
+/// Only the current owner of the bundle can unbundle it, receiving the components.
+/// Bundles cannot be nested.
+///
+/// A bundle burned in any other way than with `unbundle` leaves its components in custody,
+/// so the contract should reject such burns, see `ensure_not_bundle`.
+#[ink::storage_item]
+#[derive(Default, Debug)]
+pub struct Data {
+    contents: Mapping<Id, Vec<Id>>,
+    created: Lazy<u128>,
+}
+
+impl Data {
+    /// Returns the components of bundle `id`, or `None` if it is not a bundle.
+    pub fn contents_of(&self, token: &PSP34Data, id: &Id) -> Option<Vec<Id>> {
+        let id = &token.normalize_id(id.clone());
+        self.contents.get(id)
+    }
+
+    /// Fails if token `id` is a bundle, which would lose its components if burned.
+    pub fn ensure_not_bundle(&self, token: &PSP34Data, id: &Id) -> Result<(), PSP34Error> {
+        if self.contents_of(token, id).is_some() {
+            return Err(PSP34Error::Custom(String::from(
+                "Bundles can only be burned by unbundling.",
+            )));
+        }
+        Ok(())
+    }
+
+    /// Bundles tokens `ids` of `caller`, moving them to `custodian`, the contract's
+    /// account, and minting the bundle token to `caller`. Returns the id of the bundle.
+    /// Fails without changing anything if any of the tokens cannot be transferred.
+    pub fn create_bundle(
+        &mut self,
+        token: &mut PSP34Data,
+        caller: AccountId,
+        custodian: AccountId,
+        ids: Vec<Id>,
+        block_number: u32,
+        timestamp: u64,
+    ) -> Result<(Id, EventList), PSP34Error> {
+        let ids: Vec<Id> = ids.into_iter().map(|id| token.normalize_id(id)).collect();
+        if ids.is_empty() || ids.len() > MAX_BUNDLE_SIZE {
+            return Err(PSP34Error::Custom(String::from(
+                "Invalid number of bundle components.",
+            )));
+        }
+        for (i, id) in ids.iter().enumerate() {
+            if ids[..i].contains(id) {
+                return Err(PSP34Error::Custom(String::from(
+                    "Duplicate bundle component.",
+                )));
+            }
+            if token.owner_of(id).ok_or(PSP34Error::TokenNotExists)? != caller {
+                return Err(PSP34Error::NotApproved);
+            }
+            if self.contents.contains(id) {
+                return Err(PSP34Error::Custom(String::from(
+                    "Bundles cannot be nested.",
+                )));
+            }
+            token.simulate_transfer(caller, custodian, id)?;
+        }
+        token.ensure_balance_fits(custodian, ids.len())?;
+
+        let created = self.created.get_or_default();
+        let bundle_id = Id::U128(u128::MAX - created);
+        let mut events =
+            token.mint_unchecked(caller, bundle_id.clone(), block_number, timestamp)?;
+        for id in &ids {
+            events.extend(token.transfer_unchecked(caller, custodian, id.clone())?);
+            token.hold(id, TokenHold::Bundled)?;
+        }
+        self.created.set(&(created + 1));
+        self.contents.insert(&bundle_id, &ids);
+        events.push(PSP34Event::BundleCreated {
+            id: bundle_id.clone(),
+            owner: caller,
+            components: ids,
+        });
+        Ok((bundle_id, events))
+    }
+
+    /// Burns bundle `id` of `caller` and returns its components from `custodian`,
+    /// the contract's account, to `caller`.
+    pub fn unbundle(
+        &mut self,
+        token: &mut PSP34Data,
+        caller: AccountId,
+        custodian: AccountId,
+        id: Id,
+    ) -> Result<EventList, PSP34Error> {
+        let id = token.normalize_id(id);
+        let components = self
+            .contents
+            .get(&id)
+            .ok_or(PSP34Error::Custom(String::from("Token is not a bundle.")))?;
+        if token.owner_of(&id).ok_or(PSP34Error::TokenNotExists)? != caller {
+            return Err(PSP34Error::NotApproved);
+        }
+        token.ensure_movable(&id)?;
+        // The bundle is burned before the components arrive.
+        token.ensure_balance_fits(caller, components.len() - 1)?;
+
+        let mut events = token.burn_unchecked(caller, id.clone())?;
+        self.contents.remove(&id);
+        for component in &components {
+            events.extend(token.transfer_unchecked(custodian, caller, component.clone())?);
+        }
+        events.push(PSP34Event::Unbundled {
+            id,
+            owner: caller,
+            components,
+        });
+        Ok(events)
+    }
+}
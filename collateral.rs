@@ -0,0 +1,133 @@
+use crate::{
+    data::{EventList, Id, PSP34Data, PSP34Event, TokenHold},
+    PSP34Error,
+};
+use ink::{
+    prelude::{string::String, vec::Vec},
+    primitives::AccountId,
+    storage::Mapping,
+};
+
+/// A token pledged as collateral of a loan by `lender`.
+#[derive(Debug, PartialEq, Eq, Clone, scale::Encode, scale::Decode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct Collateral {
+    pub owner: AccountId,
+    pub lender: AccountId,
+    /// The terms under which the token is to be released, as agreed with the lender.
+    /// They are only recorded, it is up to the lender to honor them.
+    pub release_conditions: Vec<u8>,
+}
+
+/// Tokens of a `PSP34Data` locked as collateral in their owner's wallet.
+///
+/// A locked token is held with `TokenHold::Collateral`, so its owner cannot transfer
+/// or burn it. Only the lender can end the lock, by releasing the token to its owner
+/// or by seizing it on default.
+#[ink::storage_item]
+#[derive(Default, Debug)]
+pub struct Data {
+    collaterals: Mapping<Id, Collateral>,
+}
+
+impl Data {
+    /// Returns the collateral lock of token `id`, if there is one.
+    pub fn collateral_status(&self, token: &PSP34Data, id: &Id) -> Option<Collateral> {
+        let id = &token.normalize_id(id.clone());
+        self.collaterals.get(id).filter(|collateral| {
+            token.owner_of(id) == Some(collateral.owner)
+                && token.hold_of(id) == Some(TokenHold::Collateral)
+        })
+    }
+
+    /// Locks token `id` of `caller` as collateral of a loan from `lender`.
+    pub fn lock_as_collateral(
+        &mut self,
+        token: &mut PSP34Data,
+        caller: AccountId,
+        id: Id,
+        lender: AccountId,
+        release_conditions: Vec<u8>,
+    ) -> Result<EventList, PSP34Error> {
+        let id = token.normalize_id(id);
+        if token.owner_of(&id).ok_or(PSP34Error::TokenNotExists)? != caller {
+            return Err(PSP34Error::NotApproved);
+        }
+        if lender == caller {
+            return Err(PSP34Error::Custom(String::from(
+                "Owner cannot be their own lender.",
+            )));
+        }
+        token.hold(&id, TokenHold::Collateral)?;
+        self.collaterals.insert(
+            &id,
+            &Collateral {
+                owner: caller,
+                lender,
+                release_conditions,
+            },
+        );
+        Ok(EventList::from(PSP34Event::CollateralLocked {
+            id,
+            owner: caller,
+            lender,
+        }))
+    }
+
+    /// Releases token `id` back to its owner, conducted by the lender.
+    pub fn release(
+        &mut self,
+        token: &mut PSP34Data,
+        caller: AccountId,
+        id: Id,
+    ) -> Result<EventList, PSP34Error> {
+        let id = token.normalize_id(id);
+        let collateral = self.lent_by(token, caller, &id)?;
+        self.collaterals.remove(&id);
+        token.release(&id);
+        Ok(EventList::from(PSP34Event::CollateralReleased {
+            id,
+            owner: collateral.owner,
+            lender: caller,
+        }))
+    }
+
+    /// Transfers token `id` to the lender, conducted by the lender on default of the loan.
+    pub fn seize(
+        &mut self,
+        token: &mut PSP34Data,
+        caller: AccountId,
+        id: Id,
+    ) -> Result<EventList, PSP34Error> {
+        let id = token.normalize_id(id);
+        let collateral = self.lent_by(token, caller, &id)?;
+        self.collaterals.remove(&id);
+        let mut events = token.transfer_unchecked(collateral.owner, caller, id.clone())?;
+        events.push(PSP34Event::CollateralSeized {
+            id,
+            owner: collateral.owner,
+            lender: caller,
+        });
+        Ok(events)
+    }
+
+    fn lent_by(
+        &self,
+        token: &PSP34Data,
+        lender: AccountId,
+        id: &Id,
+    ) -> Result<Collateral, PSP34Error> {
+        let collateral =
+            self.collateral_status(token, id)
+                .ok_or(PSP34Error::Custom(String::from(
+                    "Token is not locked as collateral.",
+                )))?;
+        if collateral.lender != lender {
+            return Err(PSP34Error::NotApproved);
+        }
+        Ok(collateral)
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{
+    data::{EventList, Id, PSP34Data, PSP34Event},
+    metadata, PSP34Error,
+};
+use ink::{
+    prelude::{string::String, vec::Vec},
+    primitives::AccountId,
+    storage::{Lazy, Mapping},
+};
+
+/// Maximal number of inputs of a single recipe.
+pub const MAX_RECIPE_INPUTS: usize = 8;
+
+/// A requirement for one input of a recipe.
+#[derive(Debug, PartialEq, Eq, Clone, scale::Encode, scale::Decode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum Input {
+    /// The input must be this token.
+    Token(Id),
+    /// The input must be a token with the attribute `key` set to `value`.
+    Attribute { key: Vec<u8>, value: Vec<u8> },
+}
+
+/// Burning tokens matching `inputs`, in the same order, crafts a new token with
+/// the `output` attributes.
+#[derive(Debug, PartialEq, Eq, Clone, scale::Encode, scale::Decode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct Recipe {
+    pub inputs: Vec<Input>,
+    pub output: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Recipes crafting new tokens of a `PSP34Data` out of existing ones.
+///
+/// The output token gets the next sequential `Id::U128` id, like with
+/// `PSP34Data::mint_sequential`, and the attributes of the recipe's output template.
+#[ink::storage_item]
+#[derive(Default, Debug)]
+pub struct Data {
+    recipes: Mapping<u32, Recipe>,
+    next_recipe_id: Lazy<u32>,
+}
+
+impl Data {
+    /// Returns the recipe with `recipe_id`, if there is one.
+    pub fn recipe(&self, recipe_id: u32) -> Option<Recipe> {
+        self.recipes.get(recipe_id)
+    }
+
+    /// Registers `recipe` and returns its id.
+    /// The caller is responsible for checking that the admin conducts it.
+    pub fn register_recipe(&mut self, recipe: Recipe) -> Result<u32, PSP34Error> {
+        if recipe.inputs.is_empty() || recipe.inputs.len() > MAX_RECIPE_INPUTS {
+            return Err(PSP34Error::Custom(String::from(
+                "Invalid number of recipe inputs.",
+            )));
+        }
+        let recipe_id = self.next_recipe_id.get_or_default();
+        let next_recipe_id = recipe_id
+            .checked_add(1)
+            .ok_or(PSP34Error::Custom(String::from("Max recipe id exceeded.")))?;
+        self.next_recipe_id.set(&next_recipe_id);
+        self.recipes.insert(recipe_id, &recipe);
+        Ok(recipe_id)
+    }
+
+    /// Crafts a token for `caller` with recipe `recipe_id`, burning `inputs`, which must
+    /// be `caller`'s and match the recipe's inputs in order. Returns the crafted id.
+    /// Fails without burning or minting anything if any input does not qualify.
+    #[allow(clippy::too_many_arguments)]
+    pub fn craft(
+        &self,
+        token: &mut PSP34Data,
+        metadata: &mut metadata::Data,
+        caller: AccountId,
+        recipe_id: u32,
+        inputs: Vec<Id>,
+        block_number: u32,
+        timestamp: u64,
+    ) -> Result<(Id, EventList), PSP34Error> {
+        let recipe = self
+            .recipes
+            .get(recipe_id)
+            .ok_or(PSP34Error::Custom(String::from("Recipe does not exist.")))?;
+        let mismatch = || PSP34Error::Custom(String::from("Inputs do not match the recipe."));
+        let inputs: Vec<Id> = inputs
+            .into_iter()
+            .map(|id| token.normalize_id(id))
+            .collect();
+        if inputs.len() != recipe.inputs.len() {
+            return Err(mismatch());
+        }
+        for (i, (id, input)) in inputs.iter().zip(&recipe.inputs).enumerate() {
+            if token.owner_of(id).ok_or(PSP34Error::TokenNotExists)? != caller {
+                return Err(PSP34Error::NotApproved);
+            }
+            let matches = match input {
+                Input::Token(required) => token.normalize_id(required.clone()) == *id,
+                Input::Attribute { key, value } => {
+                    metadata.get_attribute(id.clone(), key.clone()).as_ref() == Some(value)
+                }
+            };
+            if !matches || inputs[..i].contains(id) {
+                return Err(mismatch());
+            }
+            token.ensure_movable(id)?;
+        }
+
+        let (ids, mut events) = token.mint_sequential(caller, 1, block_number, timestamp)?;
+        let output = Id::U128(ids.start);
+        for (key, value) in recipe.output {
+            events.extend(metadata.set_attribute(output.clone(), key, value)?);
+        }
+        for id in &inputs {
+            events.extend(token.burn_unchecked(caller, id.clone())?);
+        }
+        events.push(PSP34Event::Crafted {
+            recipe_id,
+            owner: caller,
+            inputs,
+            output: output.clone(),
+        });
+        Ok((output, events))
+    }
+}
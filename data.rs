@@ -1,9 +1,10 @@
-use crate::balances::balance_manager::Balances;
+use crate::balances::balance_manager::{BalanceCache, Balances};
 use crate::PSP34Error;
+use core::{cmp::Ordering, ops::Range};
 use ink::{
-    prelude::{string::String, vec, vec::Vec},
+    prelude::{collections::BTreeMap, string::String, vec, vec::Vec},
     primitives::AccountId,
-    storage::Mapping,
+    storage::{Lazy, Mapping},
 };
 
 #[cfg(feature = "std")]
@@ -11,21 +12,44 @@ use ink::storage::traits::StorageLayout;
 
 /// Type for a PSP34 token id.
 /// Contains all the possible permutations of id according to the standard.
+///
+/// The variant indices are fixed, so that the SCALE encoding is byte-identical
+/// with the `Id` type of OpenBrush and contracts built with either crate can call
+/// each other. `U256`, which OpenBrush does not have, comes after all of them.
+///
+/// The derived `Ord` compares the variants first and their values second, so that
+/// e.g. `Id::U8(200) < Id::U16(1)` and every numeric id comes before `Id::Bytes`,
+/// except for `Id::U256`. Use `Id::canonical_cmp` to order ids by their values instead.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, scale::Encode, scale::Decode)]
 #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
 pub enum Id {
+    #[codec(index = 0)]
     U8(u8),
+    #[codec(index = 1)]
     U16(u16),
+    #[codec(index = 2)]
     U32(u32),
+    #[codec(index = 3)]
     U64(u64),
+    #[codec(index = 4)]
     U128(u128),
+    #[codec(index = 5)]
     Bytes(Vec<u8>),
+    /// A 256-bit id, e.g. of a token bridged from an EVM chain, as big-endian bytes,
+    /// so that the ids of this variant are ordered by their numeric value.
+    #[codec(index = 6)]
+    U256([u8; 32]),
 }
 
 /// Temporary type for events emitted during operations that change the
 /// state of PSP34Data struct.
 /// This is meant to be replaced with proper ink! events as soon as the
 /// language allows for event definitions outside contracts.
+///
+/// The events can be SCALE encoded on their own, e.g. to be compared in tests
+/// without going through the contract's event types.
+#[derive(Debug, PartialEq, Eq, Clone, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
 pub enum PSP34Event {
     Transfer {
         from: Option<AccountId>,
@@ -43,199 +67,4431 @@ pub enum PSP34Event {
         key: Vec<u8>,
         data: Vec<u8>,
     },
+    TransferOffered {
+        from: AccountId,
+        to: AccountId,
+        id: Id,
+        deadline: Option<u32>,
+    },
+    TransferClaimed {
+        from: AccountId,
+        to: AccountId,
+        id: Id,
+    },
+    TransferCancelled {
+        from: AccountId,
+        to: AccountId,
+        id: Id,
+    },
+    ProvenanceSet {
+        hash: [u8; 32],
+    },
+    Minted {
+        to: AccountId,
+        id: Id,
+    },
+    Burned {
+        from: AccountId,
+        caller: AccountId,
+        id: Id,
+    },
+    TransferMemo {
+        id: Id,
+        sequence: u32,
+        memo: Vec<u8>,
+    },
+    TokenRescued {
+        id: Id,
+        to: AccountId,
+    },
+    TeleportedOut {
+        id: Id,
+        from: AccountId,
+        dest_chain: u32,
+        dest_account: Vec<u8>,
+        nonce: u64,
+    },
+    TeleportedIn {
+        id: Id,
+        to: AccountId,
+    },
+    BalanceChanged {
+        account: AccountId,
+        new_balance: u32,
+    },
+    Listed {
+        id: Id,
+        seller: AccountId,
+        price: u128,
+    },
+    Delisted {
+        id: Id,
+        seller: AccountId,
+    },
+    Sold {
+        id: Id,
+        seller: AccountId,
+        buyer: AccountId,
+        price: u128,
+    },
+    OfferMade {
+        id: Id,
+        bidder: AccountId,
+        amount: u128,
+    },
+    OfferCancelled {
+        id: Id,
+        bidder: AccountId,
+        amount: u128,
+    },
+    OfferAccepted {
+        id: Id,
+        seller: AccountId,
+        bidder: AccountId,
+        amount: u128,
+    },
+    AuctionCreated {
+        id: Id,
+        seller: AccountId,
+        start_price: u128,
+        min_increment: u128,
+        end_time: u64,
+    },
+    BidPlaced {
+        id: Id,
+        bidder: AccountId,
+        amount: u128,
+        end_time: u64,
+    },
+    AuctionSettled {
+        id: Id,
+        seller: AccountId,
+        winner: Option<AccountId>,
+        price: u128,
+    },
+    CollateralLocked {
+        id: Id,
+        owner: AccountId,
+        lender: AccountId,
+    },
+    CollateralReleased {
+        id: Id,
+        owner: AccountId,
+        lender: AccountId,
+    },
+    CollateralSeized {
+        id: Id,
+        owner: AccountId,
+        lender: AccountId,
+    },
+    BundleCreated {
+        id: Id,
+        owner: AccountId,
+        components: Vec<Id>,
+    },
+    Unbundled {
+        id: Id,
+        owner: AccountId,
+        components: Vec<Id>,
+    },
+    Crafted {
+        recipe_id: u32,
+        owner: AccountId,
+        inputs: Vec<Id>,
+        output: Id,
+    },
+}
+
+/// Kind of a `PSP34Event`, without its payload.
+/// Returned by read-only methods that report which events an operation would emit.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP34EventKind {
+    Transfer,
+    Approval,
+    AttributeSet,
+    TransferOffered,
+    TransferClaimed,
+    TransferCancelled,
+    ProvenanceSet,
+    Minted,
+    Burned,
+    TransferMemo,
+    TokenRescued,
+    TeleportedOut,
+    TeleportedIn,
+    BalanceChanged,
+    Listed,
+    Delisted,
+    Sold,
+    OfferMade,
+    OfferCancelled,
+    OfferAccepted,
+    AuctionCreated,
+    BidPlaced,
+    AuctionSettled,
+    CollateralLocked,
+    CollateralReleased,
+    CollateralSeized,
+    BundleCreated,
+    Unbundled,
+    Crafted,
+}
+
+/// Handling of a transfer of a token to its current owner.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
+pub enum SelfTransferMode {
+    /// Succeeds without any change or event.
+    #[default]
+    Noop,
+    /// Succeeds without any change, but emits a `Transfer` event from the owner to itself.
+    Emit,
+    /// Fails.
+    Reject,
+}
+
+/// Extension holding a token, which then cannot be transferred, burned or teleported
+/// by its owner until the extension releases it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
+pub enum TokenHold {
+    /// The token is listed for sale in the `marketplace`.
+    Listed,
+    /// The token is auctioned in the `marketplace`.
+    Auctioned,
+    /// The token is locked as `collateral` of a loan.
+    Collateral,
+    /// The token is a component of a `bundles` bundle, in the custody of the contract.
+    Bundled,
+}
+
+/// How `teleport_out` takes a token off this chain.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
+pub enum TeleportMode {
+    /// The token is burned, and minted again when it is teleported back.
+    #[default]
+    Burn,
+    /// The token stays with its owner but cannot be transferred or burned
+    /// until it is teleported back.
+    Lock,
+}
+
+/// A token sent to another chain with `teleport_out`, for the bridge to deliver.
+#[derive(Debug, PartialEq, Eq, Clone, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
+pub struct OutboundTeleport {
+    pub id: Id,
+    pub from: AccountId,
+    pub dest_chain: u32,
+    pub dest_account: Vec<u8>,
+}
+
+/// Events emitted when a token is minted or burned.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
+pub enum MintBurnEvents {
+    /// Only `Transfer` from or to `None`, as required by the standard.
+    #[default]
+    Transfer,
+    /// Only `Minted` and `Burned`.
+    Dedicated,
+    /// `Transfer` followed by `Minted` or `Burned`.
+    Both,
+}
+
+impl PSP34Event {
+    pub fn kind(&self) -> PSP34EventKind {
+        match self {
+            PSP34Event::Transfer { .. } => PSP34EventKind::Transfer,
+            PSP34Event::Approval { .. } => PSP34EventKind::Approval,
+            PSP34Event::AttributeSet { .. } => PSP34EventKind::AttributeSet,
+            PSP34Event::TransferOffered { .. } => PSP34EventKind::TransferOffered,
+            PSP34Event::TransferClaimed { .. } => PSP34EventKind::TransferClaimed,
+            PSP34Event::TransferCancelled { .. } => PSP34EventKind::TransferCancelled,
+            PSP34Event::ProvenanceSet { .. } => PSP34EventKind::ProvenanceSet,
+            PSP34Event::Minted { .. } => PSP34EventKind::Minted,
+            PSP34Event::Burned { .. } => PSP34EventKind::Burned,
+            PSP34Event::TransferMemo { .. } => PSP34EventKind::TransferMemo,
+            PSP34Event::TokenRescued { .. } => PSP34EventKind::TokenRescued,
+            PSP34Event::TeleportedOut { .. } => PSP34EventKind::TeleportedOut,
+            PSP34Event::TeleportedIn { .. } => PSP34EventKind::TeleportedIn,
+            PSP34Event::BalanceChanged { .. } => PSP34EventKind::BalanceChanged,
+            PSP34Event::Listed { .. } => PSP34EventKind::Listed,
+            PSP34Event::Delisted { .. } => PSP34EventKind::Delisted,
+            PSP34Event::Sold { .. } => PSP34EventKind::Sold,
+            PSP34Event::OfferMade { .. } => PSP34EventKind::OfferMade,
+            PSP34Event::OfferCancelled { .. } => PSP34EventKind::OfferCancelled,
+            PSP34Event::OfferAccepted { .. } => PSP34EventKind::OfferAccepted,
+            PSP34Event::AuctionCreated { .. } => PSP34EventKind::AuctionCreated,
+            PSP34Event::BidPlaced { .. } => PSP34EventKind::BidPlaced,
+            PSP34Event::AuctionSettled { .. } => PSP34EventKind::AuctionSettled,
+            PSP34Event::CollateralLocked { .. } => PSP34EventKind::CollateralLocked,
+            PSP34Event::CollateralReleased { .. } => PSP34EventKind::CollateralReleased,
+            PSP34Event::CollateralSeized { .. } => PSP34EventKind::CollateralSeized,
+            PSP34Event::BundleCreated { .. } => PSP34EventKind::BundleCreated,
+            PSP34Event::Unbundled { .. } => PSP34EventKind::Unbundled,
+            PSP34Event::Crafted { .. } => PSP34EventKind::Crafted,
+        }
+    }
+}
+
+/// Events emitted by a single operation of `PSP34Data`, in the order they happened.
+///
+/// Most operations emit exactly one event, which is kept inline without allocating.
+/// Dereferences to a slice of events and can be iterated like a `Vec`.
+#[derive(Debug, Default, Clone)]
+pub struct EventList(EventListInner);
+
+#[derive(Debug, Default, Clone)]
+enum EventListInner {
+    #[default]
+    Empty,
+    One(PSP34Event),
+    Many(Vec<PSP34Event>),
+}
+
+impl EventList {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, event: PSP34Event) {
+        self.0 = match core::mem::take(&mut self.0) {
+            EventListInner::Empty => EventListInner::One(event),
+            EventListInner::One(first) => EventListInner::Many(vec![first, event]),
+            EventListInner::Many(mut events) => {
+                events.push(event);
+                EventListInner::Many(events)
+            }
+        };
+    }
+
+    pub fn into_vec(self) -> Vec<PSP34Event> {
+        self.into_iter().collect()
+    }
+}
+
+impl core::ops::Deref for EventList {
+    type Target = [PSP34Event];
+
+    fn deref(&self) -> &[PSP34Event] {
+        match &self.0 {
+            EventListInner::Empty => &[],
+            EventListInner::One(event) => core::slice::from_ref(event),
+            EventListInner::Many(events) => events,
+        }
+    }
+}
+
+impl PartialEq for EventList {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for EventList {}
+
+impl PartialEq<Vec<PSP34Event>> for EventList {
+    fn eq(&self, other: &Vec<PSP34Event>) -> bool {
+        **self == **other
+    }
+}
+
+impl From<PSP34Event> for EventList {
+    fn from(event: PSP34Event) -> Self {
+        EventList(EventListInner::One(event))
+    }
+}
+
+impl From<Vec<PSP34Event>> for EventList {
+    fn from(events: Vec<PSP34Event>) -> Self {
+        events.into_iter().collect()
+    }
+}
+
+impl Extend<PSP34Event> for EventList {
+    fn extend<T: IntoIterator<Item = PSP34Event>>(&mut self, events: T) {
+        for event in events {
+            self.push(event);
+        }
+    }
+}
+
+impl FromIterator<PSP34Event> for EventList {
+    fn from_iter<T: IntoIterator<Item = PSP34Event>>(events: T) -> Self {
+        let mut list = EventList::new();
+        list.extend(events);
+        list
+    }
+}
+
+impl IntoIterator for EventList {
+    type Item = PSP34Event;
+    type IntoIter = EventListIter;
+
+    fn into_iter(self) -> EventListIter {
+        EventListIter(match self.0 {
+            EventListInner::Empty => EventListIterInner::One(None),
+            EventListInner::One(event) => EventListIterInner::One(Some(event)),
+            EventListInner::Many(events) => EventListIterInner::Many(events.into_iter()),
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a EventList {
+    type Item = &'a PSP34Event;
+    type IntoIter = core::slice::Iter<'a, PSP34Event>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An owning iterator over the events of an `EventList`.
+pub struct EventListIter(EventListIterInner);
+
+enum EventListIterInner {
+    One(Option<PSP34Event>),
+    Many(vec::IntoIter<PSP34Event>),
+}
+
+impl Iterator for EventListIter {
+    type Item = PSP34Event;
+
+    fn next(&mut self) -> Option<PSP34Event> {
+        match &mut self.0 {
+            EventListIterInner::One(event) => event.take(),
+            EventListIterInner::Many(events) => events.next(),
+        }
+    }
+}
+
+/// A transfer of a token offered by its owner `from`, waiting to be claimed by `to`.
+/// Cannot be claimed after the block number `deadline`, if one is set.
+#[derive(Debug, PartialEq, Eq, Clone, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
+pub struct PendingTransfer {
+    pub from: AccountId,
+    pub to: AccountId,
+    pub deadline: Option<u32>,
+}
+
+/// Maximal length in bytes of a memo stored with `transfer_with_memo`.
+pub const MAX_MEMO_LEN: usize = 256;
+
+/// Maximal number of entries returned by `all_tokens_with_owners_page`.
+pub const MAX_PAGE_SIZE: u32 = 64;
+
+/// Version of the storage layout of `PSP34Data` written by this version of the crate.
+pub const CURRENT_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Steps of `PSP34Data::migrate`, where the step at index `i` upgrades the storage
+/// from version `i` to version `i + 1`. New steps are only ever appended.
+const MIGRATIONS: &[fn(&mut PSP34Data)] = &[
+    // 0 -> 1: the storage version is introduced. The first release only counted the supply,
+    // so every token in supply is taken as minted.
+    |data| {
+        let minted = data.minted_count().max(data.total_supply());
+        data.minted_count.set(&minted);
+        // Nor did it list the holders.
+        #[cfg(feature = "enumerable")]
+        {
+            let token_owner = &data.token_owner;
+            data.balance.list_holders(|id| token_owner.get(id));
+        }
+    },
+    // 1 -> 2: `burned_count` is introduced, every minted token not in supply was burned.
+    |data| {
+        let burned = data.minted_count().saturating_sub(data.total_supply());
+        data.burned_count.set(&burned)
+    },
+    // 2 -> 3: the count of owners is introduced. With the `enumerable` feature it is the
+    // length of the list of holders, without it the owners are not listed, so the count
+    // of the existing ones cannot be derived and is marked unknown.
+    |data| {
+        #[cfg(not(feature = "enumerable"))]
+        if data.total_supply() > 0 {
+            data.balance.forget_owner_count();
+        }
+        #[cfg(feature = "enumerable")]
+        let _ = data;
+    },
+];
+
+/// The limits configured with `PSP34DataBuilder`, `None` where there is no limit.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct Limits {
+    pub max_supply: Option<u128>,
+    pub max_balance: Option<u32>,
+}
+
+/// Operations allowed in the collection, fixed when `PSP34Data` is created.
+/// By default everything is allowed. Minting is never affected.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, StorageLayout))]
+pub struct Config {
+    /// Whether tokens can be transferred, otherwise they are soulbound to the minted account.
+    pub transferable: bool,
+    /// Whether tokens can be burned by their owners and approved operators.
+    pub burnable: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            transferable: true,
+            burnable: true,
+        }
+    }
+}
+
+/// A class implementing the internal logic of a PSP34 token.
+//
+/// Holds the state of all account balances and approvals.
+/// Each method of this class corresponds to one type of transaction
+/// as defined in the PSP34 standard.
+//
+/// Since this code is outside of `ink::contract` macro, the caller's
+/// address cannot be obtained automatically. Because of that, all
+/// the methods that need to know the caller require an additional argument
+/// (compared to transactions defined by the PSP34 standard or the PSP34 trait).
+//
+/// `lib.rs` contains an example implementation of a smart contract using this class.
+//
+/// Only the fields of the first release are encoded inline with the contract's storage.
+/// Every field added later is kept in its own `Lazy` cell, so the storage written by
+/// an older version still decodes after a code upgrade and can be upgraded with `migrate`.
+#[ink::storage_item]
+#[derive(Debug, Default)]
+pub struct PSP34Data {
+    token_owner: Mapping<Id, AccountId>,
+    #[allow(clippy::type_complexity)]
+    operator_approvals: Mapping<(AccountId, AccountId, Option<Id>), ()>,
+    balance: Balances,
+    minted_at: Mapping<Id, (u32, u64)>,
+    next_id: Lazy<u128>,
+    transfer_count: Mapping<Id, u32>,
+    pending_transfers: Mapping<Id, PendingTransfer>,
+    #[allow(clippy::type_complexity)]
+    approval_uses: Mapping<(AccountId, AccountId, Option<Id>), u32>,
+    minted_count: Lazy<u128>,
+    provenance_hash: Lazy<Option<[u8; 32]>>,
+    escrows: Mapping<AccountId, ()>,
+    max_supply: Lazy<Option<u128>>,
+    max_balance: Lazy<Option<u32>>,
+    normalize_ids: Lazy<bool>,
+    storage_version: Lazy<u32>,
+    mint_burn_events: Lazy<MintBurnEvents>,
+    config: Lazy<Config>,
+    balance_limit_exempt: Mapping<AccountId, ()>,
+    #[allow(clippy::type_complexity)]
+    memos: Mapping<(Id, u32), Vec<u8>>,
+    self_transfer_mode: Lazy<SelfTransferMode>,
+    burned_count: Lazy<u128>,
+    teleport_mode: Lazy<TeleportMode>,
+    bridge_authority: Lazy<Option<AccountId>>,
+    teleport_nonce: Lazy<u64>,
+    outbound_teleports: Mapping<u64, OutboundTeleport>,
+    locked_tokens: Mapping<Id, ()>,
+    holds: Mapping<Id, TokenHold>,
+    collection_id: Lazy<Option<Id>>,
+    random_remap: Mapping<u128, u128>,
+    random_drawn: Lazy<u128>,
+    keep_approvals_on_transfer: Lazy<bool>,
+    balance_change_events: Lazy<bool>,
+    reject_zero_address: Lazy<bool>,
+}
+
+/// Returns whether `account` is the zero address, i.e. all of its bytes are zero.
+/// No one holds its key, so a token sent there is lost.
+pub fn is_zero(account: &AccountId) -> bool {
+    account.as_ref() == [0u8; 32]
+}
+
+// Returns `id` as stored with `normalize_ids` set or not, see `PSP34Data::normalize_id`.
+fn normalized(id: Id, normalize_ids: bool) -> Id {
+    if !normalize_ids {
+        return id;
+    }
+    match id {
+        Id::U8(value) => Id::U128(value.into()),
+        Id::U16(value) => Id::U128(value.into()),
+        Id::U32(value) => Id::U128(value.into()),
+        Id::U64(value) => Id::U128(value.into()),
+        Id::U256(bytes) if bytes[..16].iter().all(|byte| *byte == 0) => {
+            Id::U128(u128::from_be_bytes(bytes[16..].try_into().unwrap()))
+        }
+        Id::U128(_) | Id::U256(_) | Id::Bytes(_) => id,
+    }
+}
+
+// Checks that `account` is not the zero address, if `reject_zero_address` is set.
+fn check_recipient(reject_zero_address: bool, account: &AccountId) -> Result<(), PSP34Error> {
+    if reject_zero_address && is_zero(account) {
+        return Err(PSP34Error::Custom(String::from(
+            "Recipient is the zero address.",
+        )));
+    }
+    Ok(())
+}
+
+// The settings checked by every transfer. They are read from their storage cells once
+// per call, so that a batch does not read them again for each of its tokens.
+struct TransferRules {
+    config: Config,
+    reject_zero_address: bool,
+    self_transfer_mode: SelfTransferMode,
+}
+
+/// A builder of `PSP34Data` with configured limits.
+/// Options which are not set keep their defaults, i.e. no limits.
+#[derive(Debug, Default)]
+pub struct PSP34DataBuilder {
+    max_supply: Option<u128>,
+    max_balance: Option<u32>,
+    normalize_ids: bool,
+    mint_burn_events: MintBurnEvents,
+    config: Config,
+    self_transfer_mode: SelfTransferMode,
+    teleport_mode: TeleportMode,
+    keep_approvals_on_transfer: bool,
+    balance_change_events: bool,
+    reject_zero_address: bool,
+}
+
+impl PSP34DataBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Limits the total supply of the token to `max_supply`.
+    pub fn max_supply(mut self, max_supply: u128) -> Self {
+        self.max_supply = Some(max_supply);
+        self
+    }
+
+    /// Limits the number of tokens a single account can hold to `max_balance`.
+    pub fn max_balance(mut self, max_balance: u32) -> Self {
+        self.max_balance = Some(max_balance);
+        self
+    }
+
+    /// Makes numeric ids of the same value refer to the same token, regardless of
+    /// their variant, by storing all of them as `Id::U128`. See `PSP34Data::normalize_id`.
+    pub fn normalize_ids(mut self, enabled: bool) -> Self {
+        self.normalize_ids = enabled;
+        self
+    }
+
+    /// Selects the events emitted on mint and burn, see `MintBurnEvents`.
+    pub fn mint_burn_events(mut self, mint_burn_events: MintBurnEvents) -> Self {
+        self.mint_burn_events = mint_burn_events;
+        self
+    }
+
+    /// Sets the operations allowed in the collection, see `Config`.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Selects how a transfer of a token to its current owner is handled.
+    pub fn self_transfer_mode(mut self, self_transfer_mode: SelfTransferMode) -> Self {
+        self.self_transfer_mode = self_transfer_mode;
+        self
+    }
+
+    /// Selects how `teleport_out` takes a token off this chain.
+    pub fn teleport_mode(mut self, teleport_mode: TeleportMode) -> Self {
+        self.teleport_mode = teleport_mode;
+        self
+    }
+
+    /// Selects whether a transfer by an operator removes the operator's approval for
+    /// the transferred token, like in ERC-721, which is the default. A kept approval
+    /// stays granted by the previous owner, so it has no effect until the token comes
+    /// back to that owner, and is not transferred to the new owner.
+    pub fn clear_approval_on_transfer(mut self, enabled: bool) -> Self {
+        self.keep_approvals_on_transfer = !enabled;
+        self
+    }
+
+    /// Makes every transfer, mint and burn additionally emit a `BalanceChanged` event
+    /// with the new balance of each account involved, after its `Transfer` event.
+    pub fn balance_change_events(mut self, enabled: bool) -> Self {
+        self.balance_change_events = enabled;
+        self
+    }
+
+    /// Makes mints and transfers to the zero address, see `is_zero`, fail.
+    pub fn reject_zero_address(mut self, enabled: bool) -> Self {
+        self.reject_zero_address = enabled;
+        self
+    }
+
+    /// Writes the configuration to the storage cells of a new `PSP34Data`.
+    pub fn build(self) -> PSP34Data {
+        let mut data = PSP34Data::default();
+        data.storage_version.set(&CURRENT_VERSION);
+        data.max_supply.set(&self.max_supply);
+        data.max_balance.set(&self.max_balance);
+        data.normalize_ids.set(&self.normalize_ids);
+        data.mint_burn_events.set(&self.mint_burn_events);
+        data.config.set(&self.config);
+        data.self_transfer_mode.set(&self.self_transfer_mode);
+        data.teleport_mode.set(&self.teleport_mode);
+        data.keep_approvals_on_transfer
+            .set(&self.keep_approvals_on_transfer);
+        data.balance_change_events.set(&self.balance_change_events);
+        data.reject_zero_address.set(&self.reject_zero_address);
+        data
+    }
+}
+
+impl PSP34Data {
+    /// Creates a token with default values for every field.
+    /// Initially held by the 'creator' account.
+    pub fn new() -> PSP34Data {
+        PSP34DataBuilder::new().build()
+    }
+
+    /// Creates a token allowing only the operations enabled in `config`.
+    /// The configuration cannot be changed later.
+    pub fn new_with_config(config: Config) -> PSP34Data {
+        PSP34DataBuilder::new().config(config).build()
+    }
+
+    /// Returns the operations allowed in the collection.
+    pub fn config(&self) -> Config {
+        self.config.get_or_default()
+    }
+
+    /// Returns how a transfer of a token to its current owner is handled.
+    pub fn self_transfer_mode(&self) -> SelfTransferMode {
+        self.self_transfer_mode.get_or_default()
+    }
+
+    /// Returns whether a transfer by an operator removes the operator's approval
+    /// for the transferred token.
+    pub fn clear_approval_on_transfer(&self) -> bool {
+        !self.keep_approvals_on_transfer.get_or_default()
+    }
+
+    /// Returns whether mints and transfers to the zero address fail.
+    pub fn reject_zero_address(&self) -> bool {
+        self.reject_zero_address.get_or_default()
+    }
+
+    /// Returns the version of the storage layout, see `migrate`.
+    pub fn storage_version(&self) -> u32 {
+        self.storage_version.get_or_default()
+    }
+
+    #[cfg(test)]
+    pub fn set_storage_version(&mut self, version: u32) {
+        self.storage_version.set(&version);
+    }
+
+    /// Upgrades the storage written by an older version of the crate to `CURRENT_VERSION`,
+    /// running each missing migration step once. Should be called after the code of
+    /// a contract is upgraded. Does nothing if the storage is already current.
+    /// Returns the resulting version.
+    pub fn migrate(&mut self) -> Result<u32, PSP34Error> {
+        self.migrate_with(MIGRATIONS)
+    }
+
+    fn migrate_with(&mut self, steps: &[fn(&mut PSP34Data)]) -> Result<u32, PSP34Error> {
+        let from = self.storage_version() as usize;
+        if from > steps.len() {
+            return Err(PSP34Error::Custom(String::from(
+                "Storage version is newer than supported by the code.",
+            )));
+        }
+        for step in &steps[from..] {
+            step(self);
+        }
+        let version = steps.len() as u32;
+        self.storage_version.set(&version);
+        Ok(version)
+    }
+
+    pub fn total_supply(&self) -> u128 {
+        self.balance.total_supply()
+    }
+
+    /// Returns the form in which token `id` is stored. With id normalization enabled,
+    /// numeric ids are converted to `Id::U128`, so e.g. `Id::U8(1)` and `Id::U64(1)` are
+    /// the same token. An `Id::U256` too large for `u128` and `Id::Bytes` are never changed.
+    ///
+    /// All methods taking an id normalize it, but the contract should use this method
+    /// for ids it stores elsewhere, e.g. as keys of token attributes.
+    pub fn normalize_id(&self, id: Id) -> Id {
+        normalized(id, self.normalize_ids.get_or_default())
+    }
+
+    /// Returns which events are emitted on mint and burn.
+    pub fn mint_burn_events(&self) -> MintBurnEvents {
+        self.mint_burn_events.get_or_default()
+    }
+
+    /// Returns the limit of the total supply, if one is configured.
+    pub fn max_supply(&self) -> Option<u128> {
+        self.max_supply.get_or_default()
+    }
+
+    /// Returns all the configured limits at once.
+    pub fn limits(&self) -> Limits {
+        Limits {
+            max_supply: self.max_supply(),
+            max_balance: self.max_balance(),
+        }
+    }
+
+    /// Returns whether the supply limit is configured and reached.
+    pub fn is_sold_out(&self) -> bool {
+        self.max_supply()
+            .is_some_and(|max_supply| self.total_supply() >= max_supply)
+    }
+
+    /// Returns the limit of the number of tokens held by one account, if one is configured.
+    pub fn max_balance(&self) -> Option<u32> {
+        self.max_balance.get_or_default()
+    }
+
+    /// Raises the limit of the number of tokens held by one account to `max_balance`.
+    /// The limit can never be lowered or removed, so no holder ends up above it.
+    /// Meant for the contract's admin, so the caller has to be checked by the contract.
+    pub fn raise_max_balance(&mut self, max_balance: u32) -> Result<(), PSP34Error> {
+        match self.max_balance() {
+            Some(current) if current <= max_balance => {
+                self.max_balance.set(&Some(max_balance));
+                Ok(())
+            }
+            _ => Err(PSP34Error::Custom(String::from(
+                "Balance limit can only be raised.",
+            ))),
+        }
+    }
+
+    /// Returns whether `account` can hold more tokens than the balance limit.
+    pub fn is_balance_limit_exempt(&self, account: AccountId) -> bool {
+        self.balance_limit_exempt.contains(account)
+    }
+
+    /// Exempts `account` from the balance limit, e.g. a marketplace escrow or the
+    /// contract itself, or removes the exemption if `exempt` is false. Meant for
+    /// the contract's admin, so the caller has to be checked by the contract.
+    pub fn set_balance_limit_exempt(&mut self, account: AccountId, exempt: bool) {
+        if exempt {
+            self.balance_limit_exempt.insert(account, &());
+        } else {
+            self.balance_limit_exempt.remove(account);
+        }
+    }
+
+    pub fn balance_of(&self, owner: AccountId) -> u32 {
+        self.balance.balance_of(&owner)
+    }
+
+    pub fn owner_of(&self, id: &Id) -> Option<AccountId> {
+        let id = &self.normalize_id(id.clone());
+        self.token_owner.get(id)
+    }
+
+    /// Returns whether `operator` can transfer token `id` of `owner`, or all tokens of
+    /// `owner` if no `id` is provided. Always false for an `id` which does not exist
+    /// or is not owned by `owner`, even if `operator` is approved for all tokens.
+    pub fn allowance(&self, owner: AccountId, operator: AccountId, id: Option<&Id>) -> bool {
+        let id = id.map(|id| self.normalize_id(id.clone()));
+        let id = id.as_ref();
+        if id.is_some_and(|id| self.token_owner.get(id) != Some(owner)) {
+            return false;
+        }
+        self.operator_approvals
+            .get((owner, operator, &None))
+            .is_some()
+            || id.is_some() && self.operator_approvals.get((owner, operator, id)).is_some()
+    }
+
+    /// Returns whether the approval granted by `owner` to `operator` for exactly `id`
+    /// is stored. Unlike `allowance`, an approval for all tokens does not count when
+    /// `id` is `Some`.
+    pub fn has_exact_approval(
+        &self,
+        owner: AccountId,
+        operator: AccountId,
+        id: Option<&Id>,
+    ) -> bool {
+        let id = id.map(|id| self.normalize_id(id.clone()));
+        let id = id.as_ref();
+        self.operator_approvals.contains((owner, operator, id))
+    }
+
+    /// Returns how many more transfers the approval granted by `owner` to `operator`
+    /// for token `id` (or all tokens) allows, or `None` if it is not limited.
+    pub fn approval_uses(
+        &self,
+        owner: AccountId,
+        operator: AccountId,
+        id: Option<&Id>,
+    ) -> Option<u32> {
+        let id = id.map(|id| self.normalize_id(id.clone()));
+        let id = id.as_ref();
+        self.approval_uses.get((owner, operator, id))
+    }
+
+    /// Returns the number and the timestamp of the block in which token `id` was minted,
+    /// or `None` if the token does not exist.
+    pub fn minted_at(&self, id: &Id) -> Option<(u32, u64)> {
+        let id = &self.normalize_id(id.clone());
+        self.minted_at.get(id)
+    }
+
+    /// Returns how many times token `id` has been transferred since it was minted.
+    pub fn transfer_count(&self, id: &Id) -> u32 {
+        let id = &self.normalize_id(id.clone());
+        self.transfer_count.get(id).unwrap_or(0)
+    }
+
+    /// Returns the transfer of token `id` waiting to be claimed, if there is one.
+    pub fn pending_transfer(&self, id: &Id) -> Option<PendingTransfer> {
+        let id = &self.normalize_id(id.clone());
+        self.pending_transfers.get(id)
+    }
+
+    /// Returns the extension holding token `id`, if there is one.
+    pub fn hold_of(&self, id: &Id) -> Option<TokenHold> {
+        let id = &self.normalize_id(id.clone());
+        self.holds.get(id)
+    }
+
+    /// Puts token `id` on `hold` for an extension. The caller is responsible for checking
+    /// that the owner agreed to it. Fails if the token is already held, offered or locked.
+    pub fn hold(&mut self, id: &Id, hold: TokenHold) -> Result<(), PSP34Error> {
+        let id = &self.normalize_id(id.clone());
+        if !self.token_owner.contains(id) {
+            return Err(PSP34Error::TokenNotExists);
+        }
+        self.ensure_not_offered(id)?;
+        self.ensure_not_locked(id)?;
+        self.holds.insert(id, &hold);
+        Ok(())
+    }
+
+    /// Releases token `id` from the hold of an extension.
+    /// Moving the token with `transfer_unchecked` or burning it releases it as well.
+    pub fn release(&mut self, id: &Id) {
+        let id = &self.normalize_id(id.clone());
+        self.holds.remove(id);
+    }
+
+    /// Checks that token `id` has no pending transfer offer, is not locked in the bridge
+    /// and is not held by an extension, so that its owner could move or burn it.
+    /// Extensions can use it before moving a token with the unchecked methods.
+    pub fn ensure_movable(&self, id: &Id) -> Result<(), PSP34Error> {
+        let id = &self.normalize_id(id.clone());
+        self.ensure_not_offered(id)?;
+        self.ensure_not_locked(id)
+    }
+
+    /// Returns the number of tokens minted so far, including the burned ones.
+    pub fn minted_count(&self) -> u128 {
+        self.minted_count.get_or_default()
+    }
+
+    /// Returns the number of tokens burned so far. Minting a burned id again does not
+    /// change it, so `minted_count() == total_supply() + total_burned()` always holds.
+    pub fn total_burned(&self) -> u128 {
+        self.burned_count.get_or_default()
+    }
+
+    /// Returns the number of distinct accounts owning at least one token. Without the
+    /// `enumerable` feature it is `None` for storage migrated from a version which did not
+    /// count the owners while tokens existed, until no token is left.
+    pub fn owner_count(&self) -> Option<u32> {
+        self.balance.owner_count()
+    }
+
+    /// Returns the provenance hash committed to before minting, if it was set.
+    pub fn provenance_hash(&self) -> Option<[u8; 32]> {
+        self.provenance_hash.get_or_default()
+    }
+
+    /// Commits to the provenance `hash` of the collection's content.
+    /// It can be set only once and only before the first token is minted.
+    pub fn set_provenance_hash(&mut self, hash: [u8; 32]) -> Result<EventList, PSP34Error> {
+        if self.provenance_hash().is_some() {
+            return Err(PSP34Error::Custom(String::from(
+                "Provenance hash is already set.",
+            )));
+        }
+        if self.minted_count() != 0 {
+            return Err(PSP34Error::Custom(String::from(
+                "Provenance hash cannot be set after minting has started.",
+            )));
+        }
+        self.provenance_hash.set(&Some(hash));
+        Ok(EventList::from(PSP34Event::ProvenanceSet { hash }))
+    }
+
+    /// Returns the id that will be assigned to the first token of the next sequential mint.
+    pub fn next_id(&self) -> u128 {
+        self.next_id.get_or_default()
+    }
+
+    /// Returns the id set with `set_collection_id`, or the id derived from the contract's
+    /// `account_id` if none was set.
+    pub fn collection_id(&self, account_id: AccountId) -> Id {
+        self.collection_id
+            .get_or_default()
+            .unwrap_or_else(|| Id::Bytes(<_ as AsRef<[u8; 32]>>::as_ref(&account_id).to_vec()))
+    }
+
+    /// Sets the id of the collection, replacing the one derived from the contract's account.
+    /// It can be set only once. The collection attributes written under the derived id
+    /// stay there, so it should be set before them.
+    pub fn set_collection_id(&mut self, id: Id) -> Result<(), PSP34Error> {
+        if self.collection_id.get_or_default().is_some() {
+            return Err(PSP34Error::Custom(String::from(
+                "Collection id is already set.",
+            )));
+        }
+        self.collection_id.set(&Some(id));
+        Ok(())
+    }
+
+    /// Sets a new `approved` for a token `id` or for all tokens if no `id` is provided,
+    /// granted by `caller` to `operator`.
+    /// Overwrites the previously granted value.
+    ///
+    /// Approving for all tokens keeps the operator's single token approvals, which
+    /// take effect again once the approval for all tokens is revoked.
+    /// A call which would leave the approval as it is, i.e. granting an unlimited approval
+    /// which is already in place or revoking an approval which is not, succeeds without
+    /// changing anything and emits no event. This applies to approvals for single tokens
+    /// and for all tokens alike.
+    ///
+    /// An operator approved for all tokens of an owner can grant and revoke approvals for
+    /// single tokens of that owner, which are then recorded as granted by the owner.
+    /// It cannot grant an approval for all tokens on the owner's behalf: without an `id`
+    /// the approval is always granted by `caller` for `caller`'s own tokens.
+    pub fn approve(
+        &mut self,
+        mut caller: AccountId,
+        operator: AccountId,
+        id: Option<Id>,
+        approved: bool,
+    ) -> Result<EventList, PSP34Error> {
+        let id = id.map(|id| self.normalize_id(id));
+        if let Some(id) = &id {
+            let owner = self.owner_of(id).ok_or(PSP34Error::TokenNotExists)?;
+            if approved && owner == operator {
+                return Err(PSP34Error::SelfApprove);
+            }
+
+            if owner != caller && !self.allowance(owner, caller, None) {
+                return Err(PSP34Error::NotApproved);
+            }
+
+            if !approved && self.allowance(owner, operator, None) {
+                return Err(PSP34Error::Custom(String::from(
+                    "Cannot revoke approval for a single token, when the operator has approval for all tokens."
+                )));
+            }
+            caller = owner;
+        }
+        let granted = self
+            .operator_approvals
+            .contains((caller, operator, id.as_ref()));
+        let limited = self.approval_uses.contains((caller, operator, id.as_ref()));
+        if approved == granted && (!approved || !limited) {
+            return Ok(EventList::new());
+        }
+
+        if approved {
+            self.operator_approvals
+                .insert((caller, operator, id.as_ref()), &());
+        } else {
+            self.operator_approvals
+                .remove((caller, operator, id.as_ref()));
+        }
+        self.approval_uses.remove((caller, operator, id.as_ref()));
+
+        Ok(EventList::from(PSP34Event::Approval {
+            owner: caller,
+            operator,
+            id,
+            approved,
+        }))
+    }
+
+    /// Revokes the approval of `operator` for token `id`, conducted by `caller`, who must
+    /// be the owner or approved for all of the owner's tokens. Unlike `approve` with
+    /// `approved` set to false, fails with `PSP34Error::ApprovedForAll` if `operator`
+    /// is approved for all tokens of the owner, since it could still move the token.
+    /// Resetting an approval which was not granted changes nothing.
+    pub fn reset_approval(
+        &mut self,
+        caller: AccountId,
+        operator: AccountId,
+        id: Id,
+    ) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        let owner = self.owner_of(&id).ok_or(PSP34Error::TokenNotExists)?;
+        if owner != caller && !self.allowance(owner, caller, None) {
+            return Err(PSP34Error::NotApproved);
+        }
+        if self.allowance(owner, operator, None) {
+            return Err(PSP34Error::ApprovedForAll);
+        }
+        self.approve(caller, operator, Some(id), false)
+    }
+
+    /// Returns whether `account` accepts tokens with `transfer_with_approval`.
+    pub fn is_escrow(&self, account: AccountId) -> bool {
+        self.escrows.contains(account)
+    }
+
+    /// Registers `caller` as an escrow, or unregisters it if `enabled` is false.
+    /// An escrow consents to approving the previous owner of every token it receives
+    /// with `transfer_with_approval`.
+    pub fn set_escrow(&mut self, caller: AccountId, enabled: bool) {
+        if enabled {
+            self.escrows.insert(caller, &());
+        } else {
+            self.escrows.remove(caller);
+        }
+    }
+
+    /// Transfers token `id` to `to` like `transfer` does. If `approve_back` is set,
+    /// `to` must be a registered escrow and the previous owner is approved for the token
+    /// on its behalf, so that either of them can move the token back later.
+    pub fn transfer_with_approval(
+        &mut self,
+        caller: AccountId,
+        to: AccountId,
+        id: Id,
+        approve_back: bool,
+        data: Vec<u8>,
+    ) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        if !approve_back {
+            return self.transfer(caller, to, id, data);
+        }
+        if !self.is_escrow(to) {
+            return Err(PSP34Error::Custom(String::from(
+                "Recipient is not a registered escrow.",
+            )));
+        }
+        let owner = self.owner_of(&id).ok_or(PSP34Error::TokenNotExists)?;
+        let mut events = self.transfer(caller, to, id.clone(), data)?;
+        // A token transferred to its owner stays with it, so there is nobody to approve.
+        if owner == to {
+            return Ok(events);
+        }
+        self.operator_approvals.insert((to, owner, Some(&id)), &());
+        events.push(PSP34Event::Approval {
+            owner: to,
+            operator: owner,
+            id: Some(id),
+            approved: true,
+        });
+        Ok(events)
+    }
+
+    /// Approves `operator` like `approve` does, but only for the next `uses` transfers
+    /// conducted by the operator. The approval is removed once they are used up.
+    ///
+    /// By default only approvals for all tokens can be used more than once, since a single
+    /// token approval is removed when the operator transfers the token. With
+    /// `clear_approval_on_transfer` disabled the single token approval is kept, and each
+    /// transfer conducted with it consumes one of its uses as well.
+    pub fn approve_limited(
+        &mut self,
+        caller: AccountId,
+        operator: AccountId,
+        id: Option<Id>,
+        uses: u32,
+    ) -> Result<EventList, PSP34Error> {
+        let id = id.map(|id| self.normalize_id(id));
+        if uses == 0 {
+            return Err(PSP34Error::Custom(String::from(
+                "Limited approval must allow at least one use.",
+            )));
+        }
+        let events = self.approve(caller, operator, id.clone(), true)?;
+        let owner = match &id {
+            Some(id) => self.owner_of(id).ok_or(PSP34Error::TokenNotExists)?,
+            None => caller,
+        };
+        self.approval_uses.insert((owner, operator, &id), &uses);
+        Ok(events)
+    }
+
+    /// Removes the given approvals of single tokens, as `(owner, operator, id)`, which
+    /// remained after their tokens were burned, emitting an `Approval` revocation for each.
+    /// Approvals are stored per owner and operator, so the entries to prune have to be
+    /// collected off-chain, e.g. from the `Approval` events. Entries of existing tokens
+    /// and entries which are not stored are skipped.
+    pub fn prune_approvals(&mut self, approvals: Vec<(AccountId, AccountId, Id)>) -> EventList {
+        let mut events = EventList::new();
+        for (owner, operator, id) in approvals {
+            let id = self.normalize_id(id);
+            if self.token_owner.contains(&id)
+                || !self
+                    .operator_approvals
+                    .contains((owner, operator, Some(&id)))
+            {
+                continue;
+            }
+            self.operator_approvals.remove((owner, operator, Some(&id)));
+            self.approval_uses.remove((owner, operator, Some(&id)));
+            events.push(PSP34Event::Approval {
+                owner,
+                operator,
+                id: Some(id),
+                approved: false,
+            });
+        }
+        events
+    }
+
+    /// Transfers `value` tokens from `caller` to `to`.
+    pub fn transfer(
+        &mut self,
+        caller: AccountId,
+        to: AccountId,
+        id: Id,
+        _data: Vec<u8>,
+    ) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        let owner = match self.validate_transfer(caller, to, &id)? {
+            Some(owner) => owner,
+            None => return Ok(self.self_transfer_events(to, id)),
+        };
+        self.ensure_balance_fits(to, 1)?;
+
+        let events = self.transfer_events(caller, owner, to, id.clone());
+        self.move_token(caller, owner, to, &id)?;
+        Ok(events)
+    }
+
+    // Returns the events of a transfer of token `id` from `owner` to `to` conducted by
+    // `caller`, computed before the token is moved, so that `simulate_transfer` reports
+    // exactly what `transfer` emits.
+    fn transfer_events(
+        &self,
+        caller: AccountId,
+        owner: AccountId,
+        to: AccountId,
+        id: Id,
+    ) -> EventList {
+        let mut events = EventList::from(PSP34Event::Transfer {
+            from: Some(caller),
+            to: Some(to),
+            id,
+        });
+        let mut balances = BTreeMap::new();
+        if self.balance_change_events.get_or_default() {
+            balances.insert(owner, self.balance_of(owner));
+            balances.insert(to, self.balance_of(to));
+        }
+        self.push_balance_change(&mut events, &mut balances, owner, false);
+        self.push_balance_change(&mut events, &mut balances, to, true);
+        events
+    }
+
+    /// Transfers token `id` owned by the contract itself, with the account `contract`,
+    /// to `to`, e.g. after it was sent to the contract by mistake, like `transfer_unchecked`.
+    /// Emits a `Transfer` event followed by `TokenRescued`. Fails if the contract does not
+    /// own the token, or holds it for an extension, e.g. as a component of a bundle.
+    /// Meant for the contract's admin, so the caller has to be checked by the contract.
+    pub fn rescue_token(
+        &mut self,
+        contract: AccountId,
+        id: Id,
+        to: AccountId,
+    ) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        if self.owner_of(&id).ok_or(PSP34Error::TokenNotExists)? != contract {
+            return Err(PSP34Error::Custom(String::from(
+                "Token is not owned by the contract.",
+            )));
+        }
+        self.ensure_movable(&id)?;
+        let mut events = self.transfer_unchecked(contract, to, id.clone())?;
+        if !events.is_empty() {
+            events.push(PSP34Event::TokenRescued { id, to });
+        }
+        Ok(events)
+    }
+
+    /// Transfers token `id` like `transfer` and stores `memo` for this transfer, to be read
+    /// with `memo`. The memo is keyed by the id and the value of `transfer_count` after the
+    /// transfer, which is reported in the `TransferMemo` event. A memo longer than
+    /// `MAX_MEMO_LEN` bytes is rejected. A transfer to the current owner stores nothing.
+    pub fn transfer_with_memo(
+        &mut self,
+        caller: AccountId,
+        to: AccountId,
+        id: Id,
+        memo: Vec<u8>,
+        data: Vec<u8>,
+    ) -> Result<EventList, PSP34Error> {
+        if memo.len() > MAX_MEMO_LEN {
+            return Err(PSP34Error::Custom(String::from("Memo is too long.")));
+        }
+        let id = self.normalize_id(id);
+        let previous = self.transfer_count(&id);
+        let mut events = self.transfer(caller, to, id.clone(), data)?;
+        let sequence = self.transfer_count(&id);
+        if sequence != previous {
+            self.memos.insert((&id, sequence), &memo);
+            events.push(PSP34Event::TransferMemo { id, sequence, memo });
+        }
+        Ok(events)
+    }
+
+    /// Returns the memo stored with the `sequence`-th transfer of token `id`, if any.
+    /// Memos are kept when the token is burned, so a token minted again with the same id
+    /// overwrites them as it is transferred.
+    pub fn memo(&self, id: &Id, sequence: u32) -> Option<Vec<u8>> {
+        let id = &self.normalize_id(id.clone());
+        self.memos.get((id, sequence))
+    }
+
+    /// Transfers token `id` like `transfer`, but only if the current block `timestamp`
+    /// does not exceed `deadline`. Returns `Expired` otherwise.
+    pub fn transfer_before(
+        &mut self,
+        caller: AccountId,
+        to: AccountId,
+        id: Id,
+        deadline: u64,
+        timestamp: u64,
+        data: Vec<u8>,
+    ) -> Result<EventList, PSP34Error> {
+        if timestamp > deadline {
+            return Err(PSP34Error::Expired);
+        }
+        self.transfer(caller, to, id, data)
+    }
+
+    /// Transfers multiple tokens, possibly belonging to different owners, conducted by `caller`.
+    /// Each `(from, to, id)` entry requires `from` to own `id` and `caller` to be `from`
+    /// or to be approved by `from`.
+    /// All the entries are validated before any token is moved, so either all of them
+    /// succeed or none does.
+    pub fn transfer_from_many(
+        &mut self,
+        caller: AccountId,
+        transfers: Vec<(AccountId, AccountId, Id)>,
+        _data: Vec<u8>,
+    ) -> Result<EventList, PSP34Error> {
+        let normalize_ids = self.normalize_ids.get_or_default();
+        let transfers: Vec<_> = transfers
+            .into_iter()
+            .map(|(from, to, id)| (from, to, normalized(id, normalize_ids)))
+            .collect();
+        let rules = self.transfer_rules();
+        // Every storage cell shared by the transfers is read once and written once:
+        // the approvals for all tokens and their uses are memoized per owner, and
+        // the balances are updated in `cache` and flushed at the end.
+        let mut approved_for_all = BTreeMap::new();
+        let mut uses: BTreeMap<AccountId, (u32, Option<u32>)> = BTreeMap::new();
+        let mut uses_for_all = Vec::with_capacity(transfers.len());
+        let mut cache = BalanceCache::default();
+        for (i, (from, to, id)) in transfers.iter().enumerate() {
+            if transfers[..i].iter().any(|(_, _, other)| other == id) {
+                return Err(PSP34Error::Custom(String::from(
+                    "Token id appears more than once in a batch transfer.",
+                )));
+            }
+            let owner = self
+                .validate_transfer_with(caller, *to, id, &rules, &mut approved_for_all)?
+                .unwrap_or(*to);
+            if owner != *from {
+                return Err(PSP34Error::NotApproved);
+            }
+            let for_all = from != to
+                && caller != owner
+                && approved_for_all.get(&owner) == Some(&true)
+                && !self.operator_approvals.contains((owner, caller, Some(id)));
+            if for_all {
+                let (used, remaining) = uses
+                    .entry(owner)
+                    .or_insert_with(|| (0, self.approval_uses(owner, caller, None)));
+                *used += 1;
+                if remaining.is_some_and(|remaining| *used > remaining) {
+                    return Err(PSP34Error::NotApproved);
+                }
+            }
+            if from != to {
+                self.balance.move_cached(&mut cache, from, to, id)?;
+            }
+            uses_for_all.push(for_all);
+        }
+
+        for (account, initial, balance) in cache.balances() {
+            // Like `transfer`, only limit the accounts whose balance grows.
+            if balance > initial {
+                self.ensure_balance_within_limit(account, balance)?;
+            }
+        }
+        let mut balances: BTreeMap<AccountId, u32> = BTreeMap::new();
+        if self.balance_change_events.get_or_default() {
+            balances.extend(
+                cache
+                    .balances()
+                    .map(|(account, initial, _)| (account, initial)),
+            );
+        }
+        self.balance.flush(cache);
+
+        let keep_approvals = self.keep_approvals_on_transfer.get_or_default();
+        let mut events = EventList::new();
+        for ((from, to, id), for_all) in transfers.into_iter().zip(uses_for_all) {
+            if from == to {
+                events.extend(self.self_transfer_events(to, id));
+                continue;
+            }
+            if caller != from && !for_all {
+                if keep_approvals {
+                    self.use_approval(from, caller, &id);
+                } else {
+                    self.operator_approvals.remove((from, caller, Some(&id)));
+                    self.approval_uses.remove((from, caller, Some(&id)));
+                }
+            }
+            self.token_owner.insert(&id, &to);
+            self.transfer_count.insert(
+                &id,
+                &self.transfer_count.get(&id).unwrap_or(0).saturating_add(1),
+            );
+            events.push(PSP34Event::Transfer {
+                from: Some(from),
+                to: Some(to),
+                id,
+            });
+            self.push_balance_change(&mut events, &mut balances, from, false);
+            self.push_balance_change(&mut events, &mut balances, to, true);
+        }
+        for (owner, (used, remaining)) in uses {
+            self.consume_approval_for_all(owner, caller, remaining, used);
+        }
+        Ok(events)
+    }
+
+    /// Returns the events of a transfer of token `id` to its current `owner`,
+    /// according to `self_transfer_mode`.
+    fn self_transfer_events(&self, owner: AccountId, id: Id) -> EventList {
+        match self.self_transfer_mode() {
+            SelfTransferMode::Emit => EventList::from(PSP34Event::Transfer {
+                from: Some(owner),
+                to: Some(owner),
+                id,
+            }),
+            SelfTransferMode::Noop | SelfTransferMode::Reject => EventList::new(),
+        }
+    }
+
+    // Appends a `BalanceChanged` event with the current balance of each of `accounts`,
+    // if enabled.
+    fn push_balance_changes(&self, events: &mut EventList, accounts: &[AccountId]) {
+        if self.balance_change_events.get_or_default() {
+            for account in accounts {
+                events.push(PSP34Event::BalanceChanged {
+                    account: *account,
+                    new_balance: self.balance_of(*account),
+                });
+            }
+        }
+    }
+
+    // Updates the balance of `account` tracked in `balances` by a received or sent token
+    // and appends its `BalanceChanged` event. Does nothing for untracked accounts,
+    // so the caller tracks balances only if the events are enabled.
+    fn push_balance_change(
+        &self,
+        events: &mut EventList,
+        balances: &mut BTreeMap<AccountId, u32>,
+        account: AccountId,
+        received: bool,
+    ) {
+        if let Some(balance) = balances.get_mut(&account) {
+            *balance = if received {
+                balance.saturating_add(1)
+            } else {
+                balance.saturating_sub(1)
+            };
+            events.push(PSP34Event::BalanceChanged {
+                account,
+                new_balance: *balance,
+            });
+        }
+    }
+
+    /// Moves token `id` from `owner` to `to`, clearing the approval of `caller` for it.
+    /// The balance of `to`, which is the only fallible step, is increased first, so
+    /// nothing is changed if it fails.
+    fn move_token(
+        &mut self,
+        caller: AccountId,
+        owner: AccountId,
+        to: AccountId,
+        id: &Id,
+    ) -> Result<(), PSP34Error> {
+        self.move_owner(owner, to, id)?;
+
+        if self.uses_approval_for_all(caller, owner, id) {
+            self.use_approval_for_all(owner, caller);
+        } else if !self.keep_approvals_on_transfer.get_or_default() {
+            self.operator_approvals.remove((owner, caller, Some(id)));
+            self.approval_uses.remove((owner, caller, Some(id)));
+        } else if caller != owner {
+            self.use_approval(owner, caller, id);
+        }
+        Ok(())
+    }
+
+    fn move_owner(&mut self, owner: AccountId, to: AccountId, id: &Id) -> Result<(), PSP34Error> {
+        self.balance.increase_balance(&to, id, false)?;
+        self.balance.decrease_balance(&owner, id, false);
+        self.token_owner.insert(id, &to);
+        self.transfer_count
+            .insert(id, &self.transfer_count(id).saturating_add(1));
+        Ok(())
+    }
+
+    /// Moves token `id` from `from` to `to` and returns the `Transfer` event, withdrawing
+    /// the pending transfer offer of the token, if any. Moving a token to its owner
+    /// changes nothing.
+    ///
+    /// For extension use only: neither the approvals, nor the `Config`, nor the offers
+    /// and the bridge locks are checked, so the extension has to authorize the transfer
+    /// itself. Still fails if `from` does not own the token or `to` would exceed
+    /// the balance limit.
+    pub fn transfer_unchecked(
+        &mut self,
+        from: AccountId,
+        to: AccountId,
+        id: Id,
+    ) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        if self.owner_of(&id).ok_or(PSP34Error::TokenNotExists)? != from {
+            return Err(PSP34Error::NotApproved);
+        }
+        if from == to {
+            return Ok(EventList::new());
+        }
+        self.ensure_valid_recipient(&to)?;
+        self.ensure_balance_fits(to, 1)?;
+        self.move_owner(from, to, &id)?;
+        self.pending_transfers.remove(&id);
+        self.holds.remove(&id);
+
+        let mut events = EventList::from(PSP34Event::Transfer {
+            from: Some(from),
+            to: Some(to),
+            id,
+        });
+        self.push_balance_changes(&mut events, &[from, to]);
+        Ok(events)
+    }
+
+    /// Offers token `id` to `to`, conducted by `caller`, who must be the owner or approved
+    /// for the token. The token stays with its owner, but cannot be transferred or burned
+    /// until the offer is claimed by `to` with `claim_transfer` or cancelled with
+    /// `cancel_transfer`. If `deadline` is set, the offer cannot be claimed after that
+    /// block number, but it keeps the token locked until cancelled.
+    pub fn offer_transfer(
+        &mut self,
+        caller: AccountId,
+        to: AccountId,
+        id: Id,
+        deadline: Option<u32>,
+        block_number: u32,
+    ) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        let from = self
+            .validate_transfer(caller, to, &id)?
+            .ok_or(PSP34Error::Custom(String::from(
+                "Cannot offer a token to its owner.",
+            )))?;
+        if deadline.is_some_and(|deadline| deadline < block_number) {
+            return Err(PSP34Error::Custom(String::from(
+                "Transfer offer deadline has already passed.",
+            )));
+        }
+        self.pending_transfers
+            .insert(&id, &PendingTransfer { from, to, deadline });
+
+        Ok(EventList::from(PSP34Event::TransferOffered {
+            from,
+            to,
+            id,
+            deadline,
+        }))
+    }
+
+    /// Completes the transfer of token `id` offered to `caller`, if its deadline has not
+    /// passed by the block number `block_number`.
+    /// Emits a `Transfer` event followed by `TransferClaimed`.
+    pub fn claim_transfer(
+        &mut self,
+        caller: AccountId,
+        id: Id,
+        block_number: u32,
+    ) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        let PendingTransfer { from, to, deadline } =
+            self.pending_transfers
+                .get(&id)
+                .ok_or(PSP34Error::Custom(String::from(
+                    "No pending transfer for the token.",
+                )))?;
+        if caller != to {
+            return Err(PSP34Error::NotApproved);
+        }
+        if deadline.is_some_and(|deadline| deadline < block_number) {
+            return Err(PSP34Error::Custom(String::from(
+                "Transfer offer has expired.",
+            )));
+        }
+        let mut events = self.transfer_unchecked(from, to, id.clone())?;
+        self.pending_transfers.remove(&id);
+        events.push(PSP34Event::TransferClaimed { from, to, id });
+        Ok(events)
+    }
+
+    /// Cancels the pending transfer of token `id`, conducted by `caller`, who must be
+    /// the owner or approved for the token. Expired offers can be cancelled as well.
+    pub fn cancel_transfer(&mut self, caller: AccountId, id: Id) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        let PendingTransfer { from, to, .. } =
+            self.pending_transfers
+                .get(&id)
+                .ok_or(PSP34Error::Custom(String::from(
+                    "No pending transfer for the token.",
+                )))?;
+        if caller != from && !self.allowance(from, caller, Some(&id)) {
+            return Err(PSP34Error::NotApproved);
+        }
+        self.pending_transfers.remove(&id);
+
+        Ok(EventList::from(PSP34Event::TransferCancelled {
+            from,
+            to,
+            id,
+        }))
+    }
+
+    /// Returns the account allowed to teleport tokens in, if one is set.
+    pub fn bridge_authority(&self) -> Option<AccountId> {
+        self.bridge_authority.get_or_default()
+    }
+
+    /// Sets the account allowed to teleport tokens in, which is responsible for verifying
+    /// that they were teleported out of the other chain. Meant for the contract's admin,
+    /// so the caller has to be checked by the contract.
+    pub fn set_bridge_authority(&mut self, authority: Option<AccountId>) {
+        self.bridge_authority.set(&authority);
+    }
+
+    /// Returns the outbound teleport recorded with `nonce`, if there is one.
+    pub fn outbound_teleport(&self, nonce: u64) -> Option<OutboundTeleport> {
+        self.outbound_teleports.get(nonce)
+    }
+
+    /// Returns whether token `id` is locked after being teleported out in `TeleportMode::Lock`.
+    pub fn is_locked(&self, id: &Id) -> bool {
+        let id = &self.normalize_id(id.clone());
+        self.locked_tokens.contains(id)
+    }
+
+    /// Sends token `id` of `caller` to `dest_account` on the chain `dest_chain`, burning or
+    /// locking it according to the `TeleportMode`. Records the teleport under a new nonce,
+    /// which is reported in the `TeleportedOut` event for the bridge to pick up.
+    /// Follows the `Config`: a non-transferable token cannot leave the chain, and in
+    /// `TeleportMode::Burn` neither can a non-burnable one.
+    pub fn teleport_out(
+        &mut self,
+        caller: AccountId,
+        id: Id,
+        dest_chain: u32,
+        dest_account: Vec<u8>,
+    ) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        if self.owner_of(&id).ok_or(PSP34Error::TokenNotExists)? != caller {
+            return Err(PSP34Error::NotApproved);
+        }
+        let config = self.config();
+        if !config.transferable {
+            return Err(PSP34Error::TransfersDisabled);
+        }
+        self.ensure_not_offered(&id)?;
+        self.ensure_not_locked(&id)?;
+
+        let mode = self.teleport_mode.get_or_default();
+        if mode == TeleportMode::Burn && !config.burnable {
+            return Err(PSP34Error::BurningDisabled);
+        }
+        let mut events = match mode {
+            TeleportMode::Burn => self.burn_unchecked(caller, id.clone())?,
+            TeleportMode::Lock => {
+                self.locked_tokens.insert(&id, &());
+                EventList::new()
+            }
+        };
+        let nonce = self.teleport_nonce.get_or_default();
+        self.teleport_nonce.set(&nonce.wrapping_add(1));
+        self.outbound_teleports.insert(
+            nonce,
+            &OutboundTeleport {
+                id: id.clone(),
+                from: caller,
+                dest_chain,
+                dest_account: dest_account.clone(),
+            },
+        );
+        events.push(PSP34Event::TeleportedOut {
+            id,
+            from: caller,
+            dest_chain,
+            dest_account,
+            nonce,
+        });
+        Ok(events)
+    }
+
+    /// Delivers token `id` teleported from another chain to `owner`, conducted by `caller`,
+    /// who must be the bridge authority. The `proof` is not verified here, that is left to
+    /// the authority. A locked token is unlocked and moved to `owner`, otherwise the token
+    /// is minted in the block with number `block_number` and timestamp `timestamp`.
+    pub fn teleport_in(
+        &mut self,
+        caller: AccountId,
+        id: Id,
+        owner: AccountId,
+        _proof: Vec<u8>,
+        block_number: u32,
+        timestamp: u64,
+    ) -> Result<EventList, PSP34Error> {
+        if self.bridge_authority() != Some(caller) {
+            return Err(PSP34Error::NotApproved);
+        }
+        let id = self.normalize_id(id);
+        let mut events = match self.owner_of(&id) {
+            Some(holder) if self.locked_tokens.contains(&id) => {
+                let events = self.transfer_unchecked(holder, owner, id.clone())?;
+                self.locked_tokens.remove(&id);
+                events
+            }
+            Some(_) => return Err(PSP34Error::TokenExists),
+            None => self.mint(owner, id.clone(), block_number, timestamp)?,
+        };
+        events.push(PSP34Event::TeleportedIn { id, to: owner });
+        Ok(events)
+    }
+
+    fn ensure_not_locked(&self, id: &Id) -> Result<(), PSP34Error> {
+        if self.locked_tokens.contains(id) {
+            return Err(PSP34Error::Custom(String::from(
+                "Token is locked in the bridge.",
+            )));
+        }
+        match self.holds.get(id) {
+            Some(TokenHold::Listed) => Err(PSP34Error::Custom(String::from(
+                "Token is listed for sale.",
+            ))),
+            Some(TokenHold::Auctioned) => {
+                Err(PSP34Error::Custom(String::from("Token is auctioned.")))
+            }
+            Some(TokenHold::Collateral) => Err(PSP34Error::Custom(String::from(
+                "Token is locked as collateral.",
+            ))),
+            Some(TokenHold::Bundled) => Err(PSP34Error::Custom(String::from("Token is bundled."))),
+            None => Ok(()),
+        }
+    }
+
+    fn ensure_not_offered(&self, id: &Id) -> Result<(), PSP34Error> {
+        if self.pending_transfers.contains(id) {
+            return Err(PSP34Error::Custom(String::from(
+                "Token has a pending transfer.",
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns whether a transfer of token `id` of `owner` conducted by `caller` relies on
+    /// the approval for all tokens rather than on the approval for the token itself.
+    fn uses_approval_for_all(&self, caller: AccountId, owner: AccountId, id: &Id) -> bool {
+        caller != owner && !self.operator_approvals.contains((owner, caller, Some(id)))
+    }
+
+    /// Decrements the remaining uses of a limited approval for all tokens,
+    /// removing the approval when none are left.
+    fn use_approval_for_all(&mut self, owner: AccountId, operator: AccountId) {
+        let remaining = self.approval_uses(owner, operator, None);
+        self.consume_approval_for_all(owner, operator, remaining, 1);
+    }
+
+    /// Decrements the remaining uses of a limited approval for token `id`, which is kept
+    /// on transfers, removing the approval when none are left.
+    fn use_approval(&mut self, owner: AccountId, operator: AccountId, id: &Id) {
+        match self.approval_uses.get((owner, operator, Some(id))) {
+            Some(remaining) if remaining <= 1 => {
+                self.operator_approvals.remove((owner, operator, Some(id)));
+                self.approval_uses.remove((owner, operator, Some(id)));
+            }
+            Some(remaining) => {
+                self.approval_uses
+                    .insert((owner, operator, Some(id)), &(remaining - 1));
+            }
+            None => {}
+        }
+    }
+
+    // Consumes `used` of the `remaining` uses of the approval for all tokens.
+    fn consume_approval_for_all(
+        &mut self,
+        owner: AccountId,
+        operator: AccountId,
+        remaining: Option<u32>,
+        used: u32,
+    ) {
+        match remaining {
+            Some(remaining) if remaining <= used => {
+                self.operator_approvals
+                    .remove((owner, operator, None::<Id>));
+                self.approval_uses.remove((owner, operator, None::<Id>));
+            }
+            Some(remaining) => {
+                self.approval_uses
+                    .insert((owner, operator, None::<Id>), &(remaining - used));
+            }
+            None => {}
+        }
+    }
+
+    /// Checks whether `caller` can transfer token `id` to `to` and returns
+    /// the kinds of events such transfer would emit, without changing the state.
+    pub fn simulate_transfer(
+        &self,
+        caller: AccountId,
+        to: AccountId,
+        id: &Id,
+    ) -> Result<Vec<PSP34EventKind>, PSP34Error> {
+        let id = self.normalize_id(id.clone());
+        let events = match self.validate_transfer(caller, to, &id)? {
+            Some(owner) => {
+                self.ensure_balance_fits(to, 1)?;
+                self.transfer_events(caller, owner, to, id)
+            }
+            None => self.self_transfer_events(to, id),
+        };
+        Ok(events.into_vec().iter().map(PSP34Event::kind).collect())
+    }
+
+    /// Returns the current owner of token `id` if `caller` is allowed to transfer it to `to`,
+    /// or `None` if the token already belongs to `to` and the transfer changes nothing.
+    /// Such transfer is always allowed with `SelfTransferMode::Noop`, while the other modes
+    /// require `caller` to be allowed to transfer the token.
+    /// The balance limit of `to` is left for the callers to check, since a batch
+    /// can receive and send tokens at the same time.
+    fn validate_transfer(
+        &self,
+        caller: AccountId,
+        to: AccountId,
+        id: &Id,
+    ) -> Result<Option<AccountId>, PSP34Error> {
+        let rules = self.transfer_rules();
+        self.validate_transfer_with(caller, to, id, &rules, &mut BTreeMap::new())
+    }
+
+    // Like `validate_transfer`, for an already normalized `id`, checking the `rules` read
+    // beforehand and looking up whether owners approved `caller` for all tokens in
+    // `approved_for_all` first and memoizing it there.
+    fn validate_transfer_with(
+        &self,
+        caller: AccountId,
+        to: AccountId,
+        id: &Id,
+        rules: &TransferRules,
+        approved_for_all: &mut BTreeMap<AccountId, bool>,
+    ) -> Result<Option<AccountId>, PSP34Error> {
+        if !rules.config.transferable {
+            return Err(PSP34Error::TransfersDisabled);
+        }
+        check_recipient(rules.reject_zero_address, &to)?;
+        let owner = self.token_owner.get(id).ok_or(PSP34Error::TokenNotExists)?;
+
+        if owner == to && rules.self_transfer_mode == SelfTransferMode::Noop {
+            return Ok(None);
+        }
+
+        if owner != caller
+            && !*approved_for_all.entry(owner).or_insert_with(|| {
+                self.operator_approvals
+                    .contains((owner, caller, None::<Id>))
+            })
+            && !self.operator_approvals.contains((owner, caller, Some(id)))
+        {
+            return Err(PSP34Error::NotApproved);
+        }
+        if owner == to {
+            return match rules.self_transfer_mode {
+                SelfTransferMode::Reject => Err(PSP34Error::Custom(String::from(
+                    "Cannot transfer a token to its owner.",
+                ))),
+                _ => Ok(None),
+            };
+        }
+        self.ensure_not_offered(id)?;
+        self.ensure_not_locked(id)?;
+
+        Ok(Some(owner))
+    }
+
+    /// Mints a token `id` to `account` in the block with number `block_number`
+    /// and timestamp `timestamp`.
+    pub fn mint(
+        &mut self,
+        account: AccountId,
+        id: Id,
+        block_number: u32,
+        timestamp: u64,
+    ) -> Result<EventList, PSP34Error> {
+        self.mint_unchecked(account, id, block_number, timestamp)
+    }
+
+    /// Mints token `id` to `to` in the block with number `block_number` and timestamp
+    /// `timestamp` and returns the mint events.
+    ///
+    /// For extension use only: the extension has to authorize the mint itself.
+    /// Still fails if the token exists or the supply or balance limit would be exceeded.
+    /// `mint` does not check the caller either, so both are the same operation.
+    pub fn mint_unchecked(
+        &mut self,
+        to: AccountId,
+        id: Id,
+        block_number: u32,
+        timestamp: u64,
+    ) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        if self.owner_of(&id).is_some() {
+            return Err(PSP34Error::TokenExists);
+        }
+        self.ensure_valid_recipient(&to)?;
+        self.ensure_supply_fits(1)?;
+        self.ensure_balance_fits(to, 1)?;
+        self.mint_token(to, &id, block_number, timestamp)?;
+
+        let mut events = EventList::new();
+        self.push_mint_events(&mut events, to, id);
+        self.push_balance_changes(&mut events, &[to]);
+        Ok(events)
+    }
+
+    /// Mints token `id` to `account` like `mint` and approves `operator` to transfer it
+    /// on behalf of `account`, e.g. a marketplace listing the token for sale.
+    /// Emits the events of the mint followed by an `Approval` event.
+    pub fn mint_and_approve(
+        &mut self,
+        account: AccountId,
+        id: Id,
+        operator: AccountId,
+        block_number: u32,
+        timestamp: u64,
+    ) -> Result<EventList, PSP34Error> {
+        if operator == account {
+            return Err(PSP34Error::SelfApprove);
+        }
+        let id = self.normalize_id(id);
+        let mut events = self.mint(account, id.clone(), block_number, timestamp)?;
+        self.operator_approvals
+            .insert((account, operator, Some(&id)), &());
+        events.push(PSP34Event::Approval {
+            owner: account,
+            operator,
+            id: Some(id),
+            approved: true,
+        });
+        Ok(events)
+    }
+
+    /// Mints `count` tokens with consecutive `Id::U128` ids to `account`, starting from
+    /// `next_id`, in the block with number `block_number` and timestamp `timestamp`.
+    /// Returns the range of the assigned ids.
+    ///
+    /// Fails without minting anything if any of the ids already exists or the supply
+    /// or the balance of `account` would overflow. The supply and balance are checked
+    /// before anything is allocated for the batch, but its size has to be bounded by
+    /// the contract to stay within the block weight limit.
+    pub fn mint_sequential(
+        &mut self,
+        account: AccountId,
+        count: u32,
+        block_number: u32,
+        timestamp: u64,
+    ) -> Result<(Range<u128>, EventList), PSP34Error> {
+        self.ensure_supply_fits(count.into())?;
+        self.ensure_balance_fits(account, count as usize)?;
+        self.mint_to_many(&vec![account; count as usize], block_number, timestamp)
+    }
+
+    /// Mints one token to each of the `recipients`, assigning consecutive `Id::U128` ids
+    /// starting from `next_id` in the order of the list, in the block with number
+    /// `block_number` and timestamp `timestamp`.
+    /// An account listed multiple times receives multiple tokens.
+    /// Returns the assigned ids.
+    ///
+    /// The balance of every distinct recipient is updated once. Fails without minting
+    /// anything if any of the ids already exists or the supply or any balance would overflow.
+    pub fn mint_airdrop(
+        &mut self,
+        recipients: Vec<AccountId>,
+        block_number: u32,
+        timestamp: u64,
+    ) -> Result<(Vec<Id>, EventList), PSP34Error> {
+        let (ids, events) = self.mint_to_many(&recipients, block_number, timestamp)?;
+        Ok((ids.map(Id::U128).collect(), events))
+    }
+
+    /// Mints a token with a pseudo-random `Id::U128` id to `account`, in the block with
+    /// number `block_number` and timestamp `timestamp`. Returns the assigned id.
+    ///
+    /// The ids are drawn without repetition from `0..max_supply` with a lazy Fisher-Yates
+    /// shuffle, so every id is assigned exactly once over a full mint-out. Fails if there
+    /// is no supply limit. Tokens minted with other methods are not taken out of the pool,
+    /// so drawing their id fails with `TokenExists`.
+    ///
+    /// The draw is derived from `seed`, e.g. a hash of the block data, and the number of
+    /// ids drawn so far. This is not secure randomness: block producers can influence
+    /// the block data, and anyone can predict the next id and choose when to mint.
+    pub fn mint_random(
+        &mut self,
+        account: AccountId,
+        seed: [u8; 32],
+        block_number: u32,
+        timestamp: u64,
+    ) -> Result<(Id, EventList), PSP34Error> {
+        let max_supply = self.max_supply().ok_or(PSP34Error::Custom(String::from(
+            "Random mints require a supply limit.",
+        )))?;
+        let drawn = self.random_drawn.get_or_default();
+        let remaining = max_supply.saturating_sub(drawn);
+        if remaining == 0 {
+            return Err(PSP34Error::MaxSupplyExceeded);
+        }
+        let mut hash = [0u8; 32];
+        ink::env::hash_encoded::<ink::env::hash::Blake2x256, _>(&(seed, drawn), &mut hash);
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&hash[..16]);
+        let index = u128::from_le_bytes(bytes) % remaining;
+
+        // The pool is `0..remaining` with the moved ids remapped: the drawn slot takes
+        // the id of the last slot, which leaves the pool.
+        let last = remaining - 1;
+        let id = self.random_remap.get(index).unwrap_or(index);
+        let events = self.mint(account, Id::U128(id), block_number, timestamp)?;
+        if index != last {
+            let last_id = self.random_remap.get(last).unwrap_or(last);
+            self.random_remap.insert(index, &last_id);
+        }
+        self.random_remap.remove(last);
+        self.random_drawn.set(&(drawn + 1));
+        Ok((Id::U128(id), events))
+    }
+
+    fn mint_to_many(
+        &mut self,
+        recipients: &[AccountId],
+        block_number: u32,
+        timestamp: u64,
+    ) -> Result<(Range<u128>, EventList), PSP34Error> {
+        for account in recipients {
+            self.ensure_valid_recipient(account)?;
+        }
+        let count = recipients.len() as u128;
+        let start = self.next_id();
+        let end = start
+            .checked_add(count)
+            .ok_or(PSP34Error::Custom(String::from(
+                "Max PSP34 id exceeded. Max id limited to 2^128-1.",
+            )))?;
+        self.ensure_supply_fits(count)?;
+        if (start..end).any(|id| self.owner_of(&Id::U128(id)).is_some()) {
+            return Err(PSP34Error::TokenExists);
+        }
+
+        let mut received: BTreeMap<AccountId, Vec<Id>> = BTreeMap::new();
+        for (account, id) in recipients.iter().zip(start..end) {
+            received.entry(*account).or_default().push(Id::U128(id));
+        }
+        for (account, ids) in &received {
+            self.ensure_balance_fits(*account, ids.len())?;
+        }
+
+        let mut balances: BTreeMap<AccountId, u32> = BTreeMap::new();
+        if self.balance_change_events.get_or_default() {
+            balances.extend(
+                received
+                    .keys()
+                    .map(|account| (*account, self.balance_of(*account))),
+            );
+        }
+        for (account, ids) in &received {
+            self.balance.increase_balance_by(account, ids, false)?;
+        }
+        let minted: Vec<Id> = received.into_values().flatten().collect();
+        self.balance.increase_supply(&minted)?;
+        let mut events = EventList::new();
+        for (account, id) in recipients.iter().zip(start..end) {
+            let id = Id::U128(id);
+            self.token_owner.insert(&id, account);
+            self.minted_at.insert(&id, &(block_number, timestamp));
+            self.push_mint_events(&mut events, *account, id);
+            self.push_balance_change(&mut events, &mut balances, *account, true);
+        }
+        self.next_id.set(&end);
+        self.minted_count
+            .set(&self.minted_count().saturating_add(count));
+
+        Ok((start..end, events))
+    }
+
+    /// Checks that `account` is not the zero address, if such recipients are rejected.
+    fn ensure_valid_recipient(&self, account: &AccountId) -> Result<(), PSP34Error> {
+        check_recipient(self.reject_zero_address(), account)
+    }
+
+    // Reads the settings checked by every transfer from their storage cells.
+    fn transfer_rules(&self) -> TransferRules {
+        TransferRules {
+            config: self.config(),
+            reject_zero_address: self.reject_zero_address(),
+            self_transfer_mode: self.self_transfer_mode(),
+        }
+    }
+
+    /// Checks that the total supply can grow by `count` tokens within its limit.
+    fn ensure_supply_fits(&self, count: u128) -> Result<(), PSP34Error> {
+        self.total_supply()
+            .checked_add(count)
+            .filter(|supply| self.max_supply().is_none_or(|max| *supply <= max))
+            .map(|_| ())
+            .ok_or(PSP34Error::MaxSupplyExceeded)
+    }
+
+    /// Checks that `account` can receive `count` more tokens within the balance limit,
+    /// unless it is exempt from the limit. Extensions moving several tokens with
+    /// `transfer_unchecked` can use it to check the whole move upfront.
+    pub fn ensure_balance_fits(&self, account: AccountId, count: usize) -> Result<(), PSP34Error> {
+        let balance = u32::try_from(count)
+            .ok()
+            .and_then(|count| self.balance_of(account).checked_add(count))
+            .ok_or(PSP34Error::MaxBalanceExceeded)?;
+        self.ensure_balance_within_limit(account, balance)
+    }
+
+    /// Checks that `balance` of `account` is within the balance limit,
+    /// unless the account is exempt from the limit.
+    fn ensure_balance_within_limit(
+        &self,
+        account: AccountId,
+        balance: u32,
+    ) -> Result<(), PSP34Error> {
+        match self.max_balance() {
+            Some(max) if balance > max && !self.is_balance_limit_exempt(account) => {
+                Err(PSP34Error::MaxBalanceExceeded)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn push_mint_events(&self, events: &mut EventList, to: AccountId, id: Id) {
+        let mint_burn_events = self.mint_burn_events();
+        if mint_burn_events != MintBurnEvents::Dedicated {
+            events.push(PSP34Event::Transfer {
+                from: None,
+                to: Some(to),
+                id: id.clone(),
+            });
+        }
+        if mint_burn_events != MintBurnEvents::Transfer {
+            events.push(PSP34Event::Minted { to, id });
+        }
+    }
+
+    fn mint_token(
+        &mut self,
+        account: AccountId,
+        id: &Id,
+        block_number: u32,
+        timestamp: u64,
+    ) -> Result<(), PSP34Error> {
+        self.balance.increase_balance(&account, id, true)?;
+        self.token_owner.insert(id, &account);
+        self.minted_at.insert(id, &(block_number, timestamp));
+        self.minted_count
+            .set(&self.minted_count().saturating_add(1));
+        Ok(())
+    }
+
+    /// Burns token `id` from `account`, conducted by `caller`
+    pub fn burn(
+        &mut self,
+        caller: AccountId,
+        account: AccountId,
+        id: Id,
+    ) -> Result<EventList, PSP34Error> {
+        if !self.config().burnable {
+            return Err(PSP34Error::BurningDisabled);
+        }
+        let id = self.normalize_id(id);
+        let owner = self.owner_of(&id).ok_or(PSP34Error::TokenNotExists)?;
+        if owner != account {
+            return Err(PSP34Error::NotApproved);
+        }
+        if account != caller && !self.allowance(account, caller, Some(&id)) {
+            return Err(PSP34Error::NotApproved);
+        }
+        self.ensure_not_offered(&id)?;
+        self.ensure_not_locked(&id)?;
+        Ok(self.burn_token(caller, account, id))
+    }
+
+    /// Burns token `id` regardless of its owner and approvals, also withdrawing its
+    /// pending transfer offer. The approvals of the given `operators` granted by the owner
+    /// for the token are revoked, so they do not apply to the id if it is minted again.
+    /// Approvals are stored per owner and operator, so the operators have to be collected
+    /// off-chain, e.g. from the `Approval` events, like for `prune_approvals`. Meant for
+    /// moderation by the contract's admin, so the `caller` has to be checked by the contract.
+    ///
+    /// Fails if the token is held by an extension or locked in the bridge, which has
+    /// to release it first.
+    pub fn admin_burn(
+        &mut self,
+        caller: AccountId,
+        id: Id,
+        operators: Vec<AccountId>,
+    ) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        let owner = self.owner_of(&id).ok_or(PSP34Error::TokenNotExists)?;
+        self.ensure_not_locked(&id)?;
+        self.pending_transfers.remove(&id);
+        let mut events = EventList::new();
+        for operator in operators {
+            if !self
+                .operator_approvals
+                .contains((owner, operator, Some(&id)))
+            {
+                continue;
+            }
+            self.operator_approvals.remove((owner, operator, Some(&id)));
+            self.approval_uses.remove((owner, operator, Some(&id)));
+            events.push(PSP34Event::Approval {
+                owner,
+                operator,
+                id: Some(id.clone()),
+                approved: false,
+            });
+        }
+        events.extend(self.burn_token(caller, owner, id));
+        Ok(events)
+    }
+
+    /// Burns token `id` of `from` and returns the burn events, withdrawing the pending
+    /// transfer offer of the token, if any.
+    ///
+    /// For extension use only: neither the approvals, nor the `Config`, nor the offers
+    /// and the bridge locks are checked, so the extension has to authorize the burn
+    /// itself. Still fails if `from` does not own the token.
+    pub fn burn_unchecked(&mut self, from: AccountId, id: Id) -> Result<EventList, PSP34Error> {
+        let id = self.normalize_id(id);
+        if self.owner_of(&id).ok_or(PSP34Error::TokenNotExists)? != from {
+            return Err(PSP34Error::NotApproved);
+        }
+        self.pending_transfers.remove(&id);
+        Ok(self.burn_token(from, from, id))
+    }
+
+    fn burn_token(&mut self, caller: AccountId, owner: AccountId, id: Id) -> EventList {
+        self.burn_tokens(caller, owner, core::slice::from_ref(&id))
+    }
+
+    fn burn_tokens(&mut self, caller: AccountId, owner: AccountId, ids: &[Id]) -> EventList {
+        let mut balances: BTreeMap<AccountId, u32> = BTreeMap::new();
+        if self.balance_change_events.get_or_default() {
+            balances.insert(owner, self.balance_of(owner));
+        }
+        self.balance.decrease_balance_by(&owner, ids, true);
+        self.burned_count
+            .set(&self.total_burned().saturating_add(ids.len() as u128));
+
+        let mint_burn_events = self.mint_burn_events();
+        let mut events = EventList::new();
+        for id in ids {
+            self.token_owner.remove(id);
+            self.minted_at.remove(id);
+            self.transfer_count.remove(id);
+            self.locked_tokens.remove(id);
+            self.holds.remove(id);
+
+            if mint_burn_events != MintBurnEvents::Dedicated {
+                events.push(PSP34Event::Transfer {
+                    from: Some(owner),
+                    to: None,
+                    id: id.clone(),
+                });
+            }
+            if mint_burn_events != MintBurnEvents::Transfer {
+                events.push(PSP34Event::Burned {
+                    from: owner,
+                    caller,
+                    id: id.clone(),
+                });
+            }
+            self.push_balance_change(&mut events, &mut balances, owner, false);
+        }
+        events
+    }
+
+    #[cfg(feature = "enumerable")]
+    pub fn owners_token_by_index(&self, owner: AccountId, index: u128) -> Result<Id, PSP34Error> {
+        self.balance.owners_token_by_index(owner, index)
+    }
+
+    #[cfg(feature = "enumerable")]
+    pub fn token_by_index(&self, index: u128) -> Result<Id, PSP34Error> {
+        self.balance.token_by_index(index)
+    }
+
+    /// Returns the index of token `id` among the tokens of `owner`,
+    /// or `None` if `owner` does not own it.
+    /// Burning or transferring away another token of `owner` may move it.
+    #[cfg(feature = "enumerable")]
+    pub fn index_of_owned(&self, owner: AccountId, id: &Id) -> Option<u128> {
+        let id = &self.normalize_id(id.clone());
+        self.balance.index_of_owned(&owner, id)
+    }
+
+    /// Returns all existing tokens for which `pred` holds.
+    ///
+    /// Reads the whole list of tokens at once, so the cost grows with the total supply
+    /// without a bound. Meant for off-chain tooling and tests only, never call it from a message.
+    #[cfg(all(feature = "std", feature = "enumerable"))]
+    pub fn tokens_where(&self, pred: impl Fn(&Id) -> bool) -> Vec<Id> {
+        self.balance.tokens_where(pred)
+    }
+
+    /// Returns all tokens of `owner`, in the order of `owners_token_by_index`.
+    ///
+    /// Reads the whole list of `owner`'s tokens at once, so the cost grows with their
+    /// balance without a bound. Meant for off-chain tooling and tests only.
+    #[cfg(all(feature = "std", feature = "enumerable"))]
+    pub fn tokens_of_owner(&self, owner: AccountId) -> Vec<Id> {
+        self.balance.tokens_of_owner(&owner)
+    }
+
+    /// Burns up to `limit` tokens of `owner`, conducted by `caller`, who must be `owner`
+    /// or approved for all of `owner`'s tokens.
+    /// Returns the burned ids. Can be called again to continue burning the remaining tokens.
+    #[cfg(feature = "enumerable")]
+    pub fn burn_all_of(
+        &mut self,
+        caller: AccountId,
+        owner: AccountId,
+        limit: u32,
+    ) -> Result<(Vec<Id>, EventList), PSP34Error> {
+        if owner != caller && !self.allowance(owner, caller, None) {
+            return Err(PSP34Error::NotApproved);
+        }
+        // Taking the last tokens keeps the indices of the remaining ones intact.
+        let ids = self.balance.last_owned(&owner, limit as usize);
+        if ids.is_empty() {
+            return Ok((ids, EventList::new()));
+        }
+        if !self.config().burnable {
+            return Err(PSP34Error::BurningDisabled);
+        }
+        for id in &ids {
+            self.ensure_not_offered(id)?;
+            self.ensure_not_locked(id)?;
+        }
+        let events = self.burn_tokens(caller, owner, &ids);
+        Ok((ids, events))
+    }
+
+    /// Returns the account at a given `index` of the list of all current holders.
+    #[cfg(feature = "enumerable")]
+    pub fn owner_by_index(&self, index: u128) -> Option<AccountId> {
+        self.balance.owner_by_index(index)
+    }
+
+    /// Returns at most `limit` current holders, skipping the first `offset` of them.
+    #[cfg(feature = "enumerable")]
+    pub fn holders(&self, offset: u32, limit: u32) -> Vec<AccountId> {
+        self.balance.holders(offset, limit)
+    }
+
+    /// Returns at most `count` tokens with their owners, in the order of `token_by_index`,
+    /// starting at index `start`. `count` is clamped to `MAX_PAGE_SIZE`.
+    #[cfg(feature = "enumerable")]
+    pub fn all_tokens_with_owners_page(&self, start: u128, count: u32) -> Vec<(Id, AccountId)> {
+        let count = count.min(MAX_PAGE_SIZE) as u128;
+        (start..start.saturating_add(count))
+            .map_while(|index| self.token_by_index(index).ok())
+            .filter_map(|id| Some((id.clone(), self.owner_of(&id)?)))
+            .collect()
+    }
+}
+
+impl Default for Id {
+    fn default() -> Self {
+        Self::U128(0)
+    }
+}
+
+impl Id {
+    /// Compares ids by their values: numeric ids of any variant by their numeric value,
+    /// followed by `Id::Bytes` ordered lexicographically. Numerically equal ids of
+    /// different variants, e.g. `Id::U8(1)` and `Id::U16(1)`, are ordered by variant,
+    /// so that only equal ids compare as `Ordering::Equal`.
+    pub fn canonical_cmp(&self, other: &Id) -> Ordering {
+        match (self.numeric_value(), other.numeric_value()) {
+            (Some(value), Some(other_value)) => {
+                value.cmp(&other_value).then_with(|| self.cmp(other))
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => self.cmp(other),
+        }
+    }
+
+    // Returns the value of a numeric id as 256-bit big-endian bytes.
+    fn numeric_value(&self) -> Option<[u8; 32]> {
+        let value = match self {
+            Id::U8(val) => *val as u128,
+            Id::U16(val) => *val as u128,
+            Id::U32(val) => *val as u128,
+            Id::U64(val) => *val as u128,
+            Id::U128(val) => *val,
+            Id::Bytes(_) => return None,
+            Id::U256(val) => return Some(*val),
+        };
+        let mut bytes = [0; 32];
+        bytes[16..].copy_from_slice(&value.to_be_bytes());
+        Some(bytes)
+    }
+}
+
+macro_rules! impl_from_uint_for_id {
+    ($($uint:ident => $variant:ident),*) => {
+        $(
+            impl From<$uint> for Id {
+                fn from(val: $uint) -> Self {
+                    Id::$variant(val)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_uint_for_id!(u8 => U8, u16 => U16, u32 => U32, u64 => U64, u128 => U128);
+
+/// Fails for an `Id::U256` above `u128::MAX` and for an `Id::Bytes` that is not
+/// exactly 16 bytes long.
+impl TryFrom<Id> for u128 {
+    type Error = PSP34Error;
+
+    fn try_from(id: Id) -> Result<Self, Self::Error> {
+        let does_not_fit = || PSP34Error::Custom(String::from("Id does not fit in u128."));
+        match id {
+            Id::U8(val) => Ok(val as u128),
+            Id::U16(val) => Ok(val as u128),
+            Id::U32(val) => Ok(val as u128),
+            Id::U64(val) => Ok(val as u128),
+            Id::U128(val) => Ok(val),
+            Id::Bytes(val) => val
+                .as_slice()
+                .try_into()
+                .map(u128::from_be_bytes)
+                .map_err(|_| does_not_fit()),
+            Id::U256(val) => {
+                if val[..16].iter().any(|byte| *byte != 0) {
+                    return Err(does_not_fit());
+                }
+                Ok(u128::from_be_bytes(val[16..].try_into().unwrap()))
+            }
+        }
+    }
 }
 
-/// A class implementing the internal logic of a PSP34 token.
-//
-/// Holds the state of all account balances and approvals.
-/// Each method of this class corresponds to one type of transaction
-/// as defined in the PSP34 standard.
-//
-/// Since this code is outside of `ink::contract` macro, the caller's
-/// address cannot be obtained automatically. Because of that, all
-/// the methods that need to know the caller require an additional argument
-/// (compared to transactions defined by the PSP34 standard or the PSP34 trait).
-//
-/// `lib.rs` contains an example implementation of a smart contract using this class.
-#[ink::storage_item]
-#[derive(Debug, Default)]
-pub struct PSP34Data {
-    token_owner: Mapping<Id, AccountId>,
-    operator_approvals: Mapping<(AccountId, AccountId, Option<Id>), ()>,
-    balance: Balances,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use scale::{Decode, DecodeAll, Encode};
+
+    // A copy of OpenBrush's `psp34::Id`, kept here to verify the encodings match.
+    #[derive(Debug, PartialEq, scale::Encode, scale::Decode)]
+    enum OpenBrushId {
+        U8(u8),
+        U16(u16),
+        U32(u32),
+        U64(u64),
+        U128(u128),
+        Bytes(Vec<u8>),
+    }
+
+    #[test]
+    fn id_encoding_matches_openbrush() {
+        let cases = vec![
+            (Id::U8(7), OpenBrushId::U8(7), vec![0, 7]),
+            (Id::U16(0x0102), OpenBrushId::U16(0x0102), vec![1, 2, 1]),
+            (
+                Id::U32(0x01020304),
+                OpenBrushId::U32(0x01020304),
+                vec![2, 4, 3, 2, 1],
+            ),
+            (
+                Id::U64(1),
+                OpenBrushId::U64(1),
+                vec![3, 1, 0, 0, 0, 0, 0, 0, 0],
+            ),
+            (
+                Id::U128(1),
+                OpenBrushId::U128(1),
+                vec![4, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            ),
+            (
+                Id::Bytes(vec![0xaa, 0xbb]),
+                OpenBrushId::Bytes(vec![0xaa, 0xbb]),
+                vec![5, 8, 0xaa, 0xbb],
+            ),
+        ];
+        for (id, openbrush_id, bytes) in cases {
+            assert_eq!(id.encode(), bytes);
+            assert_eq!(openbrush_id.encode(), bytes);
+            assert_eq!(Id::decode(&mut &bytes[..]), Ok(id));
+            assert_eq!(OpenBrushId::decode(&mut &bytes[..]), Ok(openbrush_id));
+        }
+    }
+
+    // Returns the `Id::U256` with the given high and low 128 bits.
+    fn u256(high: u128, low: u128) -> Id {
+        let mut bytes = [0; 32];
+        bytes[..16].copy_from_slice(&high.to_be_bytes());
+        bytes[16..].copy_from_slice(&low.to_be_bytes());
+        Id::U256(bytes)
+    }
+
+    #[test]
+    fn u256_ids_round_trip_and_order_numerically() {
+        let id = u256(1, 2);
+        let mut bytes = vec![6];
+        bytes.extend_from_slice(&[0; 15]);
+        bytes.push(1);
+        bytes.extend_from_slice(&[0; 15]);
+        bytes.push(2);
+        assert_eq!(id.encode(), bytes);
+        assert_eq!(Id::decode(&mut &bytes[..]), Ok(id));
+
+        let mut ids = vec![u256(1, 0), u256(0, u128::MAX), u256(0, 256), u256(0, 1)];
+        ids.sort();
+        assert_eq!(
+            ids,
+            vec![u256(0, 1), u256(0, 256), u256(0, u128::MAX), u256(1, 0)]
+        );
+        assert_eq!(u128::try_from(u256(0, 42)), Ok(42));
+        assert!(u128::try_from(u256(1, 42)).is_err());
+        assert_eq!(
+            u128::try_from(Id::Bytes(7u128.to_be_bytes().to_vec())),
+            Ok(7)
+        );
+        assert!(u128::try_from(Id::Bytes(vec![7])).is_err());
+        assert_eq!(u128::try_from(Id::from(7u8)), Ok(7));
+    }
+
+    #[ink::test]
+    fn u256_ids_fitting_in_u128_are_normalized() {
+        let alice = AccountId::from([1; 32]);
+        let data = PSP34DataBuilder::new().normalize_ids(true).build();
+        assert_eq!(data.normalize_id(u256(0, 7)), Id::U128(7));
+        assert_eq!(data.normalize_id(u256(1, 7)), u256(1, 7));
+
+        let mut data = PSP34DataBuilder::new().normalize_ids(true).build();
+        assert!(data.mint(alice, u256(0, 7), 0, 0).is_ok());
+        assert_eq!(
+            data.mint(alice, Id::U8(7), 0, 0),
+            Err(PSP34Error::TokenExists)
+        );
+        assert!(data.mint(alice, u256(1, 7), 0, 0).is_ok());
+        assert_eq!(data.owner_of(&u256(1, 7)), Some(alice));
+    }
+
+    #[test]
+    fn events_round_trip_through_scale() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let events = vec![
+            PSP34Event::Transfer {
+                from: None,
+                to: Some(alice),
+                id: Id::U8(1),
+            },
+            PSP34Event::Approval {
+                owner: alice,
+                operator: bob,
+                id: None,
+                approved: true,
+            },
+            PSP34Event::AttributeSet {
+                id: Id::Bytes(vec![1, 2]),
+                key: b"name".to_vec(),
+                data: b"Token".to_vec(),
+            },
+            PSP34Event::TransferOffered {
+                from: alice,
+                to: bob,
+                id: Id::U128(7),
+                deadline: Some(100),
+            },
+            PSP34Event::TransferClaimed {
+                from: alice,
+                to: bob,
+                id: Id::U128(7),
+            },
+            PSP34Event::TransferCancelled {
+                from: alice,
+                to: bob,
+                id: Id::U128(7),
+            },
+            PSP34Event::ProvenanceSet { hash: [7; 32] },
+            PSP34Event::Minted {
+                to: alice,
+                id: Id::U8(2),
+            },
+            PSP34Event::Burned {
+                from: alice,
+                caller: bob,
+                id: Id::U8(2),
+            },
+            PSP34Event::TransferMemo {
+                id: Id::U8(3),
+                sequence: 1,
+                memo: b"invoice".to_vec(),
+            },
+            PSP34Event::TokenRescued {
+                id: Id::U8(4),
+                to: bob,
+            },
+            PSP34Event::TeleportedOut {
+                id: Id::U8(5),
+                from: alice,
+                dest_chain: 2000,
+                dest_account: vec![2; 20],
+                nonce: 0,
+            },
+            PSP34Event::TeleportedIn {
+                id: Id::U8(5),
+                to: bob,
+            },
+            PSP34Event::BalanceChanged {
+                account: bob,
+                new_balance: 3,
+            },
+            PSP34Event::Listed {
+                id: Id::U8(1),
+                seller: alice,
+                price: 1000,
+            },
+            PSP34Event::Delisted {
+                id: Id::U8(1),
+                seller: alice,
+            },
+            PSP34Event::Sold {
+                id: Id::U8(1),
+                seller: alice,
+                buyer: bob,
+                price: 1000,
+            },
+            PSP34Event::OfferMade {
+                id: Id::U8(1),
+                bidder: bob,
+                amount: 1000,
+            },
+            PSP34Event::OfferCancelled {
+                id: Id::U8(1),
+                bidder: bob,
+                amount: 1000,
+            },
+            PSP34Event::OfferAccepted {
+                id: Id::U8(1),
+                seller: alice,
+                bidder: bob,
+                amount: 1000,
+            },
+            PSP34Event::AuctionCreated {
+                id: Id::U8(1),
+                seller: alice,
+                start_price: 1000,
+                min_increment: 100,
+                end_time: 5,
+            },
+            PSP34Event::BidPlaced {
+                id: Id::U8(1),
+                bidder: bob,
+                amount: 1000,
+                end_time: 5,
+            },
+            PSP34Event::AuctionSettled {
+                id: Id::U8(1),
+                seller: alice,
+                winner: Some(bob),
+                price: 1000,
+            },
+            PSP34Event::CollateralLocked {
+                id: Id::U8(1),
+                owner: alice,
+                lender: bob,
+            },
+            PSP34Event::CollateralReleased {
+                id: Id::U8(1),
+                owner: alice,
+                lender: bob,
+            },
+            PSP34Event::CollateralSeized {
+                id: Id::U8(1),
+                owner: alice,
+                lender: bob,
+            },
+            PSP34Event::BundleCreated {
+                id: Id::U8(1),
+                owner: alice,
+                components: vec![Id::U8(2), Id::U8(3)],
+            },
+            PSP34Event::Unbundled {
+                id: Id::U8(1),
+                owner: alice,
+                components: vec![Id::U8(2), Id::U8(3)],
+            },
+            PSP34Event::Crafted {
+                recipe_id: 1,
+                owner: alice,
+                inputs: vec![Id::U8(2), Id::U8(3)],
+                output: Id::U128(4),
+            },
+        ];
+        for (index, event) in events.into_iter().enumerate() {
+            let bytes = event.encode();
+            assert_eq!(bytes[0] as usize, index);
+            assert_eq!(PSP34Event::decode(&mut &bytes[..]), Ok(event));
+        }
+    }
+
+    #[cfg(not(feature = "enumerable"))]
+    #[ink::test]
+    fn transfer_to_full_account_changes_nothing() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let mut data = PSP34Data::new();
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(data.approve(alice, bob, Some(Id::U8(1)), true).is_ok());
+        data.balance.set_balance(&bob, u32::MAX);
+
+        assert!(data.simulate_transfer(bob, bob, &Id::U8(1)).is_err());
+        assert!(data.transfer(bob, bob, Id::U8(1), vec![]).is_err());
+        assert_eq!(data.owner_of(&Id::U8(1)), Some(alice));
+        assert_eq!(data.balance_of(alice), 1);
+        assert_eq!(data.balance_of(bob), u32::MAX);
+        assert_eq!(data.transfer_count(&Id::U8(1)), 0);
+        assert!(data.allowance(alice, bob, Some(&Id::U8(1))));
+
+        assert_eq!(
+            data.transfer(bob, bob, Id::U8(1), vec![]),
+            Err(PSP34Error::MaxBalanceExceeded)
+        );
+        // Moving the token directly fails before touching the state as well.
+        assert!(data.move_token(bob, alice, bob, &Id::U8(1)).is_err());
+        assert_eq!(data.owner_of(&Id::U8(1)), Some(alice));
+        assert_eq!(data.balance_of(alice), 1);
+        assert_eq!(data.total_supply(), 1);
+    }
+    #[cfg(not(feature = "enumerable"))]
+    #[ink::test]
+    fn mint_reports_which_limit_is_exceeded() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let mut data = PSP34Data::new();
+        data.balance.set_balance(&alice, u32::MAX);
+        assert_eq!(
+            data.mint(alice, Id::U8(1), 0, 0),
+            Err(PSP34Error::MaxBalanceExceeded)
+        );
+        assert_eq!(
+            data.mint_sequential(alice, 1, 0, 0).map(|(ids, _)| ids),
+            Err(PSP34Error::MaxBalanceExceeded)
+        );
+        // Bob has room for more tokens, but the supply is full.
+        data.balance.set_total_supply(u128::MAX);
+        assert_eq!(
+            data.mint(bob, Id::U8(1), 0, 0),
+            Err(PSP34Error::MaxSupplyExceeded)
+        );
+        assert_eq!(
+            data.mint_airdrop(vec![bob], 0, 0).map(|(ids, _)| ids),
+            Err(PSP34Error::MaxSupplyExceeded)
+        );
+        assert_eq!(data.owner_of(&Id::U8(1)), None);
+        assert_eq!(data.balance_of(bob), 0);
+    }
+    #[ink::test]
+    fn builder_configures_limits() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        // The instances share the storage, so the one used below is built last.
+        assert_eq!(PSP34Data::new().max_supply(), None);
+        assert_eq!(PSP34Data::new().max_balance(), None);
+        assert_eq!(
+            PSP34Data::new().limits(),
+            Limits {
+                max_supply: None,
+                max_balance: None,
+            }
+        );
+        assert_eq!(
+            PSP34DataBuilder::new().max_balance(5).build().limits(),
+            Limits {
+                max_supply: None,
+                max_balance: Some(5),
+            }
+        );
+        let mut data = PSP34DataBuilder::new().max_supply(3).max_balance(2).build();
+        assert_eq!(data.max_supply(), Some(3));
+        assert_eq!(data.max_balance(), Some(2));
+        assert_eq!(
+            data.limits(),
+            Limits {
+                max_supply: Some(3),
+                max_balance: Some(2),
+            }
+        );
+
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(data.mint(alice, Id::U8(2), 0, 0).is_ok());
+        assert_eq!(
+            data.mint(alice, Id::U8(3), 0, 0),
+            Err(PSP34Error::MaxBalanceExceeded)
+        );
+        assert_eq!(
+            data.mint_sequential(bob, 2, 0, 0).map(|(ids, _)| ids),
+            Err(PSP34Error::MaxSupplyExceeded)
+        );
+        // A huge batch fails before anything is allocated for it.
+        assert_eq!(
+            data.mint_sequential(bob, u32::MAX, 0, 0)
+                .map(|(ids, _)| ids),
+            Err(PSP34Error::MaxSupplyExceeded)
+        );
+        assert!(data.mint(bob, Id::U8(3), 0, 0).is_ok());
+        assert_eq!(
+            data.mint(bob, Id::U8(4), 0, 0),
+            Err(PSP34Error::MaxSupplyExceeded)
+        );
 
-impl PSP34Data {
-    /// Creates a token with default values for every field.
-    /// Initially held by the 'creator' account.
-    pub fn new() -> PSP34Data {
-        Default::default()
+        // Transfers respect the balance limit, also within a batch.
+        assert!(data.transfer(bob, alice, Id::U8(3), vec![]).is_err());
+        assert_eq!(
+            data.transfer_from_many(
+                alice,
+                vec![(alice, bob, Id::U8(1)), (alice, bob, Id::U8(2))],
+                vec![]
+            )
+            .map(|events| events.len()),
+            Err(PSP34Error::MaxBalanceExceeded)
+        );
+        // Swapping tokens between full accounts is fine.
+        assert!(data.approve(bob, alice, Some(Id::U8(3)), true).is_ok());
+        assert_eq!(
+            data.transfer_from_many(
+                alice,
+                vec![(alice, bob, Id::U8(1)), (bob, alice, Id::U8(3))],
+                vec![]
+            )
+            .map(|events| events.len()),
+            Ok(2)
+        );
+    }
+    #[ink::test]
+    fn is_never_sold_out_without_supply_limit() {
+        let alice = AccountId::from([1; 32]);
+        let mut uncapped = PSP34Data::new();
+        assert!(!uncapped.is_sold_out());
+        assert!(uncapped.mint_sequential(alice, 2, 0, 0).is_ok());
+        assert!(!uncapped.is_sold_out());
     }
 
-    pub fn total_supply(&self) -> u128 {
-        self.balance.total_supply()
+    #[ink::test]
+    fn is_sold_out_with_full_supply_limit() {
+        let alice = AccountId::from([1; 32]);
+        let mut capped = PSP34DataBuilder::new().max_supply(2).build();
+        assert!(!capped.is_sold_out());
+        assert!(capped.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(!capped.is_sold_out());
+        assert!(capped.mint(alice, Id::U8(2), 0, 0).is_ok());
+        assert!(capped.is_sold_out());
+        // Burning makes room again.
+        assert!(capped.burn(alice, alice, Id::U8(2)).is_ok());
+        assert!(!capped.is_sold_out());
+    }
+    #[ink::test]
+    fn numeric_ids_are_the_same_token_with_normalization() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let mut data = PSP34DataBuilder::new().normalize_ids(true).build();
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert_eq!(data.owner_of(&Id::U64(1)), Some(alice));
+        assert_eq!(data.owner_of(&Id::U128(1)), Some(alice));
+        assert_eq!(
+            data.mint(alice, Id::U64(1), 0, 0),
+            Err(PSP34Error::TokenExists)
+        );
+        // An approval granted under one variant applies to the others.
+        assert!(data.approve(alice, bob, Some(Id::U16(1)), true).is_ok());
+        assert!(data.allowance(alice, bob, Some(&Id::U64(1))));
+        assert!(data.transfer(bob, bob, Id::U32(1), vec![]).is_ok());
+        assert_eq!(data.owner_of(&Id::U8(1)), Some(bob));
+        assert!(!data.allowance(alice, bob, Some(&Id::U16(1))));
+        assert_eq!(data.transfer_count(&Id::U16(1)), 1);
+        // Bytes ids are kept as they are.
+        assert!(data.mint(alice, Id::Bytes(vec![1]), 0, 0).is_ok());
+        assert_eq!(data.owner_of(&Id::Bytes(vec![1])), Some(alice));
+        assert_eq!(data.normalize_id(Id::Bytes(vec![1])), Id::Bytes(vec![1]));
+        assert_eq!(data.total_supply(), 2);
     }
 
-    pub fn balance_of(&self, owner: AccountId) -> u32 {
-        self.balance.balance_of(&owner)
+    #[ink::test]
+    fn numeric_ids_are_different_tokens_without_normalization() {
+        let alice = AccountId::from([1; 32]);
+        let mut data = PSP34Data::new();
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert_eq!(data.owner_of(&Id::U64(1)), None);
+        assert!(data.mint(alice, Id::U64(1), 0, 0).is_ok());
+        assert_eq!(data.normalize_id(Id::U8(1)), Id::U8(1));
+        assert_eq!(data.total_supply(), 2);
     }
+    #[ink::test]
+    fn migrate_runs_missing_steps_once() {
+        let mut data = PSP34Data::new();
+        assert_eq!(data.storage_version(), CURRENT_VERSION);
+        assert_eq!(data.migrate(), Ok(CURRENT_VERSION));
 
-    pub fn owner_of(&self, id: &Id) -> Option<AccountId> {
-        self.token_owner.get(id)
+        // Each step leaves a trace in `next_id`.
+        let steps: &[fn(&mut PSP34Data)] = &[
+            |data| data.next_id.set(&(data.next_id() + 1)),
+            |data| data.next_id.set(&(data.next_id() + 10)),
+            |data| data.next_id.set(&(data.next_id() + 100)),
+        ];
+        data.storage_version.set(&1);
+        assert_eq!(data.migrate_with(steps), Ok(3));
+        assert_eq!(data.next_id(), 110);
+        assert_eq!(data.migrate_with(steps), Ok(3));
+        assert_eq!(data.next_id(), 110);
+
+        data.storage_version.set(&4);
+        assert!(data.migrate_with(steps).is_err());
+        assert_eq!(data.storage_version(), 4);
     }
 
-    pub fn allowance(&self, owner: AccountId, operator: AccountId, id: Option<&Id>) -> bool {
-        self.operator_approvals
-            .get((owner, operator, &None))
-            .is_some()
-            || id.is_some() && self.operator_approvals.get((owner, operator, id)).is_some()
+    // The layout of `PSP34Data` in the first release. The names are the same, so its
+    // storage cells have the same keys as the current ones.
+    mod first_release {
+        use super::*;
+
+        #[cfg(not(feature = "enumerable"))]
+        #[ink::storage_item]
+        #[derive(Default)]
+        pub struct Balances {
+            pub owned_tokens_count: Mapping<AccountId, u32>,
+            pub total_supply: u128,
+        }
+
+        #[cfg(feature = "enumerable")]
+        #[ink::storage_item]
+        #[derive(Default)]
+        pub struct Balances {
+            pub enumerable: Mapping<Option<AccountId>, Vec<Id>>,
+        }
+
+        #[ink::storage_item]
+        #[derive(Default)]
+        pub struct PSP34Data {
+            pub token_owner: Mapping<Id, AccountId>,
+            #[allow(clippy::type_complexity)]
+            pub operator_approvals: Mapping<(AccountId, AccountId, Option<Id>), ()>,
+            pub balance: Balances,
+        }
     }
 
-    pub fn collection_id(&self, account_id: AccountId) -> Id {
-        Id::Bytes(<_ as AsRef<[u8; 32]>>::as_ref(&account_id).to_vec())
+    #[ink::test]
+    fn storage_of_the_first_release_decodes_and_migrates() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let root = 0u32;
+        let mut old = first_release::PSP34Data::default();
+        let tokens = [(Id::U8(1), alice), (Id::U8(2), alice), (Id::U8(3), bob)];
+        for (id, owner) in &tokens {
+            old.token_owner.insert(id, owner);
+        }
+        old.operator_approvals.insert((alice, bob, None::<Id>), &());
+        #[cfg(not(feature = "enumerable"))]
+        {
+            old.balance.owned_tokens_count.insert(alice, &2);
+            old.balance.owned_tokens_count.insert(bob, &1);
+            old.balance.total_supply = 3;
+        }
+        #[cfg(feature = "enumerable")]
+        {
+            old.balance
+                .enumerable
+                .insert(Some(alice), &vec![Id::U8(1), Id::U8(2)]);
+            old.balance.enumerable.insert(Some(bob), &vec![Id::U8(3)]);
+            old.balance
+                .enumerable
+                .insert(None::<AccountId>, &vec![Id::U8(1), Id::U8(2), Id::U8(3)]);
+        }
+        ink::env::set_contract_storage(&root, &old);
+
+        let mut data: PSP34Data = ink::env::get_contract_storage(&root).unwrap().unwrap();
+        assert_eq!(data.storage_version(), 0);
+        assert_eq!(data.config(), Config::default());
+        assert_eq!((data.balance_of(alice), data.balance_of(bob)), (2, 1));
+        assert_eq!(data.total_supply(), 3);
+        assert!(data.allowance(alice, bob, None));
+
+        assert_eq!(data.migrate(), Ok(CURRENT_VERSION));
+        assert_eq!(data.minted_count(), 3);
+        assert_eq!(data.total_burned(), 0);
+        #[cfg(feature = "enumerable")]
+        assert_eq!(data.owner_count(), Some(2));
+        #[cfg(not(feature = "enumerable"))]
+        assert_eq!(data.owner_count(), None);
+
+        assert!(data.transfer(bob, bob, Id::U8(1), vec![]).is_ok());
+        assert!(data.burn(bob, alice, Id::U8(2)).is_ok());
+        assert!(data.mint(alice, Id::U8(4), 0, 0).is_ok());
+        assert_eq!(
+            data.minted_count() - data.total_burned(),
+            data.total_supply()
+        );
+
+        // The upgraded storage is written and read back as the current layout.
+        ink::env::set_contract_storage(&root, &data);
+        let data: PSP34Data = ink::env::get_contract_storage(&root).unwrap().unwrap();
+        assert_eq!(data.storage_version(), CURRENT_VERSION);
+        assert_eq!(data.owner_of(&Id::U8(1)), Some(bob));
+        assert_eq!(data.owner_of(&Id::U8(2)), None);
+        assert_eq!((data.balance_of(alice), data.balance_of(bob)), (1, 2));
+        assert_eq!(data.total_supply(), 3);
     }
 
-    /// Sets a new `approved` for a token `id` or for all tokens if no `id` is provided,
-    /// granted by `caller` to `operator`.
-    /// Overwrites the previously granted value.
-    pub fn approve(
-        &mut self,
-        mut caller: AccountId,
-        operator: AccountId,
-        id: Option<Id>,
-        approved: bool,
-    ) -> Result<Vec<PSP34Event>, PSP34Error> {
-        if let Some(id) = &id {
-            let owner = self.owner_of(id).ok_or(PSP34Error::TokenNotExists)?;
-            if approved && owner == operator {
-                return Err(PSP34Error::SelfApprove);
+    #[cfg(feature = "enumerable")]
+    #[ink::test]
+    fn tokens_where_filters_all_tokens() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let mut data = PSP34Data::new();
+        for id in 0..6u8 {
+            let owner = if id < 3 { alice } else { bob };
+            assert!(data.mint(owner, Id::U8(id), 0, 0).is_ok());
+        }
+        assert!(data.mint(alice, Id::Bytes(vec![2]), 0, 0).is_ok());
+
+        let is_even = |id: &Id| match id {
+            Id::Bytes(_) => false,
+            id => u128::try_from(id.clone()).unwrap() % 2 == 0,
+        };
+        let mut even = data.tokens_where(is_even);
+        even.sort_by_key(|id| u128::try_from(id.clone()).unwrap());
+        assert_eq!(even, vec![Id::U8(0), Id::U8(2), Id::U8(4)]);
+        assert!(data.tokens_where(|_| false).is_empty());
+        assert_eq!(data.tokens_where(|_| true).len(), 7);
+    }
+
+    #[cfg(feature = "enumerable")]
+    #[ink::test]
+    fn holders_are_not_limited_by_a_storage_cell() {
+        // Listed in a single cell, 1000 accounts would take twice the 16 KiB it can hold.
+        let accounts: Vec<_> = (0..1000u16)
+            .map(|i| {
+                let mut bytes = [0xff; 32];
+                bytes[..2].copy_from_slice(&i.to_le_bytes());
+                AccountId::from(bytes)
+            })
+            .collect();
+        let mut data = PSP34Data::new();
+        for (i, owner) in accounts.iter().enumerate() {
+            assert!(data.mint(*owner, Id::U16(i as u16), 0, 0).is_ok());
+        }
+        assert_eq!(data.owner_count(), Some(1000));
+        assert_eq!(data.owner_by_index(999), Some(accounts[999]));
+        assert_eq!(data.holders(998, 10), vec![accounts[998], accounts[999]]);
+
+        // A holder leaving is replaced by the last one.
+        assert!(data.burn(accounts[0], accounts[0], Id::U16(0)).is_ok());
+        assert_eq!(data.owner_count(), Some(999));
+        assert_eq!(data.owner_by_index(0), Some(accounts[999]));
+        assert_eq!(data.owner_by_index(999), None);
+        assert_eq!(data.holders(997, 10), vec![accounts[997], accounts[998]]);
+        assert!(data
+            .transfer(accounts[1], accounts[2], Id::U16(1), vec![])
+            .is_ok());
+        assert_eq!(data.owner_by_index(1), Some(accounts[998]));
+        assert_eq!(
+            data.holders(0, 3),
+            vec![accounts[999], accounts[998], accounts[2]]
+        );
+        assert_eq!(data.owner_count(), Some(998));
+    }
+
+    #[cfg(feature = "enumerable")]
+    #[ink::test]
+    fn all_tokens_with_owners_are_paged() {
+        let accounts: Vec<_> = (1..=3).map(|i| AccountId::from([i; 32])).collect();
+        let mut data = PSP34Data::new();
+        for id in 0..100u8 {
+            let owner = accounts[id as usize % 3];
+            assert!(data.mint(owner, Id::U8(id), 0, 0).is_ok());
+        }
+
+        let first = data.all_tokens_with_owners_page(0, 10);
+        assert_eq!(first.len(), 10);
+        for (index, (id, owner)) in first.iter().enumerate() {
+            assert_eq!(data.token_by_index(index as u128), Ok(id.clone()));
+            assert_eq!(data.owner_of(id), Some(*owner));
+        }
+        assert_eq!(
+            data.all_tokens_with_owners_page(0, u32::MAX).len(),
+            MAX_PAGE_SIZE as usize
+        );
+        assert_eq!(data.all_tokens_with_owners_page(95, 10).len(), 5);
+        assert!(data.all_tokens_with_owners_page(100, 10).is_empty());
+        assert!(data.all_tokens_with_owners_page(u128::MAX, 10).is_empty());
+
+        let mut all = Vec::new();
+        for page in 0.. {
+            let chunk = data.all_tokens_with_owners_page(page * 32, 32);
+            if chunk.is_empty() {
+                break;
             }
+            all.extend(chunk);
+        }
+        all.sort_by_key(|(id, _)| u128::try_from(id.clone()).unwrap());
+        let expected: Vec<_> = (0..100u8)
+            .map(|id| (Id::U8(id), accounts[id as usize % 3]))
+            .collect();
+        assert_eq!(all, expected);
+    }
 
-            if owner != caller && !self.allowance(owner, caller, None) {
-                return Err(PSP34Error::NotApproved);
+    #[ink::test]
+    fn mint_burn_events_follow_configured_mode() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let modes = [
+            MintBurnEvents::Transfer,
+            MintBurnEvents::Dedicated,
+            MintBurnEvents::Both,
+        ];
+        // The instances share the storage, so each mode uses its own token.
+        for (index, mode) in modes.into_iter().enumerate() {
+            let id = Id::U8(index as u8);
+            let mut data = PSP34DataBuilder::new().mint_burn_events(mode).build();
+            assert_eq!(data.mint_burn_events(), mode);
+
+            let minted = data.mint(alice, id.clone(), 0, 0).unwrap();
+            assert!(data.approve(alice, bob, Some(id.clone()), true).is_ok());
+            let burned = data.burn(bob, alice, id.clone()).unwrap();
+
+            let transfer_in = PSP34Event::Transfer {
+                from: None,
+                to: Some(alice),
+                id: id.clone(),
+            };
+            let transfer_out = PSP34Event::Transfer {
+                from: Some(alice),
+                to: None,
+                id: id.clone(),
+            };
+            let mint = PSP34Event::Minted {
+                to: alice,
+                id: id.clone(),
+            };
+            let burn = PSP34Event::Burned {
+                from: alice,
+                caller: bob,
+                id: id.clone(),
+            };
+            let (expected_minted, expected_burned) = match mode {
+                MintBurnEvents::Transfer => (vec![transfer_in], vec![transfer_out]),
+                MintBurnEvents::Dedicated => (vec![mint], vec![burn]),
+                MintBurnEvents::Both => (vec![transfer_in, mint], vec![transfer_out, burn]),
+            };
+            assert_eq!(minted, expected_minted);
+            assert_eq!(burned, expected_burned);
+        }
+
+        let mut data = PSP34DataBuilder::new()
+            .mint_burn_events(MintBurnEvents::Dedicated)
+            .build();
+        let (_, events) = data.mint_sequential(alice, 2, 0, 0).unwrap();
+        assert_eq!(
+            events.iter().map(PSP34Event::kind).collect::<Vec<_>>(),
+            vec![PSP34EventKind::Minted; 2]
+        );
+    }
+
+    #[ink::test]
+    fn config_disables_transfers_and_burning() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        assert_eq!(PSP34Data::new().config(), Config::default());
+        // The instances share the storage, so each configuration uses its own tokens.
+        let mut next_id = 0u8;
+        for transferable in [true, false] {
+            for burnable in [true, false] {
+                let config = Config {
+                    transferable,
+                    burnable,
+                };
+                let mut data = PSP34Data::new_with_config(config);
+                assert_eq!(data.config(), config);
+                let (first, second) = (Id::U8(next_id), Id::U8(next_id + 1));
+                next_id += 2;
+                // Minting is always allowed.
+                assert!(data.mint(alice, first.clone(), 0, 0).is_ok());
+                assert!(data.mint(alice, second.clone(), 0, 0).is_ok());
+
+                let transfer = data.transfer(alice, bob, first.clone(), vec![]);
+                let batch =
+                    data.transfer_from_many(alice, vec![(alice, bob, second.clone())], vec![]);
+                let offer = data.offer_transfer(bob, alice, second.clone(), None, 0);
+                if transferable {
+                    assert!(transfer.is_ok() && batch.is_ok() && offer.is_ok());
+                    assert!(data.claim_transfer(alice, second.clone(), 0).is_ok());
+                } else {
+                    assert_eq!(transfer, Err(PSP34Error::TransfersDisabled));
+                    assert_eq!(batch, Err(PSP34Error::TransfersDisabled));
+                    assert_eq!(offer, Err(PSP34Error::TransfersDisabled));
+                }
+
+                let owner = data.owner_of(&first).unwrap();
+                let burn = data.burn(owner, owner, first.clone());
+                if burnable {
+                    assert!(burn.is_ok());
+                    assert_eq!(data.owner_of(&first), None);
+                } else {
+                    assert_eq!(burn, Err(PSP34Error::BurningDisabled));
+                    assert_eq!(data.owner_of(&first), Some(owner));
+                }
             }
+        }
+    }
 
-            if !approved && self.allowance(owner, operator, None) {
-                return Err(PSP34Error::Custom(String::from(
-                    "Cannot revoke approval for a single token, when the operator has approval for all tokens."
-                )));
+    #[ink::test]
+    fn balance_limit_exemption_and_raise() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let escrow = AccountId::from([3; 32]);
+        // The instances share the storage, so the unlimited one is checked first.
+        assert_eq!(
+            PSP34Data::new().raise_max_balance(5),
+            Err(PSP34Error::Custom(String::from(
+                "Balance limit can only be raised."
+            )))
+        );
+        let mut data = PSP34DataBuilder::new().max_balance(2).build();
+
+        // Fill Bob up to the limit, the next transfer in fails.
+        assert!(data.mint(alice, Id::U8(0), 0, 0).is_ok());
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(data.transfer(alice, bob, Id::U8(0), vec![]).is_ok());
+        assert!(data.transfer(alice, bob, Id::U8(1), vec![]).is_ok());
+        assert!(data.mint(alice, Id::U8(2), 0, 0).is_ok());
+        assert_eq!(
+            data.transfer(alice, bob, Id::U8(2), vec![]),
+            Err(PSP34Error::MaxBalanceExceeded)
+        );
+        assert_eq!(
+            data.mint(bob, Id::U8(3), 0, 0),
+            Err(PSP34Error::MaxBalanceExceeded)
+        );
+
+        // An exempt account is not limited, until the exemption is removed.
+        data.set_balance_limit_exempt(escrow, true);
+        assert!(data.is_balance_limit_exempt(escrow));
+        assert!(data.transfer(bob, escrow, Id::U8(0), vec![]).is_ok());
+        assert!(data.transfer(bob, escrow, Id::U8(1), vec![]).is_ok());
+        assert!(data.transfer(alice, escrow, Id::U8(2), vec![]).is_ok());
+        assert!(data.mint(escrow, Id::U8(3), 0, 0).is_ok());
+        data.set_balance_limit_exempt(escrow, false);
+        assert!(!data.is_balance_limit_exempt(escrow));
+        assert_eq!(
+            data.mint(escrow, Id::U8(4), 0, 0),
+            Err(PSP34Error::MaxBalanceExceeded)
+        );
+
+        // An account over the limit can still send its tokens away, in a batch as well.
+        assert!(data
+            .transfer_from_many(escrow, vec![(escrow, alice, Id::U8(3))], vec![])
+            .is_ok());
+        assert_eq!(data.balance_of(escrow), 3);
+        assert!(data.transfer(escrow, alice, Id::U8(2), vec![]).is_ok());
+        assert_eq!(
+            data.transfer_from_many(escrow, vec![(escrow, alice, Id::U8(0))], vec![]),
+            Err(PSP34Error::MaxBalanceExceeded)
+        );
+
+        // The limit can be raised, but not lowered.
+        assert!(data.raise_max_balance(1).is_err());
+        assert_eq!(data.max_balance(), Some(2));
+        assert_eq!(data.raise_max_balance(5), Ok(()));
+        assert_eq!(data.max_balance(), Some(5));
+        assert!(data.mint(escrow, Id::U8(4), 0, 0).is_ok());
+    }
+
+    #[ink::test]
+    fn self_transfer_follows_configured_mode() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let modes = [
+            SelfTransferMode::Noop,
+            SelfTransferMode::Emit,
+            SelfTransferMode::Reject,
+        ];
+        // The instances share the storage, so each mode uses its own token.
+        for (index, mode) in modes.into_iter().enumerate() {
+            let id = Id::U8(index as u8);
+            let mut data = PSP34DataBuilder::new().self_transfer_mode(mode).build();
+            assert_eq!(data.self_transfer_mode(), mode);
+            assert!(data.mint(alice, id.clone(), 0, 0).is_ok());
+            data.set_escrow(alice, true);
+
+            let transfer = data.transfer(alice, alice, id.clone(), vec![]);
+            let consigned = data.transfer_with_approval(alice, alice, id.clone(), true, vec![]);
+            let consigned_by_stranger =
+                data.transfer_with_approval(bob, alice, id.clone(), true, vec![]);
+            let batch = data.transfer_from_many(alice, vec![(alice, alice, id.clone())], vec![]);
+            let simulated = data.simulate_transfer(alice, alice, &id);
+            let by_stranger = data.transfer(bob, alice, id.clone(), vec![]);
+            let self_transfer = PSP34Event::Transfer {
+                from: Some(alice),
+                to: Some(alice),
+                id: id.clone(),
+            };
+            let rejected =
+                || PSP34Error::Custom(String::from("Cannot transfer a token to its owner."));
+            match mode {
+                SelfTransferMode::Noop => {
+                    assert_eq!(transfer, Ok(EventList::new()));
+                    assert_eq!(consigned, Ok(EventList::new()));
+                    assert_eq!(consigned_by_stranger, Ok(EventList::new()));
+                    assert_eq!(batch, Ok(EventList::new()));
+                    assert_eq!(simulated, Ok(vec![]));
+                    assert_eq!(by_stranger, Ok(EventList::new()));
+                }
+                SelfTransferMode::Emit => {
+                    assert_eq!(transfer, Ok(EventList::from(self_transfer.clone())));
+                    assert_eq!(consigned, Ok(EventList::from(self_transfer.clone())));
+                    assert_eq!(consigned_by_stranger, Err(PSP34Error::NotApproved));
+                    assert_eq!(batch, Ok(EventList::from(self_transfer)));
+                    assert_eq!(simulated, Ok(vec![PSP34EventKind::Transfer]));
+                    assert_eq!(by_stranger, Err(PSP34Error::NotApproved));
+                }
+                SelfTransferMode::Reject => {
+                    assert_eq!(transfer, Err(rejected()));
+                    assert_eq!(consigned, Err(rejected()));
+                    assert_eq!(consigned_by_stranger, Err(PSP34Error::NotApproved));
+                    assert_eq!(batch, Err(rejected()));
+                    assert_eq!(simulated, Err(rejected()));
+                    assert_eq!(by_stranger, Err(PSP34Error::NotApproved));
+                }
             }
-            caller = owner;
+            assert_eq!(data.owner_of(&id), Some(alice));
+            assert_eq!(data.transfer_count(&id), 0);
+            assert!(!data.has_exact_approval(alice, alice, Some(&id)));
         }
+    }
 
-        if approved {
-            self.operator_approvals
-                .insert((caller, operator, id.as_ref()), &());
-        } else {
-            self.operator_approvals
-                .remove((caller, operator, id.as_ref()));
+    #[ink::test]
+    fn burned_count_tracks_all_burns() {
+        let alice = AccountId::from([1; 32]);
+        let mut data = PSP34Data::new();
+        let check = |data: &PSP34Data| {
+            assert_eq!(
+                data.minted_count(),
+                data.total_supply() + data.total_burned()
+            );
+        };
+        assert_eq!(data.total_burned(), 0);
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(data.mint_sequential(alice, 3, 0, 0).is_ok());
+        check(&data);
+        assert!(data.burn(alice, alice, Id::U8(1)).is_ok());
+        assert!(data.burn(alice, alice, Id::U128(0)).is_ok());
+        check(&data);
+        // Minting a burned id again keeps its burn counted.
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert_eq!(data.total_burned(), 2);
+        check(&data);
+        assert!(data.admin_burn(alice, Id::U8(1), vec![]).is_ok());
+        assert_eq!(data.total_burned(), 3);
+        check(&data);
+
+        // Storage from before the counter existed derives it in the migration.
+        data.burned_count.set(&0);
+        data.storage_version.set(&1);
+        assert_eq!(data.migrate(), Ok(CURRENT_VERSION));
+        assert_eq!(data.total_burned(), 3);
+    }
+
+    #[ink::test]
+    fn teleport_burn_mode_round_trip() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let bridge = AccountId::from([3; 32]);
+        let mut data = PSP34Data::new();
+        data.set_bridge_authority(Some(bridge));
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+
+        assert_eq!(
+            data.teleport_out(bob, Id::U8(1), 2000, vec![7; 20]),
+            Err(PSP34Error::NotApproved)
+        );
+        let events = data
+            .teleport_out(alice, Id::U8(1), 2000, vec![7; 20])
+            .unwrap();
+        assert_eq!(
+            events.iter().map(PSP34Event::kind).collect::<Vec<_>>(),
+            vec![PSP34EventKind::Transfer, PSP34EventKind::TeleportedOut]
+        );
+        assert_eq!(data.owner_of(&Id::U8(1)), None);
+        assert_eq!(
+            data.outbound_teleport(0),
+            Some(OutboundTeleport {
+                id: Id::U8(1),
+                from: alice,
+                dest_chain: 2000,
+                dest_account: vec![7; 20],
+            })
+        );
+
+        // Only the authority can bring the token back, which mints it.
+        assert_eq!(
+            data.teleport_in(alice, Id::U8(1), bob, vec![], 0, 0),
+            Err(PSP34Error::NotApproved)
+        );
+        let events = data
+            .teleport_in(bridge, Id::U8(1), bob, vec![], 0, 0)
+            .unwrap();
+        assert_eq!(
+            events.iter().map(PSP34Event::kind).collect::<Vec<_>>(),
+            vec![PSP34EventKind::Transfer, PSP34EventKind::TeleportedIn]
+        );
+        assert_eq!(data.owner_of(&Id::U8(1)), Some(bob));
+        assert_eq!(
+            data.teleport_in(bridge, Id::U8(1), bob, vec![], 0, 0),
+            Err(PSP34Error::TokenExists)
+        );
+    }
+
+    #[ink::test]
+    fn teleport_out_follows_the_config() {
+        let alice = AccountId::from([1; 32]);
+        let mut data = PSP34Data::new_with_config(Config {
+            transferable: false,
+            burnable: true,
+        });
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert_eq!(
+            data.teleport_out(alice, Id::U8(1), 2000, vec![7; 20]),
+            Err(PSP34Error::TransfersDisabled)
+        );
+        assert_eq!(data.owner_of(&Id::U8(1)), Some(alice));
+        assert_eq!(data.outbound_teleport(0), None);
+    }
+
+    #[ink::test]
+    fn teleport_out_in_burn_mode_needs_burnable_tokens() {
+        let alice = AccountId::from([1; 32]);
+        let mut data = PSP34Data::new_with_config(Config {
+            transferable: true,
+            burnable: false,
+        });
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert_eq!(
+            data.teleport_out(alice, Id::U8(1), 2000, vec![7; 20]),
+            Err(PSP34Error::BurningDisabled)
+        );
+        assert_eq!(data.owner_of(&Id::U8(1)), Some(alice));
+        assert_eq!(data.outbound_teleport(0), None);
+    }
+
+    #[ink::test]
+    fn teleport_out_in_lock_mode_keeps_non_burnable_tokens() {
+        let alice = AccountId::from([1; 32]);
+        let mut data = PSP34DataBuilder::new()
+            .config(Config {
+                transferable: true,
+                burnable: false,
+            })
+            .teleport_mode(TeleportMode::Lock)
+            .build();
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(data
+            .teleport_out(alice, Id::U8(1), 2000, vec![7; 20])
+            .is_ok());
+        assert!(data.is_locked(&Id::U8(1)));
+    }
+
+    #[ink::test]
+    fn teleport_lock_mode_round_trip() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let bridge = AccountId::from([3; 32]);
+        let mut data = PSP34DataBuilder::new()
+            .teleport_mode(TeleportMode::Lock)
+            .build();
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        // Without an authority nobody can teleport in.
+        assert_eq!(
+            data.teleport_in(bridge, Id::U8(2), bob, vec![], 0, 0),
+            Err(PSP34Error::NotApproved)
+        );
+        data.set_bridge_authority(Some(bridge));
+
+        let events = data
+            .teleport_out(alice, Id::U8(1), 2000, vec![7; 20])
+            .unwrap();
+        assert_eq!(
+            events.iter().map(PSP34Event::kind).collect::<Vec<_>>(),
+            vec![PSP34EventKind::TeleportedOut]
+        );
+        assert!(data.is_locked(&Id::U8(1)));
+        assert_eq!(data.owner_of(&Id::U8(1)), Some(alice));
+        let locked = || PSP34Error::Custom(String::from("Token is locked in the bridge."));
+        assert_eq!(data.transfer(alice, bob, Id::U8(1), vec![]), Err(locked()));
+        assert_eq!(data.burn(alice, alice, Id::U8(1)), Err(locked()));
+
+        // The token comes back to Bob, who bought it on the other chain.
+        let events = data
+            .teleport_in(bridge, Id::U8(1), bob, vec![], 0, 0)
+            .unwrap();
+        assert_eq!(
+            events.iter().map(PSP34Event::kind).collect::<Vec<_>>(),
+            vec![PSP34EventKind::Transfer, PSP34EventKind::TeleportedIn]
+        );
+        assert!(!data.is_locked(&Id::U8(1)));
+        assert_eq!(data.owner_of(&Id::U8(1)), Some(bob));
+        assert_eq!((data.balance_of(alice), data.balance_of(bob)), (0, 1));
+        assert!(data.transfer(bob, alice, Id::U8(1), vec![]).is_ok());
+    }
+
+    #[test]
+    fn event_list_keeps_push_order() {
+        let transfer = |id| PSP34Event::Transfer {
+            from: None,
+            to: None,
+            id: Id::U8(id),
+        };
+        let mut events = EventList::new();
+        assert!(events.is_empty());
+        for id in 0..5 {
+            events.push(transfer(id));
+            assert_eq!(events.len(), id as usize + 1);
+            assert_eq!(events[id as usize], transfer(id));
         }
+        let expected: Vec<_> = (0..5).map(transfer).collect();
+        assert_eq!(events, expected);
+        assert_eq!(events.iter().cloned().collect::<Vec<_>>(), expected);
+        assert_eq!(events.clone().into_vec(), expected);
+        assert_eq!(EventList::from(expected.clone()), events);
+        assert_eq!(EventList::from(transfer(0)).into_vec(), vec![transfer(0)]);
+        assert_eq!(EventList::new().into_iter().count(), 0);
+    }
 
-        Ok(vec![PSP34Event::Approval {
-            owner: caller,
-            operator,
-            id,
-            approved,
-        }])
+    #[ink::test]
+    fn multi_event_operations_deliver_all_events_in_order() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let mut data = PSP34Data::new();
+        let (ids, events) = data.mint_sequential(alice, 3, 0, 0).unwrap();
+        assert_eq!(
+            events.into_vec(),
+            ids.clone()
+                .map(|id| PSP34Event::Transfer {
+                    from: None,
+                    to: Some(alice),
+                    id: Id::U128(id),
+                })
+                .collect::<Vec<_>>()
+        );
+
+        let transfers: Vec<_> = ids.map(|id| (alice, bob, Id::U128(id))).collect();
+        let events = data
+            .transfer_from_many(alice, transfers.clone(), vec![])
+            .unwrap();
+        assert_eq!(
+            events.into_vec(),
+            transfers
+                .into_iter()
+                .map(|(from, to, id)| PSP34Event::Transfer {
+                    from: Some(from),
+                    to: Some(to),
+                    id,
+                })
+                .collect::<Vec<_>>()
+        );
+
+        assert!(data
+            .offer_transfer(bob, alice, Id::U128(0), None, 0)
+            .is_ok());
+        let events = data.claim_transfer(alice, Id::U128(0), 0).unwrap();
+        assert_eq!(
+            events.iter().map(PSP34Event::kind).collect::<Vec<_>>(),
+            vec![PSP34EventKind::Transfer, PSP34EventKind::TransferClaimed]
+        );
     }
 
-    /// Transfers `value` tokens from `caller` to `to`.
-    pub fn transfer(
-        &mut self,
-        caller: AccountId,
-        to: AccountId,
-        id: Id,
-        _data: Vec<u8>,
-    ) -> Result<Vec<PSP34Event>, PSP34Error> {
-        let owner = self.owner_of(&id).ok_or(PSP34Error::TokenNotExists)?;
+    #[ink::test]
+    fn unchecked_primitives_keep_storage_consistent() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let mut data = PSP34DataBuilder::new()
+            .config(Config {
+                transferable: true,
+                burnable: false,
+            })
+            .max_balance(2)
+            .build();
+        data.set_balance_limit_exempt(alice, true);
+        for id in 1..=3 {
+            assert!(data.mint_unchecked(alice, Id::U8(id), 0, 0).is_ok());
+        }
+        assert_eq!(
+            data.mint_unchecked(bob, Id::U8(1), 0, 0),
+            Err(PSP34Error::TokenExists)
+        );
+        assert!(data.offer_transfer(alice, bob, Id::U8(1), None, 0).is_ok());
 
-        if owner == to {
-            return Ok(vec![]);
+        // The pending offer does not stop the extension.
+        assert_eq!(
+            data.transfer_unchecked(alice, bob, Id::U8(1)),
+            Ok(EventList::from(PSP34Event::Transfer {
+                from: Some(alice),
+                to: Some(bob),
+                id: Id::U8(1),
+            }))
+        );
+        assert_eq!(data.pending_transfer(&Id::U8(1)), None);
+        assert_eq!(
+            data.transfer_unchecked(alice, bob, Id::U8(1)),
+            Err(PSP34Error::NotApproved)
+        );
+        assert_eq!(
+            data.transfer_unchecked(bob, bob, Id::U8(1)),
+            Ok(EventList::new())
+        );
+        assert!(data.transfer_unchecked(alice, bob, Id::U8(2)).is_ok());
+        // The balance limit still applies.
+        assert_eq!(
+            data.transfer_unchecked(alice, bob, Id::U8(3)),
+            Err(PSP34Error::MaxBalanceExceeded)
+        );
+        assert_eq!(data.owner_of(&Id::U8(3)), Some(alice));
+        assert_eq!((data.balance_of(alice), data.balance_of(bob)), (1, 2));
+        assert_eq!(data.transfer_count(&Id::U8(1)), 1);
+
+        // Neither does the configuration.
+        assert_eq!(
+            data.burn(bob, bob, Id::U8(1)),
+            Err(PSP34Error::BurningDisabled)
+        );
+        assert_eq!(
+            data.burn_unchecked(alice, Id::U8(1)),
+            Err(PSP34Error::NotApproved)
+        );
+        assert!(data.burn_unchecked(bob, Id::U8(1)).is_ok());
+        assert_eq!(data.owner_of(&Id::U8(1)), None);
+        assert_eq!((data.balance_of(alice), data.balance_of(bob)), (1, 1));
+        assert_eq!(data.total_supply(), 2);
+        assert_eq!(data.total_burned(), 1);
+
+        #[cfg(feature = "enumerable")]
+        {
+            assert_eq!(data.owners_token_by_index(alice, 0), Ok(Id::U8(3)));
+            assert_eq!(data.owners_token_by_index(bob, 0), Ok(Id::U8(2)));
+            assert!(data.owners_token_by_index(bob, 1).is_err());
+            let mut all = vec![
+                data.token_by_index(0).unwrap(),
+                data.token_by_index(1).unwrap(),
+            ];
+            all.sort_by_key(|id| id.encode());
+            assert_eq!(all, vec![Id::U8(2), Id::U8(3)]);
+            assert!(data.token_by_index(2).is_err());
         }
+    }
 
-        if owner != caller && !self.allowance(owner, caller, Some(&id)) {
-            return Err(PSP34Error::NotApproved);
+    // Collects everything observable about `accounts` and `ids` in `data`, which keeps
+    // its storage under the contract `callee`.
+    fn snapshot(data: &PSP34Data, callee: AccountId, accounts: &[AccountId], ids: &[Id]) -> String {
+        ink::env::test::set_callee::<ink::env::DefaultEnvironment>(callee);
+        let mut state = format!("{:?}", data.total_supply());
+        for id in ids {
+            state += &format!("{:?}{:?}", data.owner_of(id), data.transfer_count(id));
+        }
+        for owner in accounts {
+            state += &format!("{:?}", data.balance_of(*owner));
+            for operator in accounts {
+                state += &format!(
+                    "{:?}{:?}",
+                    data.allowance(*owner, *operator, None),
+                    data.approval_uses(*owner, *operator, None)
+                );
+                for id in ids {
+                    state += &format!("{:?}", data.has_exact_approval(*owner, *operator, Some(id)));
+                }
+            }
+            #[cfg(feature = "enumerable")]
+            for index in 0..data.balance_of(*owner) {
+                state += &format!("{:?}", data.owners_token_by_index(*owner, index as u128));
+            }
+        }
+        #[cfg(feature = "enumerable")]
+        {
+            for index in 0..data.total_supply() {
+                state += &format!("{:?}", data.token_by_index(index));
+            }
+            state += &format!("{:?}", data.holders(0, u32::MAX));
+        }
+        state
+    }
+
+    #[ink::test]
+    fn batch_transfers_match_single_transfers() {
+        let accounts: Vec<_> = (1..=4).map(|i| AccountId::from([i; 32])).collect();
+        let operator = accounts[0];
+        let ids: Vec<_> = (0..16).map(Id::U128).collect();
+        // Each instance keeps its storage under its own contract account.
+        let (batched_callee, single_callee) = (AccountId::from([8; 32]), AccountId::from([9; 32]));
+        let mut batched = PSP34Data::new();
+        let mut single = PSP34Data::new();
+        for (data, callee) in [(&mut batched, batched_callee), (&mut single, single_callee)] {
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(callee);
+            let recipients = (0..ids.len())
+                .map(|i| accounts[i % accounts.len()])
+                .collect();
+            assert!(data.mint_airdrop(recipients, 0, 0).is_ok());
+            assert!(data.approve(accounts[1], operator, None, true).is_ok());
+            assert!(data
+                .approve_limited(accounts[2], operator, None, 1_000)
+                .is_ok());
+            for id in [3, 11, 15] {
+                assert!(data
+                    .approve(accounts[3], operator, Some(Id::U128(id)), true)
+                    .is_ok());
+            }
         }
 
-        self.balance.decrease_balance(&owner, &id, false);
+        let mut seed: u64 = 42;
+        let mut next = |bound: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % bound
+        };
+        for _ in 0..20 {
+            // A batch of distinct tokens the operator may move, including self-transfers.
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(batched_callee);
+            let mut transfers = vec![];
+            for id in &ids {
+                let from = batched.owner_of(id).unwrap();
+                if next(2) == 0 && batched.allowance(from, operator, Some(id)) || from == operator {
+                    transfers.push((from, accounts[next(accounts.len())], id.clone()));
+                }
+            }
+            for _ in 0..ids.len() {
+                let (i, j) = (next(transfers.len().max(1)), next(transfers.len().max(1)));
+                if i < transfers.len() && j < transfers.len() {
+                    transfers.swap(i, j);
+                }
+            }
 
-        self.operator_approvals.remove((owner, caller, Some(&id)));
-        self.token_owner.remove(&id);
+            let batch_events = batched
+                .transfer_from_many(operator, transfers.clone(), vec![])
+                .unwrap();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(single_callee);
+            let mut single_events = EventList::new();
+            for (_, to, id) in transfers {
+                single_events.extend(single.transfer(operator, to, id, vec![]).unwrap());
+            }
+            // `transfer` reports the caller rather than the owner as `from`.
+            let moved = |events: EventList| -> Vec<_> {
+                events
+                    .into_iter()
+                    .map(|event| match event {
+                        PSP34Event::Transfer { to, id, .. } => (to, id),
+                        other => panic!("Unexpected event {:?}", other),
+                    })
+                    .collect()
+            };
+            assert_eq!(moved(batch_events), moved(single_events));
+            assert_eq!(
+                snapshot(&batched, batched_callee, &accounts, &ids),
+                snapshot(&single, single_callee, &accounts, &ids)
+            );
+        }
+    }
 
-        self.token_owner.insert(&id, &to);
-        self.balance.increase_balance(&to, &id, false)?;
+    #[ink::test]
+    fn batch_transfer_reads_and_writes_shared_cells_once() {
+        type E = ink::env::DefaultEnvironment;
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let operator = AccountId::from([3; 32]);
+        let mut data = PSP34Data::new();
+        assert!(data.mint_sequential(alice, 20, 0, 0).is_ok());
+        assert!(data.mint(bob, Id::U8(0), 0, 0).is_ok());
+        assert!(data.approve_limited(alice, operator, None, 100).is_ok());
 
-        Ok(vec![PSP34Event::Transfer {
-            from: Some(caller),
-            to: Some(to),
-            id,
-        }])
+        let callee = ink::env::test::callee::<E>();
+        let batch_rw = |data: &mut PSP34Data, ids: core::ops::Range<u128>| {
+            let transfers = ids.map(|id| (alice, bob, Id::U128(id))).collect();
+            let (reads, writes) = ink::env::test::get_contract_storage_rw::<E>(&callee);
+            assert!(data.transfer_from_many(operator, transfers, vec![]).is_ok());
+            let (all_reads, all_writes) = ink::env::test::get_contract_storage_rw::<E>(&callee);
+            (all_reads - reads, all_writes - writes)
+        };
+        let (short_reads, short_writes) = batch_rw(&mut data, 0..4);
+        let (long_reads, long_writes) = batch_rw(&mut data, 4..12);
+        // Every additional token costs only the reads of its owner, offer, bridge lock, hold,
+        // own approval and transfer count, and the writes of its owner and transfer count.
+        // The approval for all tokens, its uses and the balances are shared by the batch.
+        assert_eq!(long_reads - short_reads, 4 * 6);
+        assert_eq!(long_writes - short_writes, 4 * 2);
+        assert_eq!(data.approval_uses(alice, operator, None), Some(88));
+        assert_eq!((data.balance_of(alice), data.balance_of(bob)), (8, 13));
     }
 
-    /// Mints a token `id` to `account`.
-    pub fn mint(&mut self, account: AccountId, id: Id) -> Result<Vec<PSP34Event>, PSP34Error> {
-        if self.owner_of(&id).is_some() {
-            return Err(PSP34Error::TokenExists);
+    #[ink::test]
+    fn mint_random_assigns_every_id_once() {
+        let alice = AccountId::from([1; 32]);
+        assert_eq!(
+            PSP34Data::new().mint_random(alice, [0; 32], 0, 0),
+            Err(PSP34Error::Custom(String::from(
+                "Random mints require a supply limit."
+            )))
+        );
+        let mut data = PSP34DataBuilder::new().max_supply(64).build();
+        let mut ids = vec![];
+        for block in 0..64u32 {
+            let seed = [block as u8; 32];
+            let (id, events) = data.mint_random(alice, seed, block, 0).unwrap();
+            assert_eq!(
+                events.into_vec(),
+                vec![PSP34Event::Transfer {
+                    from: None,
+                    to: Some(alice),
+                    id: id.clone(),
+                }]
+            );
+            ids.push(id);
         }
-        self.balance.increase_balance(&account, &id, true)?;
-        self.token_owner.insert(&id, &account);
+        assert_eq!(
+            data.mint_random(alice, [0; 32], 64, 0),
+            Err(PSP34Error::MaxSupplyExceeded)
+        );
+        assert_eq!(data.total_supply(), 64);
+        assert_ne!(ids, (0..64).map(Id::U128).collect::<Vec<_>>());
+        ids.sort();
+        assert_eq!(ids, (0..64).map(Id::U128).collect::<Vec<_>>());
+    }
 
-        Ok(vec![PSP34Event::Transfer {
-            from: None,
-            to: Some(account),
-            id,
-        }])
+    #[ink::test]
+    fn clear_approval_on_transfer_follows_configured_mode() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let operator = AccountId::from([3; 32]);
+        for (i, clear) in [true, false].into_iter().enumerate() {
+            let (single, batched) = (Id::U8(2 * i as u8), Id::U8(2 * i as u8 + 1));
+            let mut data = PSP34DataBuilder::new()
+                .clear_approval_on_transfer(clear)
+                .build();
+            assert_eq!(data.clear_approval_on_transfer(), clear);
+            for id in [&single, &batched] {
+                assert!(data.mint(alice, id.clone(), 0, 0).is_ok());
+                assert!(data
+                    .approve(alice, operator, Some(id.clone()), true)
+                    .is_ok());
+            }
+            assert!(data.transfer(operator, bob, single.clone(), vec![]).is_ok());
+            assert!(data
+                .transfer_from_many(operator, vec![(alice, bob, batched.clone())], vec![])
+                .is_ok());
+
+            for id in [single, batched] {
+                assert_eq!(data.has_exact_approval(alice, operator, Some(&id)), !clear);
+                // A kept approval is not taken over by the new owner.
+                assert!(!data.allowance(bob, operator, Some(&id)));
+                assert!(!data.allowance(alice, operator, Some(&id)));
+                // It applies again once the token is back with the owner who granted it.
+                assert!(data.transfer(bob, alice, id.clone(), vec![]).is_ok());
+                assert_eq!(data.allowance(alice, operator, Some(&id)), !clear);
+                assert_eq!(data.transfer(operator, bob, id, vec![]).is_ok(), !clear);
+            }
+        }
     }
 
-    /// Burns token `id` from `account`, conducted by `caller`
-    pub fn burn(
-        &mut self,
-        caller: AccountId,
-        account: AccountId,
-        id: Id,
-    ) -> Result<Vec<PSP34Event>, PSP34Error> {
-        if self.owner_of(&id).is_none() {
-            return Err(PSP34Error::TokenNotExists);
+    #[ink::test]
+    fn kept_limited_approvals_are_used_up() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let operator = AccountId::from([3; 32]);
+        let mut data = PSP34DataBuilder::new()
+            .clear_approval_on_transfer(false)
+            .build();
+        for id in [Id::U8(0), Id::U8(1)] {
+            assert!(data.mint(alice, id.clone(), 0, 0).is_ok());
+            assert!(data.approve_limited(alice, operator, Some(id), 2).is_ok());
         }
-        if account != caller && !self.allowance(caller, account, None) {
-            return Err(PSP34Error::NotApproved);
+
+        // Each transfer by the operator, single or batched, consumes one use.
+        assert!(data.transfer(operator, bob, Id::U8(0), vec![]).is_ok());
+        assert!(data
+            .transfer_from_many(operator, vec![(alice, bob, Id::U8(1))], vec![])
+            .is_ok());
+        for id in [Id::U8(0), Id::U8(1)] {
+            assert_eq!(data.approval_uses(alice, operator, Some(&id)), Some(1));
+            assert!(data.transfer(bob, alice, id.clone(), vec![]).is_ok());
         }
-        self.balance.decrease_balance(&account, &id, true);
-        self.token_owner.remove(&id);
 
-        Ok(vec![PSP34Event::Transfer {
-            from: Some(account),
-            to: None,
-            id,
-        }])
+        // The last use removes the approval.
+        assert!(data.transfer(operator, bob, Id::U8(0), vec![]).is_ok());
+        assert!(data
+            .transfer_from_many(operator, vec![(alice, bob, Id::U8(1))], vec![])
+            .is_ok());
+        for id in [Id::U8(0), Id::U8(1)] {
+            assert!(!data.has_exact_approval(alice, operator, Some(&id)));
+            assert_eq!(data.approval_uses(alice, operator, Some(&id)), None);
+            assert!(data.transfer(bob, alice, id.clone(), vec![]).is_ok());
+            assert_eq!(
+                data.transfer(operator, bob, id, vec![]),
+                Err(PSP34Error::NotApproved)
+            );
+        }
     }
 
-    #[cfg(feature = "enumerable")]
-    pub fn owners_token_by_index(&self, owner: AccountId, index: u128) -> Result<Id, PSP34Error> {
-        self.balance.owners_token_by_index(owner, index)
+    #[ink::test]
+    fn balance_change_events_report_new_balances() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let changed = |account, new_balance| PSP34Event::BalanceChanged {
+            account,
+            new_balance,
+        };
+        let mut data = PSP34DataBuilder::new().balance_change_events(true).build();
+        assert_eq!(
+            data.mint(alice, Id::U8(0), 0, 0).unwrap().into_vec(),
+            vec![
+                PSP34Event::Transfer {
+                    from: None,
+                    to: Some(alice),
+                    id: Id::U8(0),
+                },
+                changed(alice, 1),
+            ]
+        );
+        let (_, events) = data.mint_airdrop(vec![alice, bob, alice], 0, 0).unwrap();
+        assert_eq!(
+            events
+                .into_iter()
+                .filter(|event| event.kind() == PSP34EventKind::BalanceChanged)
+                .collect::<Vec<_>>(),
+            vec![changed(alice, 2), changed(bob, 1), changed(alice, 3)]
+        );
+
+        // Both accounts of a transfer get the event, after the `Transfer` event,
+        // which the simulation reports as well.
+        assert_eq!(
+            data.simulate_transfer(alice, bob, &Id::U8(0)),
+            Ok(vec![
+                PSP34EventKind::Transfer,
+                PSP34EventKind::BalanceChanged,
+                PSP34EventKind::BalanceChanged,
+            ])
+        );
+        assert_eq!(
+            data.transfer(alice, bob, Id::U8(0), vec![])
+                .unwrap()
+                .into_vec(),
+            vec![
+                PSP34Event::Transfer {
+                    from: Some(alice),
+                    to: Some(bob),
+                    id: Id::U8(0),
+                },
+                changed(alice, 2),
+                changed(bob, 2),
+            ]
+        );
+        // In a batch each event reports the balance right after its transfer.
+        let events = data
+            .transfer_from_many(
+                alice,
+                vec![(alice, bob, Id::U128(0)), (alice, bob, Id::U128(2))],
+                vec![],
+            )
+            .unwrap();
+        assert_eq!(
+            events
+                .into_iter()
+                .filter(|event| event.kind() == PSP34EventKind::BalanceChanged)
+                .collect::<Vec<_>>(),
+            vec![
+                changed(alice, 1),
+                changed(bob, 3),
+                changed(alice, 0),
+                changed(bob, 4),
+            ]
+        );
+        assert_eq!(
+            data.burn(bob, bob, Id::U8(0)).unwrap().into_vec(),
+            vec![
+                PSP34Event::Transfer {
+                    from: Some(bob),
+                    to: None,
+                    id: Id::U8(0),
+                },
+                changed(bob, 3),
+            ]
+        );
+
+        // Without the option no such events are emitted.
+        let mut data = PSP34Data::new();
+        assert_eq!(data.mint(alice, Id::U8(9), 0, 0).unwrap().len(), 1);
+        assert_eq!(
+            data.transfer(alice, bob, Id::U8(9), vec![]).unwrap().len(),
+            1
+        );
     }
 
-    #[cfg(feature = "enumerable")]
-    pub fn token_by_index(&self, index: u128) -> Result<Id, PSP34Error> {
-        self.balance.token_by_index(index)
+    #[ink::test]
+    fn repeated_token_approval_writes_nothing() {
+        type E = ink::env::DefaultEnvironment;
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let mut data = PSP34Data::new();
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert_eq!(
+            data.approve(alice, bob, Some(Id::U8(1)), true)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let callee = ink::env::test::callee::<E>();
+        let (_, writes) = ink::env::test::get_contract_storage_rw::<E>(&callee);
+        assert_eq!(
+            data.approve(alice, bob, Some(Id::U8(1)), true),
+            Ok(EventList::new())
+        );
+        assert_eq!(
+            ink::env::test::get_contract_storage_rw::<E>(&callee).1,
+            writes
+        );
+        assert!(data.allowance(alice, bob, Some(&Id::U8(1))));
     }
-}
 
-impl Default for Id {
-    fn default() -> Self {
-        Self::U128(0)
+    #[ink::test]
+    fn prune_approvals_removes_only_orphaned_entries() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let mut data = PSP34Data::new();
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(data.mint(alice, Id::U8(2), 0, 0).is_ok());
+        assert!(data.approve_limited(alice, bob, Some(Id::U8(1)), 3).is_ok());
+        assert!(data.approve(alice, bob, Some(Id::U8(2)), true).is_ok());
+        assert!(data.burn(alice, alice, Id::U8(1)).is_ok());
+        assert!(data.has_exact_approval(alice, bob, Some(&Id::U8(1))));
+
+        let events = data.prune_approvals(vec![
+            (alice, bob, Id::U8(1)),
+            (alice, bob, Id::U8(1)),
+            (alice, bob, Id::U8(2)),
+            (bob, alice, Id::U8(3)),
+        ]);
+        assert_eq!(
+            events.into_vec(),
+            vec![PSP34Event::Approval {
+                owner: alice,
+                operator: bob,
+                id: Some(Id::U8(1)),
+                approved: false,
+            }]
+        );
+        assert!(!data.has_exact_approval(alice, bob, Some(&Id::U8(1))));
+        assert_eq!(data.approval_uses(alice, bob, Some(&Id::U8(1))), None);
+        // The approval of the existing token stays.
+        assert!(data.allowance(alice, bob, Some(&Id::U8(2))));
+
+        // Minting the token again does not revive the pruned approval.
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(!data.allowance(alice, bob, Some(&Id::U8(1))));
     }
-}
 
-impl From<Id> for u128 {
-    fn from(id: Id) -> Self {
-        match id {
-            Id::U8(val) => val as u128,
-            Id::U16(val) => val as u128,
-            Id::U32(val) => val as u128,
-            Id::U64(val) => val as u128,
-            Id::U128(val) => val,
-            Id::Bytes(val) => u128::from_be_bytes(val.as_slice().try_into().unwrap()),
+    #[ink::test]
+    fn admin_burn_revokes_approvals_of_the_token() {
+        let [alice, bob, charlie, dave, admin] = [1, 2, 3, 4, 5].map(|i| AccountId::from([i; 32]));
+        let mut data = PSP34Data::new();
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(data.mint(alice, Id::U8(2), 0, 0).is_ok());
+        assert!(data.approve(alice, bob, Some(Id::U8(1)), true).is_ok());
+        assert!(data
+            .approve_limited(alice, charlie, Some(Id::U8(1)), 2)
+            .is_ok());
+        assert!(data.approve(alice, dave, Some(Id::U8(1)), true).is_ok());
+        assert!(data.approve(alice, dave, Some(Id::U8(1)), false).is_ok());
+        assert!(data.approve(alice, bob, Some(Id::U8(2)), true).is_ok());
+
+        data.hold(&Id::U8(1), TokenHold::Listed).unwrap();
+        assert_eq!(
+            data.admin_burn(admin, Id::U8(1), vec![]),
+            Err(PSP34Error::Custom(String::from(
+                "Token is listed for sale."
+            )))
+        );
+        data.release(&Id::U8(1));
+
+        let revoked = |operator| PSP34Event::Approval {
+            owner: alice,
+            operator,
+            id: Some(Id::U8(1)),
+            approved: false,
+        };
+        assert_eq!(
+            data.admin_burn(admin, Id::U8(1), vec![bob, charlie, dave])
+                .unwrap()
+                .into_vec(),
+            vec![
+                revoked(bob),
+                revoked(charlie),
+                PSP34Event::Transfer {
+                    from: Some(alice),
+                    to: None,
+                    id: Id::U8(1),
+                },
+            ]
+        );
+        assert!(!data.has_exact_approval(alice, bob, Some(&Id::U8(1))));
+        assert_eq!(data.approval_uses(alice, charlie, Some(&Id::U8(1))), None);
+        // The approvals of other tokens stay.
+        assert!(data.allowance(alice, bob, Some(&Id::U8(2))));
+
+        // Minting the token again does not revive them.
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(!data.allowance(alice, bob, Some(&Id::U8(1))));
+        assert!(!data.allowance(alice, charlie, Some(&Id::U8(1))));
+    }
+
+    fn any_id() -> impl Strategy<Value = Id> {
+        prop_oneof![
+            any::<u8>().prop_map(Id::U8),
+            any::<u16>().prop_map(Id::U16),
+            any::<u32>().prop_map(Id::U32),
+            any::<u64>().prop_map(Id::U64),
+            any::<u128>().prop_map(Id::U128),
+            proptest::collection::vec(any::<u8>(), 0..=64).prop_map(Id::Bytes),
+            any::<[u8; 32]>().prop_map(Id::U256),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn ids_round_trip_through_scale(id in any_id()) {
+            prop_assert_eq!(Id::decode_all(&mut &id.encode()[..]), Ok(id));
+        }
+
+        #[test]
+        fn canonical_cmp_is_a_total_order(a in any_id(), b in any_id(), c in any_id()) {
+            prop_assert_eq!(a.canonical_cmp(&b), b.canonical_cmp(&a).reverse());
+            prop_assert_eq!(a.canonical_cmp(&b) == Ordering::Equal, a == b);
+            if a.canonical_cmp(&b).is_le() && b.canonical_cmp(&c).is_le() {
+                prop_assert!(a.canonical_cmp(&c).is_le());
+            }
+        }
+
+        #[test]
+        fn canonical_cmp_orders_numeric_ids_by_value(a in any::<u64>(), b in any::<u64>()) {
+            let mut u256 = [0; 32];
+            u256[24..].copy_from_slice(&b.to_be_bytes());
+            let ordering = Id::U64(a).canonical_cmp(&Id::U128(b as u128));
+            prop_assert_eq!(ordering, a.cmp(&b).then(Ordering::Less));
+            prop_assert_eq!(Id::U64(a).canonical_cmp(&Id::U256(u256)), ordering);
         }
     }
+
+    #[test]
+    fn edge_ids_round_trip_through_scale() {
+        for id in [
+            Id::U8(0),
+            Id::U128(u128::MAX),
+            Id::Bytes(vec![]),
+            Id::Bytes(vec![0xff; 64]),
+            Id::U256([0xff; 32]),
+        ] {
+            assert_eq!(Id::decode_all(&mut &id.encode()[..]), Ok(id));
+        }
+    }
+
+    #[test]
+    fn id_orderings_are_pinned() {
+        // The derived order compares the variants first.
+        assert!(Id::U8(200) < Id::U16(1));
+        assert!(Id::U128(u128::MAX) < Id::Bytes(vec![]));
+        assert!(Id::Bytes(vec![0xff; 64]) < Id::U256([0; 32]));
+
+        // The canonical order compares the values first.
+        let ordering = |a: Id, b: Id| a.canonical_cmp(&b);
+        assert_eq!(ordering(Id::U8(200), Id::U16(1)), Ordering::Greater);
+        assert_eq!(ordering(Id::U8(1), Id::U16(1)), Ordering::Less);
+        assert_eq!(
+            ordering(Id::U128(u128::MAX), Id::U256([0xff; 32])),
+            Ordering::Less
+        );
+        assert_eq!(
+            ordering(Id::U256([0xff; 32]), Id::Bytes(vec![])),
+            Ordering::Less
+        );
+        assert_eq!(
+            ordering(Id::Bytes(vec![1, 2]), Id::Bytes(vec![2])),
+            Ordering::Less
+        );
+    }
+
+    #[ink::test]
+    fn owner_count_follows_first_and_last_tokens() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let charlie = AccountId::from([3; 32]);
+        let mut data = PSP34Data::new();
+        assert_eq!(data.owner_count(), Some(0));
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(data.mint(alice, Id::U8(2), 0, 0).is_ok());
+        assert_eq!(data.owner_count(), Some(1));
+        assert!(data.mint_airdrop(vec![bob, charlie, bob], 0, 0).is_ok());
+        assert_eq!(data.owner_count(), Some(3));
+
+        // Only moving the last token of an account changes the count.
+        assert!(data.transfer(alice, bob, Id::U8(1), vec![]).is_ok());
+        assert_eq!(data.owner_count(), Some(3));
+        assert!(data.transfer(alice, bob, Id::U8(2), vec![]).is_ok());
+        assert_eq!(data.owner_count(), Some(2));
+        let transfers = vec![
+            (bob, alice, Id::U8(1)),
+            (bob, alice, Id::U128(0)),
+            (bob, alice, Id::U128(2)),
+            (bob, alice, Id::U8(2)),
+        ];
+        assert!(data.transfer_from_many(bob, transfers, vec![]).is_ok());
+        assert_eq!(data.owner_count(), Some(2));
+
+        // Burning all tokens of an account removes it.
+        assert!(data.burn(charlie, charlie, Id::U128(1)).is_ok());
+        assert_eq!(data.owner_count(), Some(1));
+        for id in [Id::U8(1), Id::U8(2), Id::U128(0), Id::U128(2)] {
+            assert!(data.burn(alice, alice, id).is_ok());
+        }
+        assert_eq!(data.owner_count(), Some(0));
+    }
+
+    #[cfg(not(feature = "enumerable"))]
+    #[ink::test]
+    fn owner_count_is_unknown_after_migrating_tokens() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let mut data = PSP34Data::new();
+        // Storage without tokens keeps its count.
+        data.storage_version.set(&2);
+        assert_eq!(data.migrate(), Ok(CURRENT_VERSION));
+        assert_eq!(data.owner_count(), Some(0));
+
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert!(data.mint(bob, Id::U8(2), 0, 0).is_ok());
+        data.storage_version.set(&2);
+        assert_eq!(data.migrate(), Ok(CURRENT_VERSION));
+        assert_eq!(data.owner_count(), None);
+        assert!(data.mint(bob, Id::U8(3), 0, 0).is_ok());
+        assert_eq!(data.owner_count(), None);
+
+        // Once no token is left, the count is known again.
+        for (owner, id) in [(alice, 1), (bob, 2), (bob, 3)] {
+            assert!(data.burn(owner, owner, Id::U8(id)).is_ok());
+        }
+        assert_eq!(data.owner_count(), Some(0));
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert_eq!(data.owner_count(), Some(1));
+    }
+
+    #[ink::test]
+    fn held_tokens_stay_with_their_owner() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let listed = || PSP34Error::Custom(String::from("Token is listed for sale."));
+        let mut data = PSP34Data::new();
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert_eq!(
+            data.hold(&Id::U8(2), TokenHold::Listed),
+            Err(PSP34Error::TokenNotExists)
+        );
+        assert_eq!(data.hold(&Id::U8(1), TokenHold::Listed), Ok(()));
+        assert_eq!(data.hold_of(&Id::U8(1)), Some(TokenHold::Listed));
+        assert_eq!(data.hold(&Id::U8(1), TokenHold::Listed), Err(listed()));
+
+        assert_eq!(data.transfer(alice, bob, Id::U8(1), vec![]), Err(listed()));
+        assert_eq!(data.burn(alice, alice, Id::U8(1)), Err(listed()));
+        assert_eq!(
+            data.offer_transfer(alice, bob, Id::U8(1), None, 0),
+            Err(listed())
+        );
+
+        // Moving the token with the unchecked primitive releases it.
+        assert!(data.transfer_unchecked(alice, bob, Id::U8(1)).is_ok());
+        assert_eq!(data.hold_of(&Id::U8(1)), None);
+        assert!(data.hold(&Id::U8(1), TokenHold::Listed).is_ok());
+        data.release(&Id::U8(1));
+        assert!(data.transfer(bob, alice, Id::U8(1), vec![]).is_ok());
+    }
+
+    #[ink::test]
+    fn collection_id_can_be_set_once() {
+        let contract = AccountId::from([7; 32]);
+        let mut data = PSP34Data::new();
+        assert_eq!(data.collection_id(contract), Id::Bytes(vec![7; 32]));
+        assert_eq!(data.set_collection_id(Id::U8(1)), Ok(()));
+        assert_eq!(data.collection_id(contract), Id::U8(1));
+        assert_eq!(
+            data.set_collection_id(Id::U8(2)),
+            Err(PSP34Error::Custom(String::from(
+                "Collection id is already set."
+            )))
+        );
+        assert_eq!(data.collection_id(contract), Id::U8(1));
+    }
+
+    #[ink::test]
+    fn zero_address_is_rejected_when_enabled() {
+        let alice = AccountId::from([1; 32]);
+        let zero = AccountId::from([0; 32]);
+        assert!(is_zero(&zero));
+        assert!(!is_zero(&alice));
+        let mut last = [0; 32];
+        last[31] = 1;
+        assert!(!is_zero(&AccountId::from(last)));
+
+        let mut data = PSP34DataBuilder::new().reject_zero_address(true).build();
+        assert!(data.reject_zero_address());
+        let zero_recipient = || PSP34Error::Custom(String::from("Recipient is the zero address."));
+        assert_eq!(data.mint(zero, Id::U8(1), 0, 0), Err(zero_recipient()));
+        assert_eq!(
+            data.mint_unchecked(zero, Id::U8(1), 0, 0),
+            Err(zero_recipient())
+        );
+        assert_eq!(
+            data.mint_airdrop(vec![alice, zero], 0, 0),
+            Err(zero_recipient())
+        );
+        assert_eq!(data.total_supply(), 0);
+
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+        assert_eq!(
+            data.transfer(alice, zero, Id::U8(1), vec![]),
+            Err(zero_recipient())
+        );
+        assert_eq!(
+            data.transfer_from_many(alice, vec![(alice, zero, Id::U8(1))], vec![]),
+            Err(zero_recipient())
+        );
+        assert_eq!(
+            data.transfer_unchecked(alice, zero, Id::U8(1)),
+            Err(zero_recipient())
+        );
+        assert_eq!(
+            data.simulate_transfer(alice, zero, &Id::U8(1)),
+            Err(zero_recipient())
+        );
+        assert_eq!(data.owner_of(&Id::U8(1)), Some(alice));
+
+        // Disabled by default.
+        let mut data = PSP34Data::new();
+        assert!(!data.reject_zero_address());
+        assert!(data.mint(zero, Id::U8(2), 0, 0).is_ok());
+    }
+
+    #[ink::test]
+    fn reset_approval_revokes_only_single_token_grants() {
+        let alice = AccountId::from([1; 32]);
+        let bob = AccountId::from([2; 32]);
+        let charlie = AccountId::from([3; 32]);
+        let mut data = PSP34Data::new();
+        assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+
+        assert!(data.approve(alice, bob, Some(Id::U8(1)), true).is_ok());
+        assert_eq!(
+            data.reset_approval(charlie, bob, Id::U8(1)),
+            Err(PSP34Error::NotApproved)
+        );
+        let events = data.reset_approval(alice, bob, Id::U8(1)).unwrap();
+        assert_eq!(
+            events.into_vec(),
+            vec![PSP34Event::Approval {
+                owner: alice,
+                operator: bob,
+                id: Some(Id::U8(1)),
+                approved: false,
+            }]
+        );
+        assert!(!data.allowance(alice, bob, Some(&Id::U8(1))));
+        // Nothing left to reset.
+        assert_eq!(
+            data.reset_approval(alice, bob, Id::U8(1)),
+            Ok(EventList::new())
+        );
+        assert_eq!(
+            data.reset_approval(alice, bob, Id::U8(2)),
+            Err(PSP34Error::TokenNotExists)
+        );
+
+        // The approval for all tokens has to be revoked with `approve`.
+        assert!(data.approve(alice, bob, Some(Id::U8(1)), true).is_ok());
+        assert!(data.approve(alice, bob, None, true).is_ok());
+        assert_eq!(
+            data.reset_approval(alice, bob, Id::U8(1)),
+            Err(PSP34Error::ApprovedForAll)
+        );
+        assert!(data.allowance(alice, bob, Some(&Id::U8(1))));
+        // An operator approved for all can reset the approvals of others.
+        assert!(data.approve(alice, charlie, Some(Id::U8(1)), true).is_ok());
+        assert!(data.reset_approval(bob, charlie, Id::U8(1)).is_ok());
+        assert!(!data.allowance(alice, charlie, Some(&Id::U8(1))));
+
+        assert!(data.approve(alice, bob, None, false).is_ok());
+        assert!(data.reset_approval(alice, bob, Id::U8(1)).is_ok());
+        assert!(!data.allowance(alice, bob, Some(&Id::U8(1))));
+    }
 }
@@ -1,7 +1,10 @@
-use ink::prelude::string::String;
+use ink::{prelude::string::String, LangError, MessageResult};
 
+/// New variants may be added in minor releases, so matches on this type outside
+/// of the crate need a wildcard arm. Their codes never change, see `code()`.
 #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
 #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+#[non_exhaustive]
 pub enum PSP34Error {
     /// Custom error type for cases if writer of traits added own restrictions
     Custom(String),
@@ -15,4 +18,274 @@ pub enum PSP34Error {
     TokenNotExists,
     /// Returned if safe transfer check fails
     SafeTransferCheckFailed(String),
+    /// Returned if the balance of the recipient would exceed its limit.
+    MaxBalanceExceeded,
+    /// Returned if the total supply would exceed its limit.
+    MaxSupplyExceeded,
+    /// Returned if the operation was executed after its deadline.
+    Expired,
+    /// Returned if the collection does not allow transferring tokens.
+    TransfersDisabled,
+    /// Returned if the collection does not allow burning tokens.
+    BurningDisabled,
+    /// Returned if an approval for a single token cannot be reset, because the operator
+    /// is approved for all tokens of the owner, which has to be revoked separately.
+    ApprovedForAll,
+}
+
+impl PSP34Error {
+    /// Returns a stable numeric code of the error variant, for clients that cannot
+    /// decode the whole enum. The codes match the SCALE variant indices:
+    ///
+    /// | Code | Variant                   |
+    /// |------|---------------------------|
+    /// | 0    | `Custom` (reserved)       |
+    /// | 1    | `SelfApprove`             |
+    /// | 2    | `NotApproved`             |
+    /// | 3    | `TokenExists`             |
+    /// | 4    | `TokenNotExists`          |
+    /// | 5    | `SafeTransferCheckFailed` |
+    /// | 6    | `MaxBalanceExceeded`      |
+    /// | 7    | `MaxSupplyExceeded`       |
+    /// | 8    | `Expired`                 |
+    /// | 9    | `TransfersDisabled`       |
+    /// | 10   | `BurningDisabled`         |
+    /// | 11   | `ApprovedForAll`          |
+    ///
+    /// The message carried by `Custom` and `SafeTransferCheckFailed` is not reflected in the code.
+    pub fn code(&self) -> u32 {
+        match self {
+            PSP34Error::Custom(_) => 0,
+            PSP34Error::SelfApprove => 1,
+            PSP34Error::NotApproved => 2,
+            PSP34Error::TokenExists => 3,
+            PSP34Error::TokenNotExists => 4,
+            PSP34Error::SafeTransferCheckFailed(_) => 5,
+            PSP34Error::MaxBalanceExceeded => 6,
+            PSP34Error::MaxSupplyExceeded => 7,
+            PSP34Error::Expired => 8,
+            PSP34Error::TransfersDisabled => 9,
+            PSP34Error::BurningDisabled => 10,
+            PSP34Error::ApprovedForAll => 11,
+        }
+    }
+
+    /// Returns whether the error is `Custom`, with any message.
+    pub fn is_custom(&self) -> bool {
+        matches!(self, PSP34Error::Custom(_))
+    }
+
+    /// Returns whether the error is `SelfApprove`.
+    pub fn is_self_approve(&self) -> bool {
+        matches!(self, PSP34Error::SelfApprove)
+    }
+
+    /// Returns whether the error is `NotApproved`.
+    pub fn is_not_approved(&self) -> bool {
+        matches!(self, PSP34Error::NotApproved)
+    }
+
+    /// Returns whether the error is `TokenExists`.
+    pub fn is_token_exists(&self) -> bool {
+        matches!(self, PSP34Error::TokenExists)
+    }
+
+    /// Returns whether the error is `TokenNotExists`.
+    pub fn is_token_not_exists(&self) -> bool {
+        matches!(self, PSP34Error::TokenNotExists)
+    }
+
+    /// Returns whether the error is `SafeTransferCheckFailed`, with any message.
+    pub fn is_safe_transfer_check_failed(&self) -> bool {
+        matches!(self, PSP34Error::SafeTransferCheckFailed(_))
+    }
+
+    /// Returns whether the error is `MaxBalanceExceeded`.
+    pub fn is_max_balance_exceeded(&self) -> bool {
+        matches!(self, PSP34Error::MaxBalanceExceeded)
+    }
+
+    /// Returns whether the error is `MaxSupplyExceeded`.
+    pub fn is_max_supply_exceeded(&self) -> bool {
+        matches!(self, PSP34Error::MaxSupplyExceeded)
+    }
+
+    /// Returns whether the error is `Expired`.
+    pub fn is_expired(&self) -> bool {
+        matches!(self, PSP34Error::Expired)
+    }
+
+    /// Returns whether the error is `TransfersDisabled`.
+    pub fn is_transfers_disabled(&self) -> bool {
+        matches!(self, PSP34Error::TransfersDisabled)
+    }
+
+    /// Returns whether the error is `BurningDisabled`.
+    pub fn is_burning_disabled(&self) -> bool {
+        matches!(self, PSP34Error::BurningDisabled)
+    }
+
+    /// Returns whether the error is `ApprovedForAll`.
+    pub fn is_approved_for_all(&self) -> bool {
+        matches!(self, PSP34Error::ApprovedForAll)
+    }
+}
+
+/// Error of a cross-contract call to a PSP34 message, from any of the layers
+/// that can fail: the environment, ink! itself and the token.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CallError {
+    /// The call could not be executed, e.g. the callee trapped or does not exist.
+    Env(ink::env::Error),
+    /// The callee could not dispatch the message, e.g. its selector is unknown.
+    Lang(LangError),
+    /// The message was executed and returned an error.
+    Token(PSP34Error),
+}
+
+impl CallError {
+    /// Flattens the result of `try_invoke()` on a call to a PSP34 message, made with
+    /// `contract_ref!` or `build_call`, into a single `Result`.
+    pub fn flatten<T>(
+        result: Result<MessageResult<Result<T, PSP34Error>>, ink::env::Error>,
+    ) -> Result<T, CallError> {
+        Ok(result???)
+    }
+}
+
+impl From<CallError> for PSP34Error {
+    /// Keeps the error returned by the token, describing the failures of the other layers.
+    fn from(error: CallError) -> Self {
+        match error {
+            CallError::Env(_) => {
+                PSP34Error::Custom(String::from("Call to the token contract failed."))
+            }
+            CallError::Lang(_) => {
+                PSP34Error::Custom(String::from("Token contract could not dispatch the call."))
+            }
+            CallError::Token(error) => error,
+        }
+    }
+}
+
+impl From<ink::env::Error> for CallError {
+    fn from(error: ink::env::Error) -> Self {
+        CallError::Env(error)
+    }
+}
+
+impl From<LangError> for CallError {
+    fn from(error: LangError) -> Self {
+        CallError::Lang(error)
+    }
+}
+
+impl From<PSP34Error> for CallError {
+    fn from(error: PSP34Error) -> Self {
+        CallError::Token(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scale::Encode;
+
+    #[test]
+    fn error_codes_are_stable() {
+        let cases = [
+            (PSP34Error::Custom(String::from("any")), 0),
+            (PSP34Error::SelfApprove, 1),
+            (PSP34Error::NotApproved, 2),
+            (PSP34Error::TokenExists, 3),
+            (PSP34Error::TokenNotExists, 4),
+            (PSP34Error::SafeTransferCheckFailed(String::new()), 5),
+            (PSP34Error::MaxBalanceExceeded, 6),
+            (PSP34Error::MaxSupplyExceeded, 7),
+            (PSP34Error::Expired, 8),
+            (PSP34Error::TransfersDisabled, 9),
+            (PSP34Error::BurningDisabled, 10),
+            (PSP34Error::ApprovedForAll, 11),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.code(), code);
+            assert_eq!(error.encode()[0] as u32, code);
+        }
+    }
+
+    #[test]
+    fn predicates_match_single_variant() {
+        let errors = [
+            PSP34Error::Custom(String::from("any")),
+            PSP34Error::SelfApprove,
+            PSP34Error::NotApproved,
+            PSP34Error::TokenExists,
+            PSP34Error::TokenNotExists,
+            PSP34Error::SafeTransferCheckFailed(String::from("any")),
+            PSP34Error::MaxBalanceExceeded,
+            PSP34Error::MaxSupplyExceeded,
+            PSP34Error::Expired,
+            PSP34Error::TransfersDisabled,
+            PSP34Error::BurningDisabled,
+            PSP34Error::ApprovedForAll,
+        ];
+        let predicates: [fn(&PSP34Error) -> bool; 12] = [
+            PSP34Error::is_custom,
+            PSP34Error::is_self_approve,
+            PSP34Error::is_not_approved,
+            PSP34Error::is_token_exists,
+            PSP34Error::is_token_not_exists,
+            PSP34Error::is_safe_transfer_check_failed,
+            PSP34Error::is_max_balance_exceeded,
+            PSP34Error::is_max_supply_exceeded,
+            PSP34Error::is_expired,
+            PSP34Error::is_transfers_disabled,
+            PSP34Error::is_burning_disabled,
+            PSP34Error::is_approved_for_all,
+        ];
+        for (i, error) in errors.iter().enumerate() {
+            for (j, predicate) in predicates.iter().enumerate() {
+                assert_eq!(predicate(error), i == j, "{:?}", error);
+            }
+        }
+        let result: Result<(), PSP34Error> = Err(PSP34Error::Custom(String::from("other")));
+        assert!(result.is_err_and(|error| error.is_custom()));
+    }
+
+    #[test]
+    fn call_errors_map_each_failure_layer() {
+        let env: Result<MessageResult<Result<u32, PSP34Error>>, _> =
+            Err(ink::env::Error::CalleeTrapped);
+        assert_eq!(
+            CallError::flatten(env),
+            Err(CallError::Env(ink::env::Error::CalleeTrapped))
+        );
+        let lang = Ok(Err(LangError::CouldNotReadInput));
+        assert_eq!(
+            CallError::flatten::<u32>(lang),
+            Err(CallError::Lang(LangError::CouldNotReadInput))
+        );
+        let token = Ok(Ok(Err(PSP34Error::NotApproved)));
+        assert_eq!(
+            CallError::flatten::<u32>(token),
+            Err(CallError::Token(PSP34Error::NotApproved))
+        );
+        assert_eq!(CallError::flatten(Ok(Ok(Ok(7)))), Ok(7));
+    }
+
+    #[test]
+    fn call_errors_convert_to_token_errors() {
+        assert_eq!(
+            PSP34Error::from(CallError::Env(ink::env::Error::CalleeTrapped)),
+            PSP34Error::Custom(String::from("Call to the token contract failed."))
+        );
+        assert_eq!(
+            PSP34Error::from(CallError::Lang(LangError::CouldNotReadInput)),
+            PSP34Error::Custom(String::from("Token contract could not dispatch the call."))
+        );
+        assert_eq!(
+            PSP34Error::from(CallError::Token(PSP34Error::NotApproved)),
+            PSP34Error::NotApproved
+        );
+    }
 }
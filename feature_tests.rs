@@ -0,0 +1,108 @@
+//! Checks that the public API keeps the same shape in every combination of the
+//! `enumerable` and `contract` features. Each combination gets its own module, so
+//! `cargo test` with the given features compiles and runs exactly one of them.
+
+use crate::{EventList, Id, PSP34Data, PSP34Error};
+use ink::primitives::AccountId;
+
+// The API of `PSP34Data` available regardless of the features.
+fn check_data_api() {
+    let alice = AccountId::from([1; 32]);
+    let bob = AccountId::from([2; 32]);
+    let mut data = PSP34Data::new();
+
+    let events: Result<EventList, PSP34Error> = data.mint(alice, Id::U8(1), 0, 0);
+    assert_eq!(events.map(|events| events.len()), Ok(1));
+    let events: Result<EventList, PSP34Error> = data.transfer(alice, bob, Id::U8(1), vec![]);
+    assert_eq!(events.map(|events| events.len()), Ok(1));
+
+    let total_supply: u128 = data.total_supply();
+    let balance: u32 = data.balance_of(bob);
+    let owner: Option<AccountId> = data.owner_of(&Id::U8(1));
+    let allowed: bool = data.allowance(bob, alice, None);
+    assert_eq!(
+        (total_supply, balance, owner, allowed),
+        (1, 1, Some(bob), false)
+    );
+
+    let events: Result<EventList, PSP34Error> = data.burn(bob, bob, Id::U8(1));
+    assert_eq!(events.map(|events| events.len()), Ok(1));
+    assert_eq!(data.total_supply(), 0);
+}
+
+#[cfg(feature = "enumerable")]
+fn check_enumerable_api() {
+    let alice = AccountId::from([1; 32]);
+    let mut data = PSP34Data::new();
+    assert!(data.mint(alice, Id::U8(1), 0, 0).is_ok());
+
+    let token: Result<Id, PSP34Error> = data.token_by_index(0);
+    assert_eq!(token, Ok(Id::U8(1)));
+    let token: Result<Id, PSP34Error> = data.owners_token_by_index(alice, 0);
+    assert_eq!(token, Ok(Id::U8(1)));
+    let holder: Option<AccountId> = data.owner_by_index(0);
+    assert_eq!(holder, Some(alice));
+}
+
+// The messages of the example contract, coerced to function pointers so that any
+// change of their signatures fails to compile.
+#[cfg(feature = "contract")]
+fn check_contract_api() {
+    use crate::token::Token;
+    use crate::{PSP34Burnable, PSP34Metadata, PSP34Mintable, PSP34};
+
+    let _: fn(&Token) -> Id = <Token as PSP34>::collection_id;
+    let _: fn(&Token) -> u128 = <Token as PSP34>::total_supply;
+    let _: fn(&Token, AccountId) -> u32 = <Token as PSP34>::balance_of;
+    let _: fn(&Token, AccountId, AccountId, Option<Id>) -> bool = <Token as PSP34>::allowance;
+    let _: fn(&mut Token, AccountId, Id, Vec<u8>) -> Result<(), PSP34Error> =
+        <Token as PSP34>::transfer;
+    let _: fn(&mut Token, AccountId, Option<Id>, bool) -> Result<(), PSP34Error> =
+        <Token as PSP34>::approve;
+    let _: fn(&Token, Id) -> Option<AccountId> = <Token as PSP34>::owner_of;
+    let _: fn(&Token, Id, Vec<u8>) -> Option<Vec<u8>> = <Token as PSP34Metadata>::get_attribute;
+    let _: fn(&mut Token, Id) -> Result<(), PSP34Error> = <Token as PSP34Mintable>::mint;
+    let _: fn(&mut Token, AccountId, Id) -> Result<(), PSP34Error> = <Token as PSP34Burnable>::burn;
+
+    #[cfg(feature = "enumerable")]
+    {
+        let _: fn(&Token, u128) -> Option<AccountId> = Token::owner_by_index;
+        let _: fn(&Token, u32, u32) -> Vec<AccountId> = Token::holders;
+    }
+}
+
+#[cfg(all(not(feature = "enumerable"), not(feature = "contract")))]
+mod default {
+    #[ink::test]
+    fn api_works() {
+        super::check_data_api();
+    }
+}
+
+#[cfg(all(feature = "enumerable", not(feature = "contract")))]
+mod enumerable {
+    #[ink::test]
+    fn api_works() {
+        super::check_data_api();
+        super::check_enumerable_api();
+    }
+}
+
+#[cfg(all(not(feature = "enumerable"), feature = "contract"))]
+mod contract {
+    #[ink::test]
+    fn api_works() {
+        super::check_data_api();
+        super::check_contract_api();
+    }
+}
+
+#[cfg(all(feature = "enumerable", feature = "contract"))]
+mod enumerable_contract {
+    #[ink::test]
+    fn api_works() {
+        super::check_data_api();
+        super::check_enumerable_api();
+        super::check_contract_api();
+    }
+}
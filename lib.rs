@@ -1,14 +1,29 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
 mod balances;
+pub mod bundles;
+pub mod collateral;
+pub mod crafting;
 mod data;
 mod errors;
+#[cfg(test)]
+mod feature_tests;
+pub mod marketplace;
 pub mod metadata;
+#[cfg(test)]
+mod scale_vectors;
+pub mod selectors;
+pub mod timelock;
+pub mod topics;
 mod traits;
 mod unit_tests;
 
-pub use data::{Id, PSP34Data, PSP34Event};
-pub use errors::PSP34Error;
+pub use data::{
+    is_zero, Config, EventList, Id, Limits, MintBurnEvents, OutboundTeleport, PSP34Data,
+    PSP34DataBuilder, PSP34Event, PSP34EventKind, PendingTransfer, SelfTransferMode, TeleportMode,
+    TokenHold, CURRENT_VERSION, MAX_MEMO_LEN, MAX_PAGE_SIZE,
+};
+pub use errors::{CallError, PSP34Error};
 pub use traits::{PSP34Burnable, PSP34Metadata, PSP34Mintable, PSP34};
 
 #[cfg(feature = "enumerable")]
@@ -31,33 +46,1367 @@ pub use traits::PSP34Enumerable;
 #[ink::contract]
 mod token {
     use crate::{
-        metadata, Id, PSP34Burnable, PSP34Data, PSP34Error, PSP34Event, PSP34Metadata,
-        PSP34Mintable, PSP34,
+        bundles, collateral, crafting, marketplace, metadata, timelock, CallError, Config,
+        EventList, Id, Limits, OutboundTeleport, PSP34Burnable, PSP34Data, PSP34Error, PSP34Event,
+        PSP34EventKind, PSP34Metadata, PSP34Mintable, PendingTransfer, CURRENT_VERSION, PSP34,
     };
-    use ink::prelude::vec::Vec;
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::env::hash::Blake2x256;
+    use ink::prelude::{string::String, vec::Vec};
+    use ink::storage::Lazy;
+    use ink::MessageResult;
 
     #[cfg(feature = "enumerable")]
     use crate::PSP34Enumerable;
 
+    /// Maximal number of recipients of a single `mint_airdrop` call, keeping it within
+    /// the block weight limit.
+    pub const MAX_AIRDROP_RECIPIENTS: usize = 100;
+
+    /// Maximal number of tokens minted by a single `mint_sequential` call, keeping it
+    /// within the block weight limit.
+    pub const MAX_SEQUENTIAL_MINT: u32 = 100;
+
     #[ink(storage)]
     pub struct Token {
         data: PSP34Data,          // (1)
         metadata: metadata::Data, // (8)
+        marketplace: marketplace::Data,
+        collateral: collateral::Data,
+        bundles: bundles::Data,
+        crafting: crafting::Data,
+        timelock: timelock::Data,
+        // Fields added after the first release are kept in their own cells,
+        // so the storage of an older version still decodes after an upgrade.
+        clear_user_attributes_on_transfer: Lazy<bool>,
+        refund_amount: Lazy<Balance>,
+        // The account which deployed the contract, the only one allowed to call
+        // the admin messages.
+        owner: Lazy<AccountId>,
     }
 
-    impl Token {
-        #[ink(constructor)]
-        pub fn new() -> Self {
-            Self {
-                data: PSP34Data::new(),              // (2)
-                metadata: metadata::Data::default(), // (8)
+    impl Token {
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            let mut token = Self {
+                data: PSP34Data::new(),              // (2)
+                metadata: metadata::Data::default(), // (8)
+                marketplace: marketplace::Data::default(),
+                collateral: collateral::Data::default(),
+                bundles: bundles::Data::default(),
+                crafting: crafting::Data::default(),
+                timelock: timelock::Data::default(),
+                clear_user_attributes_on_transfer: Default::default(),
+                refund_amount: Default::default(),
+                owner: Default::default(),
+            };
+            token.owner.set(&Self::env().caller());
+            token
+        }
+
+        /// Creates the token with the collection name, symbol and base URI set
+        /// as attributes of the collection id.
+        #[ink(constructor)]
+        pub fn new_with_metadata(name: Vec<u8>, symbol: Vec<u8>, base_uri: Vec<u8>) -> Self {
+            let mut token = Self::new();
+            let collection_id = token.collection_id();
+            let mut events = EventList::new();
+            for (key, value) in [
+                (metadata::NAME_KEY, name),
+                (metadata::SYMBOL_KEY, symbol),
+                (metadata::BASE_URI_KEY, base_uri),
+            ] {
+                events.extend(
+                    token
+                        .metadata
+                        .set_attribute(collection_id.clone(), key.to_vec(), value)
+                        .expect("Setting an attribute cannot fail"),
+                );
+            }
+            token.emit_events(events);
+            token
+        }
+
+        /// Returns the account allowed to call the admin messages, the deployer of the contract.
+        #[ink(message)]
+        pub fn owner(&self) -> Option<AccountId> {
+            self.owner.get()
+        }
+
+        /// Checks whether `caller` can transfer token `id` to `to` and returns
+        /// the kinds of events such transfer would emit, without committing anything.
+        #[ink(message)]
+        pub fn simulate_transfer(
+            &self,
+            caller: AccountId,
+            to: AccountId,
+            id: Id,
+        ) -> Result<Vec<PSP34EventKind>, PSP34Error> {
+            self.data.simulate_transfer(caller, to, &id)
+        }
+
+        /// Transfers tokens of possibly different owners, each `(from, to, id)` entry
+        /// requiring the caller to be `from` or to be approved by `from`.
+        #[ink(message)]
+        pub fn transfer_from_many(
+            &mut self,
+            transfers: Vec<(AccountId, AccountId, Id)>,
+            data: Vec<u8>,
+        ) -> Result<(), PSP34Error> {
+            let events = self
+                .data
+                .transfer_from_many(self.env().caller(), transfers, data)?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Mints `count` tokens with consecutive `Id::U128` ids to the caller.
+        /// Returns the first assigned id and the id following the last one.
+        #[ink(message)]
+        pub fn mint_sequential(&mut self, count: u32) -> Result<(u128, u128), PSP34Error> {
+            self.ensure_owner()?;
+            if count > MAX_SEQUENTIAL_MINT {
+                return Err(PSP34Error::Custom(String::from(
+                    "Too many tokens minted in a single call.",
+                )));
+            }
+            let (ids, events) = self.data.mint_sequential(
+                self.env().caller(),
+                count,
+                self.env().block_number(),
+                self.env().block_timestamp(),
+            )?;
+            self.emit_events(events);
+            Ok((ids.start, ids.end))
+        }
+
+        /// Mints a token with a pseudo-random unused id, up to `max_supply`, to the caller.
+        /// The id is derived from the block data, which block producers can influence,
+        /// so it should not decide anything of value on its own. Returns the assigned id.
+        #[ink(message)]
+        pub fn mint_random(&mut self) -> Result<Id, PSP34Error> {
+            self.ensure_owner()?;
+            let caller = self.env().caller();
+            let block_number = self.env().block_number();
+            let timestamp = self.env().block_timestamp();
+            let seed = self
+                .env()
+                .hash_encoded::<Blake2x256, _>(&(caller, block_number, timestamp));
+            let (id, events) = self
+                .data
+                .mint_random(caller, seed, block_number, timestamp)?;
+            self.emit_events(events);
+            Ok(id)
+        }
+
+        /// Mints one token to each of the `recipients`, assigning consecutive `Id::U128` ids
+        /// in the order of the list. Returns the assigned ids.
+        #[ink(message)]
+        pub fn mint_airdrop(&mut self, recipients: Vec<AccountId>) -> Result<Vec<Id>, PSP34Error> {
+            self.ensure_owner()?;
+            if recipients.len() > MAX_AIRDROP_RECIPIENTS {
+                return Err(PSP34Error::Custom(String::from(
+                    "Too many airdrop recipients in a single call.",
+                )));
+            }
+            let (ids, events) = self.data.mint_airdrop(
+                recipients,
+                self.env().block_number(),
+                self.env().block_timestamp(),
+            )?;
+            self.emit_events(events);
+            Ok(ids)
+        }
+
+        /// Registers the caller as an escrow accepting `transfer_with_approval`,
+        /// or unregisters it if `enabled` is false.
+        #[ink(message)]
+        pub fn set_escrow(&mut self, enabled: bool) {
+            self.data.set_escrow(self.env().caller(), enabled)
+        }
+
+        /// Raises the limit of the number of tokens held by one account.
+        #[ink(message)]
+        pub fn raise_max_balance(&mut self, max_balance: u32) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.data.raise_max_balance(max_balance)
+        }
+
+        /// Exempts `account` from the balance limit, or removes the exemption.
+        #[ink(message)]
+        pub fn set_balance_limit_exempt(
+            &mut self,
+            account: AccountId,
+            exempt: bool,
+        ) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.data.set_balance_limit_exempt(account, exempt);
+            Ok(())
+        }
+
+        /// Returns whether `account` is exempt from the balance limit.
+        #[ink(message)]
+        pub fn is_balance_limit_exempt(&self, account: AccountId) -> bool {
+            self.data.is_balance_limit_exempt(account)
+        }
+
+        /// Returns whether `account` is a registered escrow.
+        #[ink(message)]
+        pub fn is_escrow(&self, account: AccountId) -> bool {
+            self.data.is_escrow(account)
+        }
+
+        /// Transfers token `id` to `to`. If `approve_back` is set, `to` must be
+        /// a registered escrow, which approves the previous owner for the token.
+        #[ink(message)]
+        pub fn transfer_with_approval(
+            &mut self,
+            to: AccountId,
+            id: Id,
+            approve_back: bool,
+            data: Vec<u8>,
+        ) -> Result<(), PSP34Error> {
+            let events = self.data.transfer_with_approval(
+                self.env().caller(),
+                to,
+                id,
+                approve_back,
+                data,
+            )?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Approves `operator` for token `id`, or all tokens if `id` is `None`,
+        /// only for the next `uses` transfers.
+        #[ink(message)]
+        pub fn approve_limited(
+            &mut self,
+            operator: AccountId,
+            id: Option<Id>,
+            uses: u32,
+        ) -> Result<(), PSP34Error> {
+            let events = self
+                .data
+                .approve_limited(self.env().caller(), operator, id, uses)?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Returns whether `owner` approved `operator` for exactly `id`,
+        /// not counting an approval for all tokens when `id` is `Some`.
+        #[ink(message)]
+        pub fn has_exact_approval(
+            &self,
+            owner: AccountId,
+            operator: AccountId,
+            id: Option<Id>,
+        ) -> bool {
+            self.data.has_exact_approval(owner, operator, id.as_ref())
+        }
+
+        /// Returns how many more transfers a limited approval allows.
+        #[ink(message)]
+        pub fn approval_uses(
+            &self,
+            owner: AccountId,
+            operator: AccountId,
+            id: Option<Id>,
+        ) -> Option<u32> {
+            self.data.approval_uses(owner, operator, id.as_ref())
+        }
+
+        /// Removes approvals, as `(owner, operator, id)`, left behind by burned tokens.
+        /// Anyone can call it, as it only removes approvals of tokens which do not exist.
+        #[ink(message)]
+        pub fn prune_approvals(&mut self, approvals: Vec<(AccountId, AccountId, Id)>) {
+            let events = self.data.prune_approvals(approvals);
+            self.emit_events(events);
+        }
+
+        /// Sends the caller's token `id` to `dest_account` on the chain `dest_chain`.
+        #[ink(message)]
+        pub fn teleport_out(
+            &mut self,
+            id: Id,
+            dest_chain: u32,
+            dest_account: Vec<u8>,
+        ) -> Result<(), PSP34Error> {
+            self.bundles.ensure_not_bundle(&self.data, &id)?;
+            let events = self.data.teleport_out(
+                self.env().caller(),
+                id.clone(),
+                dest_chain,
+                dest_account,
+            )?;
+            if !self.data.is_locked(&id) {
+                self.metadata.clear_indexed_attributes(&id);
+            }
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Delivers token `id` teleported from another chain to `owner`.
+        /// Can be called only by the bridge authority.
+        #[ink(message)]
+        pub fn teleport_in(
+            &mut self,
+            id: Id,
+            owner: AccountId,
+            proof: Vec<u8>,
+        ) -> Result<(), PSP34Error> {
+            let events = self.data.teleport_in(
+                self.env().caller(),
+                id,
+                owner,
+                proof,
+                self.env().block_number(),
+                self.env().block_timestamp(),
+            )?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Sets the account allowed to teleport tokens in.
+        #[ink(message)]
+        pub fn set_bridge_authority(
+            &mut self,
+            authority: Option<AccountId>,
+        ) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.data.set_bridge_authority(authority);
+            Ok(())
+        }
+
+        /// Returns the account allowed to teleport tokens in, if one is set.
+        #[ink(message)]
+        pub fn bridge_authority(&self) -> Option<AccountId> {
+            self.data.bridge_authority()
+        }
+
+        /// Returns the outbound teleport recorded with `nonce`, if there is one.
+        #[ink(message)]
+        pub fn outbound_teleport(&self, nonce: u64) -> Option<OutboundTeleport> {
+            self.data.outbound_teleport(nonce)
+        }
+
+        /// Transfers token `id`, which was sent to the contract's own account, to `to`.
+        #[ink(message)]
+        pub fn rescue_token(&mut self, id: Id, to: AccountId) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            let events = self.data.rescue_token(self.env().account_id(), id, to)?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Lists the caller's token `id` for sale at `price`, or changes its price.
+        /// The token cannot be transferred or burned until it is delisted or sold.
+        #[ink(message)]
+        pub fn list(&mut self, id: Id, price: Balance) -> Result<(), PSP34Error> {
+            let caller = self.env().caller();
+            let events = self.marketplace.list(&mut self.data, caller, id, price)?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Removes the caller's listing of token `id`.
+        #[ink(message)]
+        pub fn delist(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let caller = self.env().caller();
+            let events = self.marketplace.delist(&mut self.data, caller, id)?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Buys listed token `id`, paying exactly its price with the transferred value.
+        /// The seller receives the price minus the royalty.
+        #[ink(message, payable)]
+        pub fn buy(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let (caller, paid) = (self.env().caller(), self.env().transferred_value());
+            let (payouts, events) = self.marketplace.buy(&mut self.data, caller, id, paid)?;
+            self.pay_out(payouts.payments())?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Returns the listing of token `id`, if it is listed by its current owner.
+        #[ink(message)]
+        pub fn listing_of(&self, id: Id) -> Option<marketplace::Listing> {
+            self.marketplace.listing_of(&self.data, &id)
+        }
+
+        /// Returns the valid listings among the `limit` listed tokens starting at index
+        /// `offset`, up to `listed_count`.
+        #[ink(message)]
+        pub fn active_listings(&self, offset: u32, limit: u32) -> Vec<(Id, marketplace::Listing)> {
+            self.marketplace.active_listings(&self.data, offset, limit)
+        }
+
+        /// Returns the number of listed tokens, including stale listings.
+        #[ink(message)]
+        pub fn listed_count(&self) -> u32 {
+            self.marketplace.listed_count()
+        }
+
+        /// Removes the listing of token `id` once it is no longer valid, e.g. because the
+        /// token changed owner without a sale. Anyone can call it.
+        #[ink(message)]
+        pub fn remove_stale_listing(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let events = self.marketplace.remove_stale_listing(&self.data, id)?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Offers the transferred value for token `id`, which stays escrowed in the contract
+        /// until the offer is cancelled or accepted. A repeated offer raises the previous one.
+        #[ink(message, payable)]
+        pub fn make_offer(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let (caller, value) = (self.env().caller(), self.env().transferred_value());
+            let events = self.marketplace.make_offer(&self.data, caller, id, value)?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Withdraws the caller's offer for token `id`, refunding the escrowed value.
+        #[ink(message)]
+        pub fn cancel_offer(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let caller = self.env().caller();
+            let (amount, events) = self.marketplace.cancel_offer(&self.data, caller, id)?;
+            self.env()
+                .transfer(caller, amount)
+                .map_err(|_| PSP34Error::Custom(String::from("Refund of the offer failed.")))?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Accepts the offer of `bidder` for the caller's token `id`, transferring the token
+        /// to `bidder`. The caller receives the escrowed value minus the royalty.
+        #[ink(message)]
+        pub fn accept_offer(&mut self, id: Id, bidder: AccountId) -> Result<(), PSP34Error> {
+            let caller = self.env().caller();
+            let (payouts, events) =
+                self.marketplace
+                    .accept_offer(&mut self.data, caller, id, bidder)?;
+            self.pay_out(payouts.payments())?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Returns the offers for token `id` with their escrowed values, oldest first.
+        #[ink(message)]
+        pub fn offers_for(&self, id: Id) -> Vec<(AccountId, Balance)> {
+            self.marketplace.offers_for(&self.data, &id)
+        }
+
+        /// Returns the total value escrowed by the offers and the highest bids of auctions.
+        #[ink(message)]
+        pub fn total_escrowed(&self) -> Balance {
+            self.marketplace.total_escrowed()
+        }
+
+        /// Auctions the caller's token `id` until `end_time`, a timestamp in milliseconds.
+        /// The first bid must be at least `start_price`, and each next bid higher than
+        /// the previous one by at least `min_increment`.
+        #[ink(message)]
+        pub fn create_auction(
+            &mut self,
+            id: Id,
+            start_price: Balance,
+            min_increment: Balance,
+            end_time: Timestamp,
+        ) -> Result<(), PSP34Error> {
+            let (caller, now) = (self.env().caller(), self.env().block_timestamp());
+            let events = self.marketplace.create_auction(
+                &mut self.data,
+                caller,
+                id,
+                start_price,
+                min_increment,
+                end_time,
+                now,
+            )?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Bids the transferred value for auctioned token `id`, refunding the previous
+        /// highest bidder. A bid in the last minutes of the auction extends it.
+        #[ink(message, payable)]
+        pub fn bid(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let (caller, value) = (self.env().caller(), self.env().transferred_value());
+            let now = self.env().block_timestamp();
+            let (outbid, events) = self.marketplace.bid(&self.data, caller, id, value, now)?;
+            if let Some((bidder, amount)) = outbid {
+                self.env()
+                    .transfer(bidder, amount)
+                    .map_err(|_| PSP34Error::Custom(String::from("Refund of the bid failed.")))?;
+            }
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Settles the auction of token `id` after it has ended, transferring the token to
+        /// the highest bidder and the bid to the seller, minus the royalty.
+        #[ink(message)]
+        pub fn settle(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let now = self.env().block_timestamp();
+            let (payments, events) = self.marketplace.settle(&mut self.data, id, now)?;
+            self.pay_out(payments)?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Returns the auction of token `id`, if there is one which has not been settled.
+        #[ink(message)]
+        pub fn auction_of(&self, id: Id) -> Option<marketplace::Auction> {
+            self.marketplace.auction_of(&self.data, &id)
+        }
+
+        /// Locks the caller's token `id` as collateral of a loan from `lender`, recording
+        /// the `release_conditions` agreed with them. Until the lender releases or seizes
+        /// the token, it cannot be transferred or burned.
+        #[ink(message)]
+        pub fn lock_as_collateral(
+            &mut self,
+            id: Id,
+            lender: AccountId,
+            release_conditions: Vec<u8>,
+        ) -> Result<(), PSP34Error> {
+            let caller = self.env().caller();
+            let events = self.collateral.lock_as_collateral(
+                &mut self.data,
+                caller,
+                id,
+                lender,
+                release_conditions,
+            )?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Releases token `id` locked as collateral of a loan from the caller.
+        #[ink(message)]
+        pub fn release(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let caller = self.env().caller();
+            let events = self.collateral.release(&mut self.data, caller, id)?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Transfers token `id` locked as collateral of a loan from the caller to the caller.
+        #[ink(message)]
+        pub fn seize(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let caller = self.env().caller();
+            let events = self.collateral.seize(&mut self.data, caller, id)?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Returns the collateral lock of token `id`, if there is one.
+        #[ink(message)]
+        pub fn collateral_status(&self, id: Id) -> Option<collateral::Collateral> {
+            self.collateral.collateral_status(&self.data, &id)
+        }
+
+        /// Bundles the caller's tokens `ids` into a new bundle token, taking the tokens
+        /// into the custody of the contract. Returns the id of the bundle.
+        #[ink(message)]
+        pub fn create_bundle(&mut self, ids: Vec<Id>) -> Result<Id, PSP34Error> {
+            let (caller, custodian) = (self.env().caller(), self.env().account_id());
+            let (block_number, timestamp) =
+                (self.env().block_number(), self.env().block_timestamp());
+            let (bundle_id, events) = self.bundles.create_bundle(
+                &mut self.data,
+                caller,
+                custodian,
+                ids,
+                block_number,
+                timestamp,
+            )?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(bundle_id)
+        }
+
+        /// Burns the caller's bundle `id` and returns its components to the caller.
+        #[ink(message)]
+        pub fn unbundle(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let (caller, custodian) = (self.env().caller(), self.env().account_id());
+            let events = self
+                .bundles
+                .unbundle(&mut self.data, caller, custodian, id.clone())?;
+            self.metadata.clear_indexed_attributes(&id);
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Returns the components of bundle `id`, or `None` if it is not a bundle.
+        #[ink(message)]
+        pub fn bundle_contents(&self, id: Id) -> Option<Vec<Id>> {
+            self.bundles.contents_of(&self.data, &id)
+        }
+
+        /// Revokes the approval of `operator` for token `id`. Fails if `operator` is
+        /// approved for all tokens of the owner, which has to be revoked separately.
+        #[ink(message)]
+        pub fn reset_approval(&mut self, operator: AccountId, id: Id) -> Result<(), PSP34Error> {
+            let events = self
+                .data
+                .reset_approval(self.env().caller(), operator, id)?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Registers a crafting `recipe` and returns its id.
+        #[ink(message)]
+        pub fn register_recipe(&mut self, recipe: crafting::Recipe) -> Result<u32, PSP34Error> {
+            self.ensure_owner()?;
+            self.crafting.register_recipe(recipe)
+        }
+
+        /// Returns the crafting recipe with `recipe_id`, if there is one.
+        #[ink(message)]
+        pub fn recipe(&self, recipe_id: u32) -> Option<crafting::Recipe> {
+            self.crafting.recipe(recipe_id)
+        }
+
+        /// Burns the caller's tokens `inputs`, matching the inputs of recipe `recipe_id`
+        /// in order, and mints the crafted token to the caller. Returns its id.
+        #[ink(message)]
+        pub fn craft(&mut self, recipe_id: u32, inputs: Vec<Id>) -> Result<Id, PSP34Error> {
+            for id in &inputs {
+                self.bundles.ensure_not_bundle(&self.data, id)?;
+            }
+            let caller = self.env().caller();
+            let (block_number, timestamp) =
+                (self.env().block_number(), self.env().block_timestamp());
+            let (output, events) = self.crafting.craft(
+                &mut self.data,
+                &mut self.metadata,
+                caller,
+                recipe_id,
+                inputs.clone(),
+                block_number,
+                timestamp,
+            )?;
+            for id in &inputs {
+                self.metadata.clear_indexed_attributes(id);
+            }
+            self.emit_events(events);
+            Ok(output)
+        }
+
+        /// Sets the receiver of royalties of marketplace sales and their share in basis
+        /// points, or removes the royalty if `royalty` is `None`.
+        #[ink(message)]
+        pub fn set_royalty(&mut self, royalty: Option<(AccountId, u16)>) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.timelock.ensure_unlocked()?;
+            self.marketplace.set_royalty(royalty)
+        }
+
+        /// Sets a royalty of `bps` basis points of marketplace sales, split between the
+        /// `recipients` by their shares in basis points of the royalty, adding up to 10 000.
+        /// The contract receives the royalty and keeps it until `distribute` is called.
+        #[ink(message)]
+        pub fn set_royalty_split(
+            &mut self,
+            bps: u16,
+            recipients: Vec<(AccountId, u16)>,
+        ) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.timelock.ensure_unlocked()?;
+            let contract = self.env().account_id();
+            self.marketplace
+                .set_royalty_split(contract, bps, recipients)
+        }
+
+        /// Returns the recipients the royalty is split between with their shares,
+        /// empty if it is not split.
+        #[ink(message)]
+        pub fn royalty_recipients(&self) -> Vec<(AccountId, u16)> {
+            self.marketplace.royalty_recipients()
+        }
+
+        /// Returns the receiver and the amount of the royalty of a sale at `price`.
+        /// A split royalty is received by the contract itself.
+        #[ink(message)]
+        pub fn royalty_info(&self, price: Balance) -> Option<(AccountId, Balance)> {
+            self.marketplace.royalty_info(price)
+        }
+
+        /// Returns the royalties received by the contract and not distributed yet.
+        #[ink(message)]
+        pub fn undistributed_royalties(&self) -> Balance {
+            self.marketplace.undistributed()
+        }
+
+        /// Splits the transferred value, paid as the royalty of a sale of token `id`
+        /// outside of the contract, between the royalty recipients. The remainder of
+        /// the rounding goes to the first recipient.
+        #[ink(message, payable)]
+        pub fn pay_royalty(&mut self, id: Id) -> Result<(), PSP34Error> {
+            self.data.owner_of(&id).ok_or(PSP34Error::TokenNotExists)?;
+            let payments = self
+                .marketplace
+                .pay_royalty(self.env().transferred_value())?;
+            self.pay_royalties(payments)
+        }
+
+        /// Splits the undistributed royalties between the royalty recipients.
+        /// Anyone can call it.
+        #[ink(message)]
+        pub fn distribute(&mut self) -> Result<(), PSP34Error> {
+            let payments = self.marketplace.distribute()?;
+            self.pay_royalties(payments)
+        }
+
+        /// Returns the delay in milliseconds of the timelocked admin actions,
+        /// `None` if they are executed immediately.
+        #[ink(message)]
+        pub fn timelock_delay(&self) -> Option<u64> {
+            self.timelock.delay()
+        }
+
+        /// Enables the timelock with a delay in milliseconds, after which the changes of
+        /// royalties and the contract URI are executed. While it is enabled, their messages
+        /// fail and the delay can only be changed with a scheduled action.
+        #[ink(message)]
+        pub fn set_timelock_delay(&mut self, delay: Option<u64>) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.timelock.set_delay(delay)
+        }
+
+        /// Returns the actions scheduled in the timelock with the time from which they
+        /// can be executed.
+        #[ink(message)]
+        pub fn scheduled_actions(&self) -> Vec<timelock::ScheduledAction> {
+            self.timelock.scheduled_actions()
+        }
+
+        /// Schedules `action` to be executed once the timelock delay has passed.
+        /// Returns the id of the scheduled action.
+        #[ink(message)]
+        pub fn schedule(&mut self, action: timelock::Action) -> Result<u32, PSP34Error> {
+            self.ensure_owner()?;
+            let now = self.env().block_timestamp();
+            self.timelock.schedule(action, now)
+        }
+
+        /// Executes the scheduled action `id` after its delay has passed.
+        #[ink(message)]
+        pub fn execute(&mut self, id: u32) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            let now = self.env().block_timestamp();
+            match self.timelock.take_executable(id, now)? {
+                timelock::Action::SetRoyalty(royalty) => self.marketplace.set_royalty(royalty),
+                timelock::Action::SetRoyaltySplit(bps, recipients) => {
+                    let contract = self.env().account_id();
+                    self.marketplace
+                        .set_royalty_split(contract, bps, recipients)
+                }
+                timelock::Action::SetContractUri(uri) => self.apply_contract_uri(uri),
+                timelock::Action::SetDelay(delay) => {
+                    self.timelock.apply_delay(delay);
+                    Ok(())
+                }
+            }
+        }
+
+        /// Cancels the scheduled action `id`.
+        #[ink(message)]
+        pub fn cancel(&mut self, id: u32) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.timelock.cancel(id)
+        }
+
+        /// Returns the value refunded by `burn_for_refund`.
+        #[ink(message)]
+        pub fn refund_amount(&self) -> Balance {
+            self.refund_amount.get_or_default()
+        }
+
+        /// Sets the value refunded by `burn_for_refund`.
+        #[ink(message)]
+        pub fn set_refund_amount(&mut self, amount: Balance) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.refund_amount.set(&amount);
+            Ok(())
+        }
+
+        /// Burns the caller's token `id` and refunds them `refund_amount` out of the
+        /// contract's balance. Fails if the balance, not counting the value escrowed
+        /// by marketplace offers and the undistributed royalties, cannot cover the refund.
+        #[ink(message)]
+        pub fn burn_for_refund(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let caller = self.env().caller();
+            let refund_amount = self.refund_amount();
+            let available = self
+                .env()
+                .balance()
+                .saturating_sub(self.marketplace.total_escrowed())
+                .saturating_sub(self.marketplace.undistributed());
+            if available < refund_amount {
+                return Err(PSP34Error::Custom(String::from(
+                    "Insufficient balance for the refund.",
+                )));
+            }
+            self.bundles.ensure_not_bundle(&self.data, &id)?;
+            let events = self.data.burn(caller, caller, id.clone())?;
+            self.metadata.clear_indexed_attributes(&id);
+            if refund_amount > 0 {
+                self.env()
+                    .transfer(caller, refund_amount)
+                    .map_err(|_| PSP34Error::Custom(String::from("Refund failed.")))?;
+            }
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Mints token `id` to `account` and approves `operator` to transfer it.
+        #[ink(message)]
+        pub fn mint_and_approve(
+            &mut self,
+            account: AccountId,
+            id: Id,
+            operator: AccountId,
+        ) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            let events = self.data.mint_and_approve(
+                account,
+                id,
+                operator,
+                self.env().block_number(),
+                self.env().block_timestamp(),
+            )?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Transfers token `id` of another PSP34 collection `token_contract`, which was
+        /// sent to this contract, to `to`. Tokens of this collection have to be moved
+        /// with `rescue_token` instead.
+        #[ink(message)]
+        pub fn sweep_psp34(
+            &mut self,
+            token_contract: AccountId,
+            id: Id,
+            to: AccountId,
+        ) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.ensure_foreign(token_contract)?;
+            let result = build_call::<Environment>()
+                .call(token_contract)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(crate::selectors::TRANSFER.to_be_bytes()))
+                        .push_arg(to)
+                        .push_arg(id)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP34Error>>()
+                .try_invoke();
+            Self::call_result(result)
+        }
+
+        /// Transfers `amount` of the PSP22 token `token_contract`, which was sent to this
+        /// contract, to `to`.
+        #[ink(message)]
+        pub fn sweep_psp22(
+            &mut self,
+            token_contract: AccountId,
+            amount: u128,
+            to: AccountId,
+        ) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.ensure_foreign(token_contract)?;
+            let result = build_call::<Environment>()
+                .call(token_contract)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP22::transfer")))
+                        .push_arg(to)
+                        .push_arg(amount)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP22Error>>()
+                .try_invoke();
+            Self::call_result(result)
+        }
+
+        // Flattens the result of a call to another token contract, keeping the error
+        // returned by the token.
+        fn call_result<T, E: Into<PSP34Error>>(
+            result: Result<MessageResult<Result<T, E>>, ink::env::Error>,
+        ) -> Result<T, PSP34Error> {
+            let result = result.map(|result| result.map(|result| result.map_err(Into::into)));
+            CallError::flatten(result).map_err(PSP34Error::from)
+        }
+
+        fn ensure_foreign(&self, token_contract: AccountId) -> Result<(), PSP34Error> {
+            if token_contract == self.env().account_id() {
+                return Err(PSP34Error::Custom(String::from(
+                    "Tokens of this collection cannot be swept, use rescue_token.",
+                )));
+            }
+            Ok(())
+        }
+
+        /// Transfers token `id` to `to` like `transfer` and returns the previous owner,
+        /// e.g. for a settlement contract to route the payment.
+        #[ink(message)]
+        pub fn transfer_reporting(
+            &mut self,
+            to: AccountId,
+            id: Id,
+            data: Vec<u8>,
+        ) -> Result<AccountId, PSP34Error> {
+            let previous_owner = self.data.owner_of(&id).ok_or(PSP34Error::TokenNotExists)?;
+            PSP34::transfer(self, to, id, data)?;
+            Ok(previous_owner)
+        }
+
+        /// Transfers token `id` to `to` like `transfer` and stores `memo` on-chain.
+        #[ink(message)]
+        pub fn transfer_with_memo(
+            &mut self,
+            to: AccountId,
+            id: Id,
+            memo: Vec<u8>,
+            data: Vec<u8>,
+        ) -> Result<(), PSP34Error> {
+            let events = self
+                .data
+                .transfer_with_memo(self.env().caller(), to, id, memo, data)?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Returns the memo stored with the `sequence`-th transfer of token `id`.
+        #[ink(message)]
+        pub fn memo(&self, id: Id, sequence: u32) -> Option<Vec<u8>> {
+            self.data.memo(&id, sequence)
+        }
+
+        /// Transfers token `id` to `to` like `transfer`, failing with `Expired`
+        /// if the block timestamp is past `deadline`.
+        #[ink(message)]
+        pub fn transfer_before(
+            &mut self,
+            to: AccountId,
+            id: Id,
+            deadline: Timestamp,
+            data: Vec<u8>,
+        ) -> Result<(), PSP34Error> {
+            let events = self.data.transfer_before(
+                self.env().caller(),
+                to,
+                id,
+                deadline,
+                self.env().block_timestamp(),
+                data,
+            )?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Offers token `id` to `to`, locking it until `to` claims it or the offer is
+        /// cancelled. The offer cannot be claimed after the block number `deadline`, if set.
+        #[ink(message)]
+        pub fn offer_transfer(
+            &mut self,
+            to: AccountId,
+            id: Id,
+            deadline: Option<u32>,
+        ) -> Result<(), PSP34Error> {
+            let events = self.data.offer_transfer(
+                self.env().caller(),
+                to,
+                id,
+                deadline,
+                self.env().block_number(),
+            )?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Completes the transfer of token `id` offered to the caller.
+        #[ink(message)]
+        pub fn claim_transfer(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let events =
+                self.data
+                    .claim_transfer(self.env().caller(), id, self.env().block_number())?;
+            self.clear_transferred_user_attributes(&events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Cancels the pending transfer of token `id`.
+        #[ink(message)]
+        pub fn cancel_transfer(&mut self, id: Id) -> Result<(), PSP34Error> {
+            let events = self.data.cancel_transfer(self.env().caller(), id)?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Returns the transfer of token `id` waiting to be claimed, if there is one.
+        #[ink(message)]
+        pub fn pending_transfer(&self, id: Id) -> Option<PendingTransfer> {
+            self.data.pending_transfer(&id)
+        }
+
+        /// Burns token `id` regardless of its owner, e.g. to remove illegal content,
+        /// revoking the approvals the owner granted to `operators` for the token.
+        /// The offers for the token are cancelled and refunded. Fails if the token is
+        /// listed, auctioned, locked as collateral, bundled or is a bundle itself.
+        #[ink(message)]
+        pub fn admin_burn(&mut self, id: Id, operators: Vec<AccountId>) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.bundles.ensure_not_bundle(&self.data, &id)?;
+            let mut events = self
+                .data
+                .admin_burn(self.env().caller(), id.clone(), operators)?;
+            self.metadata.clear_indexed_attributes(&id);
+            let (refunds, offer_events) = self.marketplace.cancel_offers(&self.data, id);
+            for (bidder, amount) in refunds {
+                self.env()
+                    .transfer(bidder, amount)
+                    .map_err(|_| PSP34Error::Custom(String::from("Refund of the offer failed.")))?;
+            }
+            events.extend(offer_events);
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Commits to the provenance hash of the collection's content.
+        /// Can be set only once, before the first token is minted.
+        #[ink(message)]
+        pub fn set_provenance_hash(&mut self, hash: [u8; 32]) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            let events = self.data.set_provenance_hash(hash)?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Sets the id of the collection returned by `collection_id`, instead of the one
+        /// derived from the contract's account. Can be set only once.
+        #[ink(message)]
+        pub fn set_collection_id(&mut self, id: Id) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.data.set_collection_id(id)
+        }
+
+        /// Returns the provenance hash of the collection's content, if it was set.
+        #[ink(message)]
+        pub fn provenance_hash(&self) -> Option<[u8; 32]> {
+            self.data.provenance_hash()
+        }
+
+        /// Upgrades the token storage after the contract code is upgraded.
+        /// Returns the resulting storage version.
+        ///
+        /// A contract deployed before the owner was stored has none. While its storage
+        /// is outdated, the caller becomes its owner, so call it in the same transaction
+        /// as the code upgrade, e.g. with `utility.batchAll`, for nobody else to claim it.
+        #[ink(message)]
+        pub fn migrate(&mut self) -> Result<u32, PSP34Error> {
+            if self.owner.get().is_none() && self.data.storage_version() < CURRENT_VERSION {
+                self.owner.set(&self.env().caller());
+            }
+            self.ensure_owner()?;
+            self.data.migrate()
+        }
+
+        /// Returns the version of the token storage layout.
+        #[ink(message)]
+        pub fn storage_version(&self) -> u32 {
+            self.data.storage_version()
+        }
+
+        /// Returns whether the tokens can be transferred and burned.
+        #[ink(message)]
+        pub fn config(&self) -> Config {
+            self.data.config()
+        }
+
+        /// Returns the limits of the total supply and of the balance of an account.
+        #[ink(message)]
+        pub fn limits(&self) -> Limits {
+            self.data.limits()
+        }
+
+        /// Returns whether the supply limit is configured and reached.
+        #[ink(message)]
+        pub fn is_sold_out(&self) -> bool {
+            self.data.is_sold_out()
+        }
+
+        /// Returns the number of tokens minted so far, including the burned ones.
+        #[ink(message)]
+        pub fn minted_count(&self) -> u128 {
+            self.data.minted_count()
+        }
+
+        /// Returns the number of tokens burned so far.
+        #[ink(message)]
+        pub fn total_burned(&self) -> u128 {
+            self.data.total_burned()
+        }
+
+        /// Returns the number of distinct accounts owning at least one token, or `None`
+        /// if it is unknown after an upgrade.
+        #[ink(message)]
+        pub fn owner_count(&self) -> Option<u32> {
+            self.data.owner_count()
+        }
+
+        /// Returns how many times token `id` has been transferred since it was minted.
+        #[ink(message)]
+        pub fn transfer_count(&self, id: Id) -> u32 {
+            self.data.transfer_count(&id)
+        }
+
+        /// Returns the number and the timestamp of the block in which token `id` was minted.
+        #[ink(message)]
+        pub fn minted_at(&self, id: Id) -> Option<(u32, u64)> {
+            self.data.minted_at(&id)
+        }
+
+        /// Sets the attribute `key` of token `id`. Keys in the user namespace
+        /// can only be set by the token owner with `set_user_attribute`.
+        #[ink(message)]
+        pub fn set_attribute(
+            &mut self,
+            id: Id,
+            key: Vec<u8>,
+            value: Vec<u8>,
+        ) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            if metadata::Data::is_user_key(&key) {
+                return Err(PSP34Error::Custom(String::from(
+                    "User attributes can only be set by the token owner.",
+                )));
+            }
+            let events = self.metadata.set_attribute(id, key, value)?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Sets the attribute `key` of token `id` owned by the caller.
+        /// The key must start with `metadata::USER_KEY_PREFIX`.
+        #[ink(message)]
+        pub fn set_user_attribute(
+            &mut self,
+            id: Id,
+            key: Vec<u8>,
+            value: Vec<u8>,
+        ) -> Result<(), PSP34Error> {
+            let owner = self.data.owner_of(&id).ok_or(PSP34Error::TokenNotExists)?;
+            if owner != self.env().caller() {
+                return Err(PSP34Error::NotApproved);
+            }
+            let events = self.metadata.set_user_attribute(id, key, value)?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Sets whether the user attributes of a token are removed when it is transferred.
+        #[ink(message)]
+        pub fn set_clear_user_attributes_on_transfer(
+            &mut self,
+            enabled: bool,
+        ) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.clear_user_attributes_on_transfer.set(&enabled);
+            Ok(())
+        }
+
+        /// Sets the URI of the collection-level metadata read by marketplaces.
+        #[ink(message)]
+        pub fn set_contract_uri(&mut self, uri: String) -> Result<(), PSP34Error> {
+            self.ensure_owner()?;
+            self.timelock.ensure_unlocked()?;
+            self.apply_contract_uri(uri)
+        }
+
+        // Sets the contract URI, after the caller and the timelock are checked.
+        fn apply_contract_uri(&mut self, uri: String) -> Result<(), PSP34Error> {
+            let events = self.metadata.set_attribute(
+                self.collection_id(),
+                metadata::CONTRACT_URI_KEY.to_vec(),
+                uri.into_bytes(),
+            )?;
+            self.emit_events(events);
+            Ok(())
+        }
+
+        /// Returns the URI of the collection-level metadata, if it is set.
+        #[ink(message)]
+        pub fn contract_uri(&self) -> Option<String> {
+            self.metadata
+                .get_attribute(self.collection_id(), metadata::CONTRACT_URI_KEY.to_vec())
+                .and_then(|uri| String::from_utf8(uri).ok())
+        }
+
+        /// Returns the name, symbol and base URI of the collection at once.
+        #[ink(message)]
+        pub fn collection_metadata(&self) -> metadata::CollectionMetadata {
+            self.metadata.collection_metadata(self.collection_id())
+        }
+
+        /// Returns the values of the attributes of `id` for the given `keys`, in the same
+        /// order. At most `metadata::MAX_ATTRIBUTE_KEYS` keys are read.
+        #[ink(message)]
+        pub fn get_attributes(&self, id: Id, keys: Vec<Vec<u8>>) -> Vec<Option<Vec<u8>>> {
+            self.metadata.get_attributes(id, keys)
+        }
+
+        /// Returns at most `limit` tokens with the indexed attribute `key` set to `value`,
+        /// skipping the first `offset` of them.
+        #[ink(message)]
+        pub fn tokens_by_attribute(
+            &self,
+            key: Vec<u8>,
+            value: Vec<u8>,
+            offset: u32,
+            limit: u32,
+        ) -> Vec<Id> {
+            self.metadata.tokens_by_attribute(key, value, offset, limit)
+        }
+
+        /// Returns the number of tokens with the indexed attribute `key` set to `value`.
+        #[ink(message)]
+        pub fn count_by_attribute(&self, key: Vec<u8>, value: Vec<u8>) -> u32 {
+            self.metadata.count_by_attribute(key, value)
+        }
+
+        /// Returns the number of tokens with the attribute `key` set to `value`,
+        /// whether it is indexed or not.
+        #[ink(message)]
+        pub fn count_with_attribute(&self, key: Vec<u8>, value: Vec<u8>) -> u128 {
+            self.metadata.count_with_attribute(key, value)
+        }
+
+        /// Burns up to `limit` tokens of `owner`, who has to be the caller or have approved
+        /// the caller for all tokens. Returns the burned ids.
+        #[cfg(feature = "enumerable")]
+        #[ink(message)]
+        pub fn burn_all_of(&mut self, owner: AccountId, limit: u32) -> Result<Vec<Id>, PSP34Error> {
+            let (ids, events) = self.data.burn_all_of(self.env().caller(), owner, limit)?;
+            for id in &ids {
+                // Reverts the whole call, so no bundle is burned.
+                self.bundles.ensure_not_bundle(&self.data, id)?;
+                self.metadata.clear_indexed_attributes(id);
+            }
+            self.emit_events(events);
+            Ok(ids)
+        }
+
+        /// Returns the index of token `id` among the tokens of `owner`.
+        #[cfg(feature = "enumerable")]
+        #[ink(message)]
+        pub fn index_of_owned(&self, owner: AccountId, id: Id) -> Option<u128> {
+            self.data.index_of_owned(owner, &id)
+        }
+
+        /// Returns the account at a given `index` of the list of all current holders.
+        #[cfg(feature = "enumerable")]
+        #[ink(message)]
+        pub fn owner_by_index(&self, index: u128) -> Option<AccountId> {
+            self.data.owner_by_index(index)
+        }
+
+        /// Returns at most `limit` current holders, skipping the first `offset` of them.
+        #[cfg(feature = "enumerable")]
+        #[ink(message)]
+        pub fn holders(&self, offset: u32, limit: u32) -> Vec<AccountId> {
+            self.data.holders(offset, limit)
+        }
+
+        /// Returns at most `count` tokens with their owners, starting at index `start`
+        /// of the list of all tokens. `count` is clamped to `MAX_PAGE_SIZE`.
+        #[cfg(feature = "enumerable")]
+        #[ink(message)]
+        pub fn all_tokens_with_owners_page(&self, start: u128, count: u32) -> Vec<(Id, AccountId)> {
+            self.data.all_tokens_with_owners_page(start, count)
+        }
+
+        /// Returns the number of attributes set for all tokens and the collection.
+        #[ink(message)]
+        pub fn total_attributes(&self) -> u128 {
+            self.metadata.total_attributes()
+        }
+
+        /// Returns the number of attributes set for `id`.
+        #[ink(message)]
+        pub fn attribute_count(&self, id: Id) -> u32 {
+            self.metadata.attribute_count(id)
+        }
+
+        // Checks that the caller is the owner of the contract, restricting the admin messages.
+        fn ensure_owner(&self) -> Result<(), PSP34Error> {
+            if self.owner.get() != Some(self.env().caller()) {
+                return Err(PSP34Error::NotApproved);
+            }
+            Ok(())
+        }
+
+        // Removes the user attributes of the tokens moved by `events`, if enabled.
+        fn clear_transferred_user_attributes(&mut self, events: &[PSP34Event]) {
+            if !self.clear_user_attributes_on_transfer.get_or_default() {
+                return;
+            }
+            for event in events {
+                if let PSP34Event::Transfer {
+                    from: Some(_),
+                    to: Some(_),
+                    id,
+                } = event
+                {
+                    self.metadata.clear_user_attributes(id);
+                }
+            }
+        }
+
+        // Transfers the proceeds of a marketplace sale to the seller and the royalty receiver.
+        fn pay_out(
+            &self,
+            payments: impl IntoIterator<Item = (AccountId, Balance)>,
+        ) -> Result<(), PSP34Error> {
+            for (account, amount) in payments {
+                self.env()
+                    .transfer(account, amount)
+                    .map_err(|_| PSP34Error::Custom(String::from("Payout of the sale failed.")))?;
             }
+            Ok(())
+        }
+
+        // Transfers the shares of a split royalty to its recipients.
+        fn pay_royalties(&self, payments: Vec<(AccountId, Balance)>) -> Result<(), PSP34Error> {
+            for (account, amount) in payments {
+                if amount == 0 {
+                    continue;
+                }
+                self.env().transfer(account, amount).map_err(|_| {
+                    PSP34Error::Custom(String::from("Payout of the royalty failed."))
+                })?;
+            }
+            Ok(())
         }
 
-        // A helper function translating a vector of PSP34Events into the proper
+        // A helper function translating a list of PSP34Events into the proper
         // ink event types (defined internally in this contract) and emitting them.
         // (5)
-        fn emit_events(&self, events: ink::prelude::vec::Vec<PSP34Event>) {
+        fn emit_events(&self, events: EventList) {
             for event in events {
                 match event {
                     PSP34Event::Approval {
@@ -77,40 +1426,463 @@ mod token {
                     PSP34Event::AttributeSet { id, key, data } => {
                         self.env().emit_event(AttributeSet { id, key, data })
                     }
+                    PSP34Event::TransferOffered {
+                        from,
+                        to,
+                        id,
+                        deadline,
+                    } => self.env().emit_event(TransferOffered {
+                        from,
+                        to,
+                        id,
+                        deadline,
+                    }),
+                    PSP34Event::TransferClaimed { from, to, id } => {
+                        self.env().emit_event(TransferClaimed { from, to, id })
+                    }
+                    PSP34Event::TransferCancelled { from, to, id } => {
+                        self.env().emit_event(TransferCancelled { from, to, id })
+                    }
+                    PSP34Event::ProvenanceSet { hash } => {
+                        self.env().emit_event(ProvenanceSet { hash })
+                    }
+                    PSP34Event::Minted { to, id } => self.env().emit_event(Minted { to, id }),
+                    PSP34Event::Burned { from, caller, id } => {
+                        self.env().emit_event(Burned { from, caller, id })
+                    }
+                    PSP34Event::TransferMemo { id, sequence, memo } => {
+                        self.env().emit_event(TransferMemo { id, sequence, memo })
+                    }
+                    PSP34Event::TokenRescued { id, to } => {
+                        self.env().emit_event(TokenRescued { id, to })
+                    }
+                    PSP34Event::TeleportedOut {
+                        id,
+                        from,
+                        dest_chain,
+                        dest_account,
+                        nonce,
+                    } => self.env().emit_event(TeleportedOut {
+                        id,
+                        from,
+                        dest_chain,
+                        dest_account,
+                        nonce,
+                    }),
+                    PSP34Event::TeleportedIn { id, to } => {
+                        self.env().emit_event(TeleportedIn { id, to })
+                    }
+                    PSP34Event::BalanceChanged {
+                        account,
+                        new_balance,
+                    } => self.env().emit_event(BalanceChanged {
+                        account,
+                        new_balance,
+                    }),
+                    PSP34Event::Listed { id, seller, price } => {
+                        self.env().emit_event(Listed { id, seller, price })
+                    }
+                    PSP34Event::Delisted { id, seller } => {
+                        self.env().emit_event(Delisted { id, seller })
+                    }
+                    PSP34Event::Sold {
+                        id,
+                        seller,
+                        buyer,
+                        price,
+                    } => self.env().emit_event(Sold {
+                        id,
+                        seller,
+                        buyer,
+                        price,
+                    }),
+                    PSP34Event::OfferMade { id, bidder, amount } => {
+                        self.env().emit_event(OfferMade { id, bidder, amount })
+                    }
+                    PSP34Event::OfferCancelled { id, bidder, amount } => {
+                        self.env().emit_event(OfferCancelled { id, bidder, amount })
+                    }
+                    PSP34Event::OfferAccepted {
+                        id,
+                        seller,
+                        bidder,
+                        amount,
+                    } => self.env().emit_event(OfferAccepted {
+                        id,
+                        seller,
+                        bidder,
+                        amount,
+                    }),
+                    PSP34Event::AuctionCreated {
+                        id,
+                        seller,
+                        start_price,
+                        min_increment,
+                        end_time,
+                    } => self.env().emit_event(AuctionCreated {
+                        id,
+                        seller,
+                        start_price,
+                        min_increment,
+                        end_time,
+                    }),
+                    PSP34Event::BidPlaced {
+                        id,
+                        bidder,
+                        amount,
+                        end_time,
+                    } => self.env().emit_event(BidPlaced {
+                        id,
+                        bidder,
+                        amount,
+                        end_time,
+                    }),
+                    PSP34Event::AuctionSettled {
+                        id,
+                        seller,
+                        winner,
+                        price,
+                    } => self.env().emit_event(AuctionSettled {
+                        id,
+                        seller,
+                        winner,
+                        price,
+                    }),
+                    PSP34Event::CollateralLocked { id, owner, lender } => self
+                        .env()
+                        .emit_event(CollateralLocked { id, owner, lender }),
+                    PSP34Event::CollateralReleased { id, owner, lender } => self
+                        .env()
+                        .emit_event(CollateralReleased { id, owner, lender }),
+                    PSP34Event::CollateralSeized { id, owner, lender } => self
+                        .env()
+                        .emit_event(CollateralSeized { id, owner, lender }),
+                    PSP34Event::BundleCreated {
+                        id,
+                        owner,
+                        components,
+                    } => self.env().emit_event(BundleCreated {
+                        id,
+                        owner,
+                        components,
+                    }),
+                    PSP34Event::Unbundled {
+                        id,
+                        owner,
+                        components,
+                    } => self.env().emit_event(Unbundled {
+                        id,
+                        owner,
+                        components,
+                    }),
+                    PSP34Event::Crafted {
+                        recipe_id,
+                        owner,
+                        inputs,
+                        output,
+                    } => self.env().emit_event(Crafted {
+                        recipe_id,
+                        owner,
+                        inputs,
+                        output,
+                    }),
                 }
             }
         }
     }
 
-    // (3)
+    // (3)
+    #[ink(event)]
+    pub struct Approval {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        operator: AccountId,
+        #[ink(topic)]
+        id: Option<Id>,
+        approved: bool,
+    }
+
+    // (3)
+    #[ink(event)]
+    pub struct Transfer {
+        #[ink(topic)]
+        from: Option<AccountId>,
+        #[ink(topic)]
+        to: Option<AccountId>,
+        #[ink(topic)]
+        id: Id,
+    }
+
+    // (3)
+    #[ink(event)]
+    pub struct AttributeSet {
+        id: Id,
+        key: Vec<u8>,
+        data: Vec<u8>,
+    }
+
+    #[ink(event)]
+    pub struct TransferOffered {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        #[ink(topic)]
+        id: Id,
+        deadline: Option<u32>,
+    }
+
+    #[ink(event)]
+    pub struct TransferClaimed {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        #[ink(topic)]
+        id: Id,
+    }
+
+    #[ink(event)]
+    pub struct TransferCancelled {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        #[ink(topic)]
+        id: Id,
+    }
+
+    #[ink(event)]
+    pub struct ProvenanceSet {
+        hash: [u8; 32],
+    }
+
+    #[ink(event)]
+    pub struct Minted {
+        #[ink(topic)]
+        to: AccountId,
+        #[ink(topic)]
+        id: Id,
+    }
+
+    #[ink(event)]
+    pub struct TransferMemo {
+        #[ink(topic)]
+        id: Id,
+        sequence: u32,
+        memo: Vec<u8>,
+    }
+
+    /// The errors of the PSP22 standard, needed to decode the result of `PSP22::transfer`.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PSP22Error {
+        Custom(String),
+        InsufficientBalance,
+        InsufficientAllowance,
+        ZeroRecipientAddress,
+        ZeroSenderAddress,
+        SafeTransferCheckFailed(String),
+    }
+
+    impl From<PSP22Error> for PSP34Error {
+        fn from(_: PSP22Error) -> Self {
+            PSP34Error::Custom(String::from(
+                "PSP22 transfer was rejected by the token contract.",
+            ))
+        }
+    }
+
+    #[ink(event)]
+    pub struct TeleportedOut {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        from: AccountId,
+        dest_chain: u32,
+        dest_account: Vec<u8>,
+        nonce: u64,
+    }
+
+    #[ink(event)]
+    pub struct TeleportedIn {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        to: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct BalanceChanged {
+        #[ink(topic)]
+        account: AccountId,
+        new_balance: u32,
+    }
+
+    #[ink(event)]
+    pub struct Listed {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+        price: u128,
+    }
+
+    #[ink(event)]
+    pub struct Delisted {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Sold {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+        #[ink(topic)]
+        buyer: AccountId,
+        price: u128,
+    }
+
+    #[ink(event)]
+    pub struct OfferMade {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        bidder: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct OfferCancelled {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        bidder: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct OfferAccepted {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+        #[ink(topic)]
+        bidder: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct AuctionCreated {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+        start_price: u128,
+        min_increment: u128,
+        end_time: u64,
+    }
+
+    #[ink(event)]
+    pub struct BidPlaced {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        bidder: AccountId,
+        amount: u128,
+        end_time: u64,
+    }
+
+    #[ink(event)]
+    pub struct AuctionSettled {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+        #[ink(topic)]
+        winner: Option<AccountId>,
+        price: u128,
+    }
+
+    #[ink(event)]
+    pub struct CollateralLocked {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        lender: AccountId,
+    }
+
     #[ink(event)]
-    pub struct Approval {
+    pub struct CollateralReleased {
+        #[ink(topic)]
+        id: Id,
         #[ink(topic)]
         owner: AccountId,
         #[ink(topic)]
-        operator: AccountId,
+        lender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct CollateralSeized {
         #[ink(topic)]
-        id: Option<Id>,
-        approved: bool,
+        id: Id,
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        lender: AccountId,
     }
 
-    // (3)
     #[ink(event)]
-    pub struct Transfer {
+    pub struct BundleCreated {
         #[ink(topic)]
-        from: Option<AccountId>,
+        id: Id,
         #[ink(topic)]
-        to: Option<AccountId>,
+        owner: AccountId,
+        components: Vec<Id>,
+    }
+
+    #[ink(event)]
+    pub struct Unbundled {
         #[ink(topic)]
         id: Id,
+        #[ink(topic)]
+        owner: AccountId,
+        components: Vec<Id>,
     }
 
-    // (3)
     #[ink(event)]
-    pub struct AttributeSet {
+    pub struct Crafted {
+        recipe_id: u32,
+        #[ink(topic)]
+        owner: AccountId,
+        inputs: Vec<Id>,
+        #[ink(topic)]
+        output: Id,
+    }
+
+    #[ink(event)]
+    pub struct TokenRescued {
+        #[ink(topic)]
+        id: Id,
+        #[ink(topic)]
+        to: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Burned {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        caller: AccountId,
+        #[ink(topic)]
         id: Id,
-        key: Vec<u8>,
-        data: Vec<u8>,
     }
 
     // (4)
@@ -143,6 +1915,7 @@ mod token {
             data: ink::prelude::vec::Vec<u8>,
         ) -> Result<(), PSP34Error> {
             let events = self.data.transfer(self.env().caller(), to, id, data)?;
+            self.clear_transferred_user_attributes(&events);
             self.emit_events(events);
             Ok(())
         }
@@ -173,7 +1946,12 @@ mod token {
         fn mint(&mut self, id: Id) -> Result<(), PSP34Error> {
             // Add security, restrict usage of the message
             todo!();
-            let events = self.data.mint(self.env().caller(), id)?;
+            let events = self.data.mint(
+                self.env().caller(),
+                id,
+                self.env().block_number(),
+                self.env().block_timestamp(),
+            )?;
             self.emit_events(events);
             Ok(())
         }
@@ -185,7 +1963,9 @@ mod token {
         fn burn(&mut self, account: AccountId, id: Id) -> Result<(), PSP34Error> {
             // Add security, restrict usage of the message
             todo!();
-            let events = self.data.burn(self.env().caller(), account, id)?;
+            self.bundles.ensure_not_bundle(&self.data, &id)?;
+            let events = self.data.burn(self.env().caller(), account, id.clone())?;
+            self.metadata.clear_indexed_attributes(&id);
             self.emit_events(events);
             Ok(())
         }
@@ -203,5 +1983,2247 @@ mod token {
     #[cfg(test)]
     mod tests {
         crate::tests!(Token, Token::new);
+
+        use super::*;
+        use ink::env::{test::*, DefaultEnvironment as E};
+
+        // Fails to compile if the contract has no message with the given selector.
+        macro_rules! assert_selector {
+            ($selector:expr, $label:literal) => {
+                assert_eq!(
+                    <Token as ::ink::reflect::DispatchableMessageInfo<{ $selector }>>::LABEL,
+                    $label
+                );
+                assert_eq!(
+                    <Token as ::ink::reflect::DispatchableMessageInfo<{ $selector }>>::SELECTOR,
+                    $selector.to_be_bytes()
+                );
+            };
+        }
+
+        #[test]
+        fn exported_selectors_match_contract_messages() {
+            use crate::selectors;
+            assert_selector!(selectors::COLLECTION_ID, "PSP34::collection_id");
+            assert_selector!(selectors::TOTAL_SUPPLY, "PSP34::total_supply");
+            assert_selector!(selectors::BALANCE_OF, "PSP34::balance_of");
+            assert_selector!(selectors::ALLOWANCE, "PSP34::allowance");
+            assert_selector!(selectors::TRANSFER, "PSP34::transfer");
+            assert_selector!(selectors::APPROVE, "PSP34::approve");
+            assert_selector!(selectors::OWNER_OF, "PSP34::owner_of");
+            assert_selector!(selectors::GET_ATTRIBUTE, "PSP34Metadata::get_attribute");
+            assert_selector!(selectors::MINT, "PSP34Mintable::mint");
+            assert_selector!(selectors::BURN, "PSP34Burnable::burn");
+        }
+
+        #[ink::test]
+        fn admin_messages_are_restricted_to_the_owner() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.owner(), Some(accounts.alice));
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            let recipe = crafting::Recipe {
+                inputs: vec![],
+                output: vec![],
+            };
+
+            set_caller::<E>(accounts.bob);
+            let denied = Some(PSP34Error::NotApproved);
+            assert_eq!(token.mint_sequential(1).err(), denied);
+            assert_eq!(token.mint_random().err(), denied);
+            assert_eq!(token.mint_airdrop(vec![accounts.bob]).err(), denied);
+            assert_eq!(token.raise_max_balance(10).err(), denied);
+            assert_eq!(
+                token.set_balance_limit_exempt(accounts.bob, true).err(),
+                denied
+            );
+            assert_eq!(token.set_bridge_authority(Some(accounts.bob)).err(), denied);
+            assert_eq!(token.rescue_token(Id::U8(1), accounts.bob).err(), denied);
+            assert_eq!(token.register_recipe(recipe).err(), denied);
+            assert_eq!(token.set_royalty(Some((accounts.bob, 100))).err(), denied);
+            assert_eq!(
+                token
+                    .set_royalty_split(100, vec![(accounts.bob, 10_000)])
+                    .err(),
+                denied
+            );
+            assert_eq!(token.set_timelock_delay(Some(1000)).err(), denied);
+            assert_eq!(
+                token.schedule(timelock::Action::SetDelay(Some(1000))).err(),
+                denied
+            );
+            assert_eq!(token.execute(0).err(), denied);
+            assert_eq!(token.cancel(0).err(), denied);
+            assert_eq!(token.set_refund_amount(10).err(), denied);
+            assert_eq!(
+                token
+                    .mint_and_approve(accounts.bob, Id::U8(2), accounts.bob)
+                    .err(),
+                denied
+            );
+            assert_eq!(
+                token
+                    .sweep_psp34(accounts.django, Id::U8(1), accounts.bob)
+                    .err(),
+                denied
+            );
+            assert_eq!(
+                token.sweep_psp22(accounts.django, 10, accounts.bob).err(),
+                denied
+            );
+            assert_eq!(token.admin_burn(Id::U8(1), vec![]).err(), denied);
+            assert_eq!(token.set_provenance_hash([1; 32]).err(), denied);
+            assert_eq!(token.set_collection_id(Id::U8(0)).err(), denied);
+            assert_eq!(token.migrate().err(), denied);
+            assert_eq!(
+                token
+                    .set_attribute(Id::U8(1), b"name".to_vec(), b"Bob".to_vec())
+                    .err(),
+                denied
+            );
+            assert_eq!(
+                token.set_clear_user_attributes_on_transfer(true).err(),
+                denied
+            );
+            assert_eq!(
+                token.set_contract_uri(String::from("ipfs://bob")).err(),
+                denied
+            );
+
+            // Nothing changed.
+            assert_eq!(token.total_supply(), 1);
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.alice));
+            assert_eq!(token.bridge_authority(), None);
+            assert_eq!(token.refund_amount(), 0);
+            assert_eq!(token.get_attribute(Id::U8(1), b"name".to_vec()), None);
+
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.set_refund_amount(10), Ok(()));
+            assert_eq!(token.refund_amount(), 10);
+        }
+
+        #[ink::test]
+        fn migrate_lets_the_upgrader_claim_a_contract_without_owner() {
+            use ink::storage::traits::StorageKey;
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            // Storage of a contract deployed before the owner was stored.
+            ink::env::clear_contract_storage(&token.owner.key());
+            assert_eq!(token.owner(), None);
+            // Current storage cannot be claimed.
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.migrate(), Err(PSP34Error::NotApproved));
+            assert_eq!(token.owner(), None);
+
+            token.data.set_storage_version(CURRENT_VERSION - 1);
+            assert_eq!(token.migrate(), Ok(CURRENT_VERSION));
+            assert_eq!(token.owner(), Some(accounts.bob));
+            assert_eq!(token.set_refund_amount(10), Ok(()));
+            // Nobody else can claim it afterwards.
+            set_caller::<E>(accounts.charlie);
+            token.data.set_storage_version(CURRENT_VERSION - 1);
+            assert_eq!(token.migrate(), Err(PSP34Error::NotApproved));
+            assert_eq!(token.owner(), Some(accounts.bob));
+        }
+
+        #[ink::test]
+        fn simulate_transfer_matches_transfer() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            // Simulating a transfer of a non existent token fails like the real one.
+            assert_eq!(
+                token.simulate_transfer(accounts.alice, accounts.bob, Id::U8(1)),
+                Err(PSP34Error::TokenNotExists)
+            );
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            // Transfer to the current owner is a no-op without events.
+            assert_eq!(
+                token.simulate_transfer(accounts.alice, accounts.alice, Id::U8(1)),
+                Ok(vec![])
+            );
+            // Bob is not approved by Alice.
+            assert_eq!(
+                token.simulate_transfer(accounts.bob, accounts.eve, Id::U8(1)),
+                Err(PSP34Error::NotApproved)
+            );
+            set_caller::<E>(accounts.bob);
+            assert_eq!(
+                token.transfer(accounts.eve, Id::U8(1), vec![]),
+                Err(PSP34Error::NotApproved)
+            );
+            // Alice can transfer her token and the simulation does not move it.
+            assert_eq!(
+                token.simulate_transfer(accounts.alice, accounts.bob, Id::U8(1)),
+                Ok(vec![PSP34EventKind::Transfer])
+            );
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.alice));
+            let start = recorded_events().count();
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.transfer(accounts.bob, Id::U8(1), vec![]), Ok(()));
+            assert_eq!(recorded_events().count(), start + 1);
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.bob));
+        }
+
+        #[ink::test]
+        fn minted_at_is_recorded_and_cleared() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.minted_at(Id::U8(1)), None);
+            advance_block::<E>();
+            advance_block::<E>();
+            set_block_timestamp::<E>(1_000);
+            let block = ink::env::block_number::<E>();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            advance_block::<E>();
+            set_block_timestamp::<E>(7_000);
+            assert_eq!(token.mint(Id::U8(2)), Ok(()));
+            assert_eq!(token.minted_at(Id::U8(1)), Some((block, 1_000)));
+            assert_eq!(token.minted_at(Id::U8(2)), Some((block + 1, 7_000)));
+            // Transfers do not change the creation time.
+            assert_eq!(token.transfer(accounts.bob, Id::U8(1), vec![]), Ok(()));
+            assert_eq!(token.minted_at(Id::U8(1)), Some((block, 1_000)));
+            // Burning clears it.
+            assert_eq!(token.burn(accounts.alice, Id::U8(2)), Ok(()));
+            assert_eq!(token.minted_at(Id::U8(2)), None);
+        }
+
+        #[ink::test]
+        fn batch_mint_records_the_same_time_for_all_tokens() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            advance_block::<E>();
+            set_block_timestamp::<E>(42_000);
+            let block = ink::env::block_number::<E>();
+            assert_eq!(token.mint_sequential(2), Ok((0, 2)));
+            assert_eq!(
+                token.mint_airdrop(vec![accounts.bob, accounts.charlie]),
+                Ok(vec![Id::U128(2), Id::U128(3)])
+            );
+            for id in 0..4 {
+                assert_eq!(token.minted_at(Id::U128(id)), Some((block, 42_000)));
+            }
+        }
+
+        #[ink::test]
+        fn transfer_from_many_settles_tokens_of_different_owners() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            // Alice and Bob both own a token and approve Eve for all their tokens.
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.approve(accounts.eve, None, true), Ok(()));
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.mint(Id::U8(2)), Ok(()));
+            assert_eq!(token.mint(Id::U8(3)), Ok(()));
+            assert_eq!(token.approve(accounts.eve, Some(Id::U8(2)), true), Ok(()));
+            set_caller::<E>(accounts.eve);
+            // Eve is not approved for token 3, so nothing is moved.
+            assert_eq!(
+                token.transfer_from_many(
+                    vec![
+                        (accounts.alice, accounts.frank, Id::U8(1)),
+                        (accounts.bob, accounts.frank, Id::U8(3)),
+                    ],
+                    vec![]
+                ),
+                Err(PSP34Error::NotApproved)
+            );
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.alice));
+            // Token 1 does not belong to Bob.
+            assert_eq!(
+                token.transfer_from_many(vec![(accounts.bob, accounts.frank, Id::U8(1))], vec![]),
+                Err(PSP34Error::NotApproved)
+            );
+            let start = recorded_events().count();
+            assert_eq!(
+                token.transfer_from_many(
+                    vec![
+                        (accounts.alice, accounts.frank, Id::U8(1)),
+                        (accounts.bob, accounts.frank, Id::U8(2)),
+                    ],
+                    vec![]
+                ),
+                Ok(())
+            );
+            assert_eq!(recorded_events().count(), start + 2);
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.frank));
+            assert_eq!(token.owner_of(Id::U8(2)), Some(accounts.frank));
+            assert_eq!(token.balance_of(accounts.alice), 0);
+            assert_eq!(token.balance_of(accounts.bob), 1);
+            assert_eq!(token.balance_of(accounts.frank), 2);
+        }
+
+        #[ink::test]
+        fn indexed_attributes_follow_overwrites_and_burns() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            let element = b"element".to_vec();
+            let (fire, water) = (b"fire".to_vec(), b"water".to_vec());
+            for i in 0..25 {
+                assert_eq!(token.mint(Id::U8(i)), Ok(()));
+                let value = if i % 5 == 0 { &water } else { &fire };
+                assert!(token
+                    .metadata
+                    .set_indexed_attribute(Id::U8(i), element.clone(), value.clone())
+                    .is_ok());
+            }
+            assert_eq!(token.count_by_attribute(element.clone(), fire.clone()), 20);
+            assert_eq!(token.count_by_attribute(element.clone(), water.clone()), 5);
+            // Pages of the large bucket cover every token exactly once.
+            let mut ids: Vec<Id> = (0..3)
+                .flat_map(|page| {
+                    token.tokens_by_attribute(element.clone(), fire.clone(), page * 8, 8)
+                })
+                .collect();
+            ids.sort();
+            let expected: Vec<Id> = (0..25).filter(|i| i % 5 != 0).map(Id::U8).collect();
+            assert_eq!(ids, expected);
+            // Overwriting the value moves the token to the other bucket.
+            assert!(token
+                .metadata
+                .set_indexed_attribute(Id::U8(1), element.clone(), water.clone())
+                .is_ok());
+            assert_eq!(token.count_by_attribute(element.clone(), fire.clone()), 19);
+            assert_eq!(token.count_by_attribute(element.clone(), water.clone()), 6);
+            assert!(token
+                .tokens_by_attribute(element.clone(), water.clone(), 0, 10)
+                .contains(&Id::U8(1)));
+            // Burning removes the token from the index.
+            assert_eq!(token.burn(accounts.alice, Id::U8(1)), Ok(()));
+            assert_eq!(token.count_by_attribute(element.clone(), water.clone()), 5);
+            assert_eq!(token.get_attribute(Id::U8(1), element.clone()), None);
+        }
+
+        #[cfg(feature = "enumerable")]
+        #[ink::test]
+        fn holders_follow_token_churn() {
+            let accounts = default_accounts::<E>();
+            let users = [
+                accounts.alice,
+                accounts.bob,
+                accounts.charlie,
+                accounts.django,
+                accounts.eve,
+            ];
+            let mut token = Token::new();
+            for i in 0..10 {
+                assert_eq!(token.mint(Id::U8(i)), Ok(()));
+            }
+            // Pass the tokens around, emptying some accounts and filling others.
+            for round in 0..4 {
+                for i in 0..10u8 {
+                    let id = Id::U8(i);
+                    let Some(owner) = token.owner_of(id.clone()) else {
+                        continue;
+                    };
+                    let to = users[(i as usize * 3 + round) % users.len()];
+                    set_caller::<E>(owner);
+                    assert_eq!(token.transfer(to, id, vec![]), Ok(()));
+                }
+                set_caller::<E>(accounts.alice);
+                if token.owner_of(Id::U8(round as u8)) == Some(accounts.alice) {
+                    assert_eq!(token.burn(accounts.alice, Id::U8(round as u8)), Ok(()));
+                }
+
+                let mut holders = vec![];
+                for page in 0.. {
+                    let chunk = token.holders(page * 2, 2);
+                    if chunk.is_empty() {
+                        break;
+                    }
+                    holders.extend(chunk);
+                }
+                let expected: Vec<AccountId> = users
+                    .iter()
+                    .filter(|user| token.balance_of(**user) > 0)
+                    .copied()
+                    .collect();
+                assert_eq!(holders.len(), expected.len());
+                for (index, holder) in holders.iter().enumerate() {
+                    assert!(expected.contains(holder));
+                    assert_eq!(token.owner_by_index(index as u128), Some(*holder));
+                }
+                assert_eq!(token.owner_by_index(holders.len() as u128), None);
+            }
+        }
+
+        #[ink::test]
+        fn mint_sequential_assigns_consecutive_ids() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            let start = recorded_events().count();
+            assert_eq!(token.mint_sequential(5), Ok((0, 5)));
+            assert_eq!(recorded_events().count(), start + 5);
+            for id in 0..5 {
+                assert_eq!(token.owner_of(Id::U128(id)), Some(accounts.alice));
+            }
+            assert_eq!(token.balance_of(accounts.alice), 5);
+            // Following batches continue from the next id.
+            assert_eq!(token.mint_sequential(2), Ok((5, 7)));
+            assert_eq!(token.balance_of(accounts.alice), 7);
+            assert_eq!(token.total_supply(), 7);
+            // A batch overlapping an existing id mints nothing.
+            assert_eq!(token.mint(Id::U128(8)), Ok(()));
+            assert_eq!(token.mint_sequential(3), Err(PSP34Error::TokenExists));
+            assert_eq!(token.balance_of(accounts.alice), 8);
+            assert_eq!(token.owner_of(Id::U128(7)), None);
+            // The size of a batch is bounded.
+            assert_eq!(
+                token.mint_sequential(MAX_SEQUENTIAL_MINT + 1),
+                Err(PSP34Error::Custom(String::from(
+                    "Too many tokens minted in a single call."
+                )))
+            );
+            assert_eq!(token.total_supply(), 8);
+        }
+
+        #[ink::test]
+        fn mint_airdrop_assigns_ids_in_order() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint_sequential(2), Ok((0, 2)));
+            let recipients = vec![accounts.bob, accounts.charlie, accounts.bob, accounts.eve];
+            let start = recorded_events().count();
+            assert_eq!(
+                token.mint_airdrop(recipients.clone()),
+                Ok((2..6).map(Id::U128).collect::<Vec<_>>())
+            );
+            assert_eq!(recorded_events().count(), start + 4);
+            for (recipient, id) in recipients.iter().zip(2..6) {
+                assert_eq!(token.owner_of(Id::U128(id)), Some(*recipient));
+            }
+            assert_eq!(token.balance_of(accounts.bob), 2);
+            assert_eq!(token.balance_of(accounts.charlie), 1);
+            assert_eq!(token.balance_of(accounts.eve), 1);
+            assert_eq!(token.total_supply(), 6);
+            // The number of recipients per call is bounded.
+            assert!(token
+                .mint_airdrop(vec![accounts.bob; MAX_AIRDROP_RECIPIENTS + 1])
+                .is_err());
+            assert_eq!(token.total_supply(), 6);
+        }
+
+        #[ink::test]
+        fn attribute_counts_follow_writes_and_removals() {
+            let mut token = Token::new();
+            let (name, color) = (b"name".to_vec(), b"color".to_vec());
+            assert_eq!(token.total_attributes(), 0);
+            assert_eq!(token.attribute_count(Id::U8(1)), 0);
+            assert!(token
+                .metadata
+                .set_attribute(Id::U8(1), name.clone(), b"first".to_vec())
+                .is_ok());
+            assert!(token
+                .metadata
+                .set_attribute(Id::U8(1), color.clone(), b"red".to_vec())
+                .is_ok());
+            assert!(token
+                .metadata
+                .set_attribute(Id::U8(2), name.clone(), b"second".to_vec())
+                .is_ok());
+            assert_eq!(token.total_attributes(), 3);
+            assert_eq!(token.attribute_count(Id::U8(1)), 2);
+            assert_eq!(token.attribute_count(Id::U8(2)), 1);
+            // Overwriting an attribute does not change the counts.
+            assert!(token
+                .metadata
+                .set_attribute(Id::U8(1), color.clone(), b"blue".to_vec())
+                .is_ok());
+            assert_eq!(token.total_attributes(), 3);
+            assert_eq!(token.attribute_count(Id::U8(1)), 2);
+            // Removing decrements them, removing a missing attribute does not.
+            assert_eq!(
+                token.metadata.remove_attribute(Id::U8(1), color.clone()),
+                Some(b"blue".to_vec())
+            );
+            assert_eq!(token.metadata.remove_attribute(Id::U8(1), color), None);
+            assert_eq!(token.total_attributes(), 2);
+            assert_eq!(token.attribute_count(Id::U8(1)), 1);
+            assert_eq!(
+                token.metadata.remove_attribute(Id::U8(2), name),
+                Some(b"second".to_vec())
+            );
+            assert_eq!(token.attribute_count(Id::U8(2)), 0);
+            assert_eq!(token.total_attributes(), 1);
+        }
+
+        #[cfg(feature = "enumerable")]
+        #[ink::test]
+        fn burn_all_of_burns_in_chunks() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint_sequential(7), Ok((0, 7)));
+            assert_eq!(
+                token.mint_airdrop(vec![accounts.bob]),
+                Ok(vec![Id::U128(7)])
+            );
+            // Only the owner or an operator for all tokens can burn them.
+            set_caller::<E>(accounts.eve);
+            assert_eq!(
+                token.burn_all_of(accounts.alice, 3),
+                Err(PSP34Error::NotApproved)
+            );
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.approve(accounts.eve, None, true), Ok(()));
+            set_caller::<E>(accounts.eve);
+            let mut burned = vec![];
+            for expected in [3, 3, 1, 0] {
+                let ids = token.burn_all_of(accounts.alice, 3).unwrap();
+                assert_eq!(ids.len(), expected);
+                burned.extend(ids);
+            }
+            burned.sort();
+            assert_eq!(burned, (0..7).map(Id::U128).collect::<Vec<_>>());
+            assert_eq!(token.balance_of(accounts.alice), 0);
+            assert_eq!(token.total_supply(), 1);
+            assert_eq!(token.owner_of(Id::U128(7)), Some(accounts.bob));
+        }
+
+        #[ink::test]
+        fn transfer_count_tracks_transfers_only() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.transfer_count(Id::U8(1)), 0);
+            // Pass the token back and forth.
+            for (i, (from, to)) in [
+                (accounts.alice, accounts.bob),
+                (accounts.bob, accounts.alice),
+            ]
+            .iter()
+            .cycle()
+            .take(5)
+            .enumerate()
+            {
+                set_caller::<E>(*from);
+                assert_eq!(token.transfer(*to, Id::U8(1), vec![]), Ok(()));
+                assert_eq!(token.transfer_count(Id::U8(1)), i as u32 + 1);
+            }
+            // A transfer to the current owner is a no-op and does not count.
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.transfer(accounts.bob, Id::U8(1), vec![]), Ok(()));
+            assert_eq!(token.transfer_count(Id::U8(1)), 5);
+            // Burning resets the counter, so a re-minted token starts from zero.
+            assert_eq!(token.burn(accounts.bob, Id::U8(1)), Ok(()));
+            assert_eq!(token.transfer_count(Id::U8(1)), 0);
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.transfer_count(Id::U8(1)), 0);
+        }
+
+        #[ink::test]
+        fn new_with_metadata_sets_collection_attributes() {
+            let start = recorded_events().count();
+            let token = Token::new_with_metadata(
+                b"Collection".to_vec(),
+                b"COL".to_vec(),
+                b"ipfs://base/".to_vec(),
+            );
+            assert_eq!(recorded_events().count(), start + 3);
+            let collection_id = token.collection_id();
+            assert_eq!(
+                token.get_attribute(collection_id.clone(), metadata::NAME_KEY.to_vec()),
+                Some(b"Collection".to_vec())
+            );
+            assert_eq!(
+                token.get_attribute(collection_id.clone(), metadata::SYMBOL_KEY.to_vec()),
+                Some(b"COL".to_vec())
+            );
+            assert_eq!(
+                token.get_attribute(collection_id, metadata::BASE_URI_KEY.to_vec()),
+                Some(b"ipfs://base/".to_vec())
+            );
+            assert_eq!(
+                token.collection_metadata(),
+                metadata::CollectionMetadata {
+                    name: Some(b"Collection".to_vec()),
+                    symbol: Some(b"COL".to_vec()),
+                    base_uri: Some(b"ipfs://base/".to_vec()),
+                }
+            );
+        }
+
+        #[ink::test]
+        fn collection_metadata_reports_unset_fields() {
+            let mut token = Token::new();
+            assert_eq!(
+                token.collection_metadata(),
+                metadata::CollectionMetadata::default()
+            );
+            let collection_id = token.collection_id();
+            assert_eq!(
+                token.set_attribute(
+                    collection_id,
+                    metadata::SYMBOL_KEY.to_vec(),
+                    b"COL".to_vec()
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                token.collection_metadata(),
+                metadata::CollectionMetadata {
+                    name: None,
+                    symbol: Some(b"COL".to_vec()),
+                    base_uri: None,
+                }
+            );
+        }
+
+        #[ink::test]
+        fn offered_transfer_is_claimed_by_recipient_only() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.offer_transfer(accounts.bob, Id::U8(1), None), Ok(()));
+            assert_eq!(
+                token.pending_transfer(Id::U8(1)),
+                Some(PendingTransfer {
+                    from: accounts.alice,
+                    to: accounts.bob,
+                    deadline: None,
+                })
+            );
+            // The offered token is locked for its owner.
+            assert!(token.transfer(accounts.eve, Id::U8(1), vec![]).is_err());
+            assert!(token.burn(accounts.alice, Id::U8(1)).is_err());
+            // Only the designated recipient can claim it.
+            set_caller::<E>(accounts.eve);
+            assert_eq!(
+                token.claim_transfer(Id::U8(1)),
+                Err(PSP34Error::NotApproved)
+            );
+            set_caller::<E>(accounts.bob);
+            let start = recorded_events().count();
+            assert_eq!(token.claim_transfer(Id::U8(1)), Ok(()));
+            assert_eq!(recorded_events().count(), start + 2);
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.bob));
+            assert_eq!(token.balance_of(accounts.alice), 0);
+            assert_eq!(token.balance_of(accounts.bob), 1);
+            assert_eq!(token.pending_transfer(Id::U8(1)), None);
+            assert!(token.claim_transfer(Id::U8(1)).is_err());
+            // The new owner can transfer the token normally.
+            assert_eq!(token.transfer(accounts.eve, Id::U8(1), vec![]), Ok(()));
+        }
+
+        #[ink::test]
+        fn offered_transfer_can_be_cancelled_by_sender() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.offer_transfer(accounts.bob, Id::U8(1), None), Ok(()));
+            // A second offer is not allowed while the first one is pending.
+            assert!(token.offer_transfer(accounts.eve, Id::U8(1), None).is_err());
+            set_caller::<E>(accounts.bob);
+            assert_eq!(
+                token.cancel_transfer(Id::U8(1)),
+                Err(PSP34Error::NotApproved)
+            );
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.cancel_transfer(Id::U8(1)), Ok(()));
+            assert_eq!(token.pending_transfer(Id::U8(1)), None);
+            set_caller::<E>(accounts.bob);
+            assert!(token.claim_transfer(Id::U8(1)).is_err());
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.alice));
+            // The token is unlocked again.
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.transfer(accounts.eve, Id::U8(1), vec![]), Ok(()));
+        }
+
+        #[ink::test]
+        fn offered_transfer_expires_after_deadline() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            advance_block::<E>();
+            let block = ink::env::block_number::<E>();
+            assert!(token
+                .offer_transfer(accounts.bob, Id::U8(1), Some(block - 1))
+                .is_err());
+            assert_eq!(
+                token.offer_transfer(accounts.bob, Id::U8(1), Some(block + 1)),
+                Ok(())
+            );
+            advance_block::<E>();
+            advance_block::<E>();
+            set_caller::<E>(accounts.bob);
+            assert!(token.claim_transfer(Id::U8(1)).is_err());
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.alice));
+            // The expired offer still locks the token until the sender cancels it.
+            set_caller::<E>(accounts.alice);
+            assert!(token.transfer(accounts.eve, Id::U8(1), vec![]).is_err());
+            assert_eq!(token.cancel_transfer(Id::U8(1)), Ok(()));
+            assert_eq!(token.transfer(accounts.eve, Id::U8(1), vec![]), Ok(()));
+        }
+
+        #[ink::test]
+        fn limited_approval_expires_after_its_uses() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            for i in 0..4 {
+                assert_eq!(token.mint(Id::U8(i)), Ok(()));
+            }
+            assert!(token.approve_limited(accounts.bob, None, 0).is_err());
+            assert_eq!(token.approve_limited(accounts.bob, None, 2), Ok(()));
+            assert_eq!(
+                token.approval_uses(accounts.alice, accounts.bob, None),
+                Some(2)
+            );
+
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.transfer(accounts.eve, Id::U8(0), vec![]), Ok(()));
+            assert_eq!(
+                token.approval_uses(accounts.alice, accounts.bob, None),
+                Some(1)
+            );
+            assert!(token.allowance(accounts.alice, accounts.bob, None));
+            // A batch exceeding the remaining uses fails as a whole.
+            assert_eq!(
+                token.transfer_from_many(
+                    vec![
+                        (accounts.alice, accounts.eve, Id::U8(1)),
+                        (accounts.alice, accounts.eve, Id::U8(2)),
+                    ],
+                    vec![]
+                ),
+                Err(PSP34Error::NotApproved)
+            );
+            assert_eq!(token.transfer(accounts.eve, Id::U8(1), vec![]), Ok(()));
+            assert_eq!(
+                token.approval_uses(accounts.alice, accounts.bob, None),
+                None
+            );
+            assert!(!token.allowance(accounts.alice, accounts.bob, None));
+            assert_eq!(
+                token.transfer(accounts.eve, Id::U8(2), vec![]),
+                Err(PSP34Error::NotApproved)
+            );
+
+            // A plain approval replaces the limited one.
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.approve_limited(accounts.bob, None, 1), Ok(()));
+            assert_eq!(token.approve(accounts.bob, None, true), Ok(()));
+            assert_eq!(
+                token.approval_uses(accounts.alice, accounts.bob, None),
+                None
+            );
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.transfer(accounts.eve, Id::U8(2), vec![]), Ok(()));
+            assert_eq!(token.transfer(accounts.eve, Id::U8(3), vec![]), Ok(()));
+            assert!(token.allowance(accounts.alice, accounts.bob, None));
+        }
+
+        #[ink::test]
+        fn provenance_hash_is_set_once_before_minting() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.provenance_hash(), None);
+            let start = recorded_events().count();
+            assert_eq!(token.set_provenance_hash([1; 32]), Ok(()));
+            assert_eq!(recorded_events().count(), start + 1);
+            assert_eq!(token.provenance_hash(), Some([1; 32]));
+            assert_eq!(
+                token.set_provenance_hash([2; 32]),
+                Err(PSP34Error::Custom(String::from(
+                    "Provenance hash is already set."
+                )))
+            );
+            assert_eq!(token.provenance_hash(), Some([1; 32]));
+
+            // Minting closes the window, even if all the tokens are burned afterwards.
+            // The new instance keeps its storage under its own contract account.
+            set_callee::<E>(AccountId::from([9; 32]));
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.burn(accounts.alice, Id::U8(1)), Ok(()));
+            assert_eq!(token.minted_count(), 1);
+            assert_eq!(
+                token.set_provenance_hash([1; 32]),
+                Err(PSP34Error::Custom(String::from(
+                    "Provenance hash cannot be set after minting has started."
+                )))
+            );
+            assert_eq!(token.provenance_hash(), None);
+        }
+
+        #[ink::test]
+        fn admin_burns_token_of_another_account() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.offer_transfer(accounts.eve, Id::U8(1), None), Ok(()));
+            assert_eq!(
+                token.approve(accounts.charlie, Some(Id::U8(1)), true),
+                Ok(())
+            );
+
+            // Alice is neither the owner nor approved, so she cannot burn it normally.
+            set_caller::<E>(accounts.alice);
+            assert_eq!(
+                token.burn(accounts.bob, Id::U8(1)),
+                Err(PSP34Error::NotApproved)
+            );
+            let start = recorded_events().count();
+            assert_eq!(
+                token.admin_burn(Id::U8(1), vec![accounts.charlie, accounts.django]),
+                Ok(())
+            );
+            // The approval of Charlie is revoked, Django had none.
+            assert_eq!(recorded_events().count(), start + 2);
+            assert!(!token.allowance(accounts.bob, accounts.charlie, Some(Id::U8(1))));
+            assert_eq!(token.owner_of(Id::U8(1)), None);
+            assert_eq!(token.balance_of(accounts.bob), 0);
+            assert_eq!(token.total_supply(), 0);
+            assert_eq!(token.pending_transfer(Id::U8(1)), None);
+            assert_eq!(
+                token.admin_burn(Id::U8(1), vec![]),
+                Err(PSP34Error::TokenNotExists)
+            );
+        }
+
+        #[ink::test]
+        fn admin_burn_refunds_offers_and_rejects_held_tokens() {
+            let accounts = default_accounts::<E>();
+            set_callee::<E>(AccountId::from([0xff; 32]));
+            let contract = callee::<E>();
+            let balance = |account| get_account_balance::<E>(account).unwrap_or_default();
+            let mut token = Token::new();
+            set_caller::<E>(accounts.bob);
+            for id in 1..=5 {
+                assert_eq!(token.mint(Id::U8(id)), Ok(()));
+            }
+            assert_eq!(token.list(Id::U8(2), 100), Ok(()));
+            assert_eq!(token.create_auction(Id::U8(3), 100, 10, 1000), Ok(()));
+            assert_eq!(
+                token.lock_as_collateral(Id::U8(4), accounts.charlie, vec![]),
+                Ok(())
+            );
+            let bundle = token.create_bundle(vec![Id::U8(5)]).unwrap();
+            set_caller::<E>(accounts.eve);
+            pay(150);
+            assert_eq!(token.make_offer(Id::U8(1)), Ok(()));
+
+            // Held tokens have to be released by their extension first.
+            set_caller::<E>(accounts.alice);
+            for (id, reason) in [
+                (Id::U8(2), "Token is listed for sale."),
+                (Id::U8(3), "Token is auctioned."),
+                (Id::U8(4), "Token is locked as collateral."),
+                (Id::U8(5), "Token is bundled."),
+                (bundle, "Bundles can only be burned by unbundling."),
+            ] {
+                assert_eq!(
+                    token.admin_burn(id.clone(), vec![]),
+                    Err(PSP34Error::Custom(String::from(reason)))
+                );
+                assert!(token.owner_of(id).is_some());
+            }
+
+            // The offers for a burned token are refunded.
+            let eve = balance(accounts.eve);
+            let start = recorded_events().count();
+            assert_eq!(token.admin_burn(Id::U8(1), vec![]), Ok(()));
+            assert_eq!(recorded_events().count(), start + 2);
+            assert_eq!(token.offers_for(Id::U8(1)), vec![]);
+            assert_eq!(token.total_escrowed(), 0);
+            assert_eq!(balance(accounts.eve), eve + 150);
+            assert_eq!(balance(contract), 0);
+        }
+
+        #[ink::test]
+        fn get_attributes_reads_keys_in_order() {
+            let token = Token::new_with_metadata(b"Name".to_vec(), b"SYM".to_vec(), vec![]);
+            let collection_id = token.collection_id();
+            let keys = vec![
+                metadata::SYMBOL_KEY.to_vec(),
+                b"missing".to_vec(),
+                metadata::NAME_KEY.to_vec(),
+            ];
+            assert_eq!(
+                token.get_attributes(collection_id.clone(), keys),
+                vec![Some(b"SYM".to_vec()), None, Some(b"Name".to_vec())]
+            );
+            assert_eq!(
+                token.get_attributes(Id::U8(1), vec![metadata::NAME_KEY.to_vec()]),
+                vec![None]
+            );
+            // Keys over the limit are not read.
+            let keys = vec![metadata::NAME_KEY.to_vec(); metadata::MAX_ATTRIBUTE_KEYS + 1];
+            assert_eq!(
+                token.get_attributes(collection_id, keys).len(),
+                metadata::MAX_ATTRIBUTE_KEYS
+            );
+        }
+
+        #[ink::test]
+        fn consigned_token_can_be_returned() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.mint(Id::U8(2)), Ok(()));
+            // Bob has not agreed to act as an escrow yet.
+            assert!(token
+                .transfer_with_approval(accounts.bob, Id::U8(1), true, vec![])
+                .is_err());
+            set_caller::<E>(accounts.bob);
+            token.set_escrow(true);
+            assert!(token.is_escrow(accounts.bob));
+
+            set_caller::<E>(accounts.alice);
+            let start = recorded_events().count();
+            assert_eq!(
+                token.transfer_with_approval(accounts.bob, Id::U8(1), true, vec![]),
+                Ok(())
+            );
+            assert_eq!(recorded_events().count(), start + 2);
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.bob));
+            assert!(token.allowance(accounts.bob, accounts.alice, Some(Id::U8(1))));
+            // Alice takes the token back herself.
+            assert_eq!(token.transfer(accounts.alice, Id::U8(1), vec![]), Ok(()));
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.alice));
+            assert!(!token.allowance(accounts.bob, accounts.alice, Some(Id::U8(1))));
+
+            // The escrow can return the token as well.
+            assert_eq!(
+                token.transfer_with_approval(accounts.bob, Id::U8(2), true, vec![]),
+                Ok(())
+            );
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.transfer(accounts.alice, Id::U8(2), vec![]), Ok(()));
+            assert_eq!(token.owner_of(Id::U8(2)), Some(accounts.alice));
+            assert_eq!(token.balance_of(accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn owner_writes_user_attributes_only() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(
+                token.set_user_attribute(Id::U8(1), b"user:nickname".to_vec(), b"Rex".to_vec()),
+                Ok(())
+            );
+            assert_eq!(
+                token.get_attribute(Id::U8(1), b"user:nickname".to_vec()),
+                Some(b"Rex".to_vec())
+            );
+            // The owner cannot write keys outside the user namespace.
+            assert!(token
+                .set_user_attribute(Id::U8(1), b"rarity".to_vec(), b"legendary".to_vec())
+                .is_err());
+            assert_eq!(token.get_attribute(Id::U8(1), b"rarity".to_vec()), None);
+            // The admin path does not write user keys.
+            assert!(token
+                .set_attribute(Id::U8(1), b"user:nickname".to_vec(), b"Max".to_vec())
+                .is_err());
+            assert_eq!(
+                token.set_attribute(Id::U8(1), b"rarity".to_vec(), b"common".to_vec()),
+                Ok(())
+            );
+            // Other accounts cannot write the user keys.
+            set_caller::<E>(accounts.bob);
+            assert_eq!(
+                token.set_user_attribute(Id::U8(1), b"user:nickname".to_vec(), b"Max".to_vec()),
+                Err(PSP34Error::NotApproved)
+            );
+        }
+
+        #[ink::test]
+        fn user_attributes_are_cleared_on_transfer_if_enabled() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(
+                token.set_user_attribute(Id::U8(1), b"user:skin".to_vec(), b"gold".to_vec()),
+                Ok(())
+            );
+            assert_eq!(
+                token.set_attribute(Id::U8(1), b"rarity".to_vec(), b"rare".to_vec()),
+                Ok(())
+            );
+            // By default the user attributes stay with the token.
+            assert_eq!(token.transfer(accounts.bob, Id::U8(1), vec![]), Ok(()));
+            assert_eq!(
+                token.get_attribute(Id::U8(1), b"user:skin".to_vec()),
+                Some(b"gold".to_vec())
+            );
+
+            assert_eq!(token.set_clear_user_attributes_on_transfer(true), Ok(()));
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.transfer(accounts.alice, Id::U8(1), vec![]), Ok(()));
+            assert_eq!(token.get_attribute(Id::U8(1), b"user:skin".to_vec()), None);
+            assert_eq!(
+                token.get_attribute(Id::U8(1), b"rarity".to_vec()),
+                Some(b"rare".to_vec())
+            );
+            assert_eq!(token.attribute_count(Id::U8(1)), 1);
+        }
+
+        #[ink::test]
+        fn exact_approval_distinguishes_single_and_all() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.approve(accounts.bob, Some(Id::U8(1)), true), Ok(()));
+            assert!(token.has_exact_approval(accounts.alice, accounts.bob, Some(Id::U8(1))));
+            assert!(!token.has_exact_approval(accounts.alice, accounts.bob, None));
+
+            assert_eq!(token.approve(accounts.eve, None, true), Ok(()));
+            assert!(token.allowance(accounts.alice, accounts.eve, Some(Id::U8(1))));
+            assert!(!token.has_exact_approval(accounts.alice, accounts.eve, Some(Id::U8(1))));
+            assert!(token.has_exact_approval(accounts.alice, accounts.eve, None));
+        }
+
+        #[cfg(feature = "enumerable")]
+        #[ink::test]
+        fn index_of_owned_follows_removals() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            for i in 1..=3 {
+                assert_eq!(token.mint(Id::U8(i)), Ok(()));
+            }
+            assert_eq!(token.index_of_owned(accounts.alice, Id::U8(3)), Some(2));
+            assert_eq!(token.index_of_owned(accounts.bob, Id::U8(3)), None);
+            assert_eq!(token.index_of_owned(accounts.alice, Id::U8(4)), None);
+            // The last token takes the place of the removed one.
+            assert_eq!(token.transfer(accounts.bob, Id::U8(1), vec![]), Ok(()));
+            assert_eq!(token.index_of_owned(accounts.alice, Id::U8(3)), Some(0));
+            assert_eq!(token.index_of_owned(accounts.alice, Id::U8(1)), None);
+            assert_eq!(token.index_of_owned(accounts.bob, Id::U8(1)), Some(0));
+            assert_eq!(
+                token.data.owners_token_by_index(accounts.alice, 0),
+                Ok(Id::U8(3))
+            );
+        }
+
+        #[ink::test]
+        fn setting_the_same_attribute_value_emits_no_event() {
+            let mut token = Token::new();
+            let start = recorded_events().count();
+            assert_eq!(
+                token.set_attribute(Id::U8(1), b"rarity".to_vec(), b"rare".to_vec()),
+                Ok(())
+            );
+            assert_eq!(
+                token.set_attribute(Id::U8(1), b"rarity".to_vec(), b"rare".to_vec()),
+                Ok(())
+            );
+            assert_eq!(recorded_events().count(), start + 1);
+            assert_eq!(token.attribute_count(Id::U8(1)), 1);
+            // A different value is a change again.
+            assert_eq!(
+                token.set_attribute(Id::U8(1), b"rarity".to_vec(), b"common".to_vec()),
+                Ok(())
+            );
+            assert_eq!(recorded_events().count(), start + 2);
+        }
+
+        #[cfg(feature = "enumerable")]
+        #[ink::test]
+        fn oversized_indices_return_errors() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            for index in [1, u32::MAX as u128 + 1, u64::MAX as u128 + 1, u128::MAX] {
+                assert_eq!(
+                    token.data.owners_token_by_index(accounts.alice, index),
+                    Err(PSP34Error::TokenNotExists)
+                );
+                assert_eq!(
+                    token.data.token_by_index(index),
+                    Err(PSP34Error::TokenNotExists)
+                );
+                assert_eq!(token.owner_by_index(index), None);
+            }
+            assert_eq!(token.data.token_by_index(0), Ok(Id::U8(1)));
+        }
+
+        #[ink::test]
+        fn transfer_before_respects_deadline() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            set_block_timestamp::<E>(5_000);
+            // The deadline itself is still in time.
+            assert_eq!(
+                token.transfer_before(accounts.bob, Id::U8(1), 5_000, vec![]),
+                Ok(())
+            );
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.bob));
+
+            set_caller::<E>(accounts.bob);
+            let start = recorded_events().count();
+            assert_eq!(
+                token.transfer_before(accounts.alice, Id::U8(1), 4_999, vec![]),
+                Err(PSP34Error::Expired)
+            );
+            assert_eq!(recorded_events().count(), start);
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.bob));
+        }
+
+        #[ink::test]
+        fn transfer_with_memo_stores_memo() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            let start = recorded_events().count();
+            assert_eq!(
+                token.transfer_with_memo(accounts.bob, Id::U8(1), b"invoice 7".to_vec(), vec![]),
+                Ok(())
+            );
+            // A Transfer event followed by the memo.
+            assert_eq!(recorded_events().count(), start + 2);
+            assert_eq!(token.memo(Id::U8(1), 1), Some(b"invoice 7".to_vec()));
+            assert_eq!(token.memo(Id::U8(1), 0), None);
+
+            set_caller::<E>(accounts.bob);
+            assert_eq!(
+                token.transfer_with_memo(
+                    accounts.alice,
+                    Id::U8(1),
+                    vec![0; crate::MAX_MEMO_LEN + 1],
+                    vec![]
+                ),
+                Err(PSP34Error::Custom(String::from("Memo is too long.")))
+            );
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.bob));
+            assert_eq!(
+                token.transfer_with_memo(accounts.alice, Id::U8(1), b"refund".to_vec(), vec![]),
+                Ok(())
+            );
+            assert_eq!(token.memo(Id::U8(1), 2), Some(b"refund".to_vec()));
+            assert_eq!(token.memo(Id::U8(1), 1), Some(b"invoice 7".to_vec()));
+        }
+
+        #[ink::test]
+        fn transfer_reporting_returns_previous_owner() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(
+                token.transfer_reporting(accounts.bob, Id::U8(2), vec![]),
+                Err(PSP34Error::TokenNotExists)
+            );
+            assert_eq!(
+                token.approve(accounts.charlie, Some(Id::U8(1)), true),
+                Ok(())
+            );
+            // The operator conducting the transfer learns the owner it paid.
+            set_caller::<E>(accounts.charlie);
+            assert_eq!(
+                token.transfer_reporting(accounts.bob, Id::U8(1), vec![]),
+                Ok(accounts.alice)
+            );
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.bob));
+            set_caller::<E>(accounts.bob);
+            assert_eq!(
+                token.transfer_reporting(accounts.bob, Id::U8(1), vec![]),
+                Ok(accounts.bob)
+            );
+        }
+
+        #[ink::test]
+        fn rescue_token_returns_stuck_token() {
+            let accounts = default_accounts::<E>();
+            let contract = accounts.django;
+            set_callee::<E>(contract);
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.mint(Id::U8(2)), Ok(()));
+            // Alice sends a token to the contract by mistake.
+            assert_eq!(token.transfer(contract, Id::U8(1), vec![]), Ok(()));
+
+            let start = recorded_events().count();
+            assert_eq!(token.rescue_token(Id::U8(1), accounts.alice), Ok(()));
+            assert_eq!(recorded_events().count(), start + 2);
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.alice));
+
+            // Tokens not owned by the contract cannot be moved this way.
+            assert_eq!(
+                token.rescue_token(Id::U8(2), accounts.bob),
+                Err(PSP34Error::Custom(String::from(
+                    "Token is not owned by the contract."
+                )))
+            );
+            assert_eq!(
+                token.rescue_token(Id::U8(3), accounts.bob),
+                Err(PSP34Error::TokenNotExists)
+            );
+            assert_eq!(token.owner_of(Id::U8(2)), Some(accounts.alice));
+        }
+
+        #[ink::test]
+        fn sweep_rejects_own_collection() {
+            let accounts = default_accounts::<E>();
+            set_callee::<E>(accounts.django);
+            let mut token = Token::new();
+            let error = Err(PSP34Error::Custom(String::from(
+                "Tokens of this collection cannot be swept, use rescue_token.",
+            )));
+            assert_eq!(
+                token.sweep_psp34(accounts.django, Id::U8(1), accounts.alice),
+                error
+            );
+            assert_eq!(token.sweep_psp22(accounts.django, 1, accounts.alice), error);
+        }
+
+        #[test]
+        fn sweep_reports_failed_calls() {
+            let trapped: Result<MessageResult<Result<(), PSP34Error>>, _> =
+                Err(ink::env::Error::CalleeTrapped);
+            assert_eq!(
+                Token::call_result(trapped),
+                Err(PSP34Error::Custom(String::from(
+                    "Call to the token contract failed."
+                )))
+            );
+            let unknown: Result<MessageResult<Result<(), PSP34Error>>, _> =
+                Ok(Err(ink::LangError::CouldNotReadInput));
+            assert_eq!(
+                Token::call_result(unknown),
+                Err(PSP34Error::Custom(String::from(
+                    "Token contract could not dispatch the call."
+                )))
+            );
+            // The error of a PSP34 collection is kept, a PSP22 one is described.
+            let rejected: Result<MessageResult<Result<(), _>>, _> =
+                Ok(Ok(Err(PSP34Error::TokenNotExists)));
+            assert_eq!(
+                Token::call_result(rejected),
+                Err(PSP34Error::TokenNotExists)
+            );
+            let rejected: Result<MessageResult<Result<(), _>>, _> =
+                Ok(Ok(Err(PSP22Error::InsufficientBalance)));
+            assert_eq!(
+                Token::call_result(rejected),
+                Err(PSP34Error::Custom(String::from(
+                    "PSP22 transfer was rejected by the token contract."
+                )))
+            );
+            assert_eq!(Token::call_result::<_, PSP34Error>(Ok(Ok(Ok(())))), Ok(()));
+        }
+
+        #[ink::test]
+        fn contract_uri_is_set_and_overwritten() {
+            let mut token = Token::new();
+            assert_eq!(token.contract_uri(), None);
+            let start = recorded_events().count();
+            assert_eq!(
+                token.set_contract_uri(String::from("ipfs://collection.json")),
+                Ok(())
+            );
+            assert_eq!(recorded_events().count(), start + 1);
+            assert_eq!(
+                token.contract_uri(),
+                Some(String::from("ipfs://collection.json"))
+            );
+            assert_eq!(
+                token.set_contract_uri(String::from("ipfs://collection-v2.json")),
+                Ok(())
+            );
+            assert_eq!(recorded_events().count(), start + 2);
+            assert_eq!(
+                token.contract_uri(),
+                Some(String::from("ipfs://collection-v2.json"))
+            );
+            // The URI is an ordinary collection attribute.
+            assert_eq!(
+                token.get_attribute(token.collection_id(), metadata::CONTRACT_URI_KEY.to_vec()),
+                Some(b"ipfs://collection-v2.json".to_vec())
+            );
+        }
+
+        #[ink::test]
+        fn event_topics_follow_canonical_layout() {
+            use crate::topics::*;
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            let start = recorded_events().count();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.approve(accounts.bob, Some(Id::U8(1)), true), Ok(()));
+            let collection_id = token.collection_id();
+            assert_eq!(
+                token.set_attribute(collection_id, b"key".to_vec(), b"value".to_vec()),
+                Ok(())
+            );
+
+            let topics: Vec<Vec<Vec<u8>>> = recorded_events()
+                .skip(start)
+                .map(|event| event.topics)
+                .collect();
+            let expected = [
+                vec![
+                    TRANSFER_SIGNATURE_TOPIC,
+                    topic(TRANSFER_FROM, &None::<AccountId>),
+                    topic(TRANSFER_TO, &Some(accounts.alice)),
+                    topic(TRANSFER_ID, &Id::U8(1)),
+                ],
+                vec![
+                    APPROVAL_SIGNATURE_TOPIC,
+                    topic(APPROVAL_OWNER, &accounts.alice),
+                    topic(APPROVAL_OPERATOR, &accounts.bob),
+                    topic(APPROVAL_ID, &Some(Id::U8(1))),
+                ],
+                vec![ATTRIBUTE_SET_SIGNATURE_TOPIC],
+            ];
+            assert_eq!(topics.len(), expected.len());
+            for (topics, expected) in topics.iter().zip(expected) {
+                let expected: Vec<Vec<u8>> = expected.iter().map(|topic| topic.to_vec()).collect();
+                assert_eq!(*topics, expected);
+            }
+            // A signature fits in a topic as is, after the encoded empty prefix.
+            assert_eq!(TRANSFER_SIGNATURE_TOPIC, topic(b"", b"Token::Transfer"));
+            assert_eq!(&TRANSFER_SIGNATURE_TOPIC[1..16], b"Token::Transfer");
+        }
+
+        #[ink::test]
+        fn mint_and_approve_lets_operator_transfer() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            let start = recorded_events().count();
+            assert_eq!(
+                token.mint_and_approve(accounts.bob, Id::U8(1), accounts.charlie),
+                Ok(())
+            );
+            assert_eq!(recorded_events().count(), start + 2);
+            assert!(token.allowance(accounts.bob, accounts.charlie, Some(Id::U8(1))));
+            assert_eq!(
+                token.mint_and_approve(accounts.bob, Id::U8(2), accounts.bob),
+                Err(PSP34Error::SelfApprove)
+            );
+            assert_eq!(token.owner_of(Id::U8(2)), None);
+
+            set_caller::<E>(accounts.charlie);
+            assert_eq!(token.transfer(accounts.eve, Id::U8(1), vec![]), Ok(()));
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.eve));
+        }
+
+        #[ink::test]
+        fn count_with_attribute_follows_overwrites_and_removals() {
+            let mut token = Token::new();
+            let (background, hat) = (b"background".to_vec(), b"hat".to_vec());
+            let (gold, blue) = (b"gold".to_vec(), b"blue".to_vec());
+            for id in 0..5 {
+                let color = if id < 3 { &gold } else { &blue };
+                assert!(token
+                    .metadata
+                    .set_attribute(Id::U8(id), background.clone(), color.clone())
+                    .is_ok());
+            }
+            // The same value under another key is counted separately.
+            assert!(token
+                .metadata
+                .set_indexed_attribute(Id::U8(0), hat.clone(), gold.clone())
+                .is_ok());
+            assert_eq!(
+                token.count_with_attribute(background.clone(), gold.clone()),
+                3
+            );
+            assert_eq!(
+                token.count_with_attribute(background.clone(), blue.clone()),
+                2
+            );
+            assert_eq!(token.count_with_attribute(hat.clone(), gold.clone()), 1);
+            assert_eq!(token.count_with_attribute(hat.clone(), blue.clone()), 0);
+
+            // Overwriting moves the token from the old value to the new one,
+            // writing the same value again changes nothing.
+            for _ in 0..2 {
+                assert!(token
+                    .metadata
+                    .set_attribute(Id::U8(0), background.clone(), blue.clone())
+                    .is_ok());
+            }
+            assert_eq!(
+                token.count_with_attribute(background.clone(), gold.clone()),
+                2
+            );
+            assert_eq!(
+                token.count_with_attribute(background.clone(), blue.clone()),
+                3
+            );
+
+            assert_eq!(
+                token
+                    .metadata
+                    .remove_attribute(Id::U8(3), background.clone()),
+                Some(blue.clone())
+            );
+            assert_eq!(
+                token
+                    .metadata
+                    .remove_attribute(Id::U8(3), background.clone()),
+                None
+            );
+            token.metadata.clear_indexed_attributes(&Id::U8(0));
+            assert_eq!(token.count_with_attribute(background.clone(), blue), 2);
+            assert_eq!(token.count_with_attribute(background, gold.clone()), 2);
+            assert_eq!(token.count_with_attribute(hat, gold), 0);
+        }
+
+        // Sets the value transferred with the next call, crediting it to the contract.
+        fn pay(value: Balance) {
+            let contract = callee::<E>();
+            let balance = get_account_balance::<E>(contract).unwrap_or_default();
+            set_account_balance::<E>(contract, balance + value);
+            set_value_transferred::<E>(value);
+        }
+
+        #[ink::test]
+        fn marketplace_sale_pays_seller_and_royalty() {
+            let accounts = default_accounts::<E>();
+            // The contract gets its own account, so that its payouts are not mixed up
+            // with the balances of the seller.
+            set_callee::<E>(AccountId::from([0xff; 32]));
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.set_royalty(Some((accounts.charlie, 500))), Ok(()));
+            assert_eq!(token.list(Id::U8(1), 1000), Ok(()));
+            let listing = marketplace::Listing {
+                seller: accounts.alice,
+                price: 1000,
+            };
+            assert_eq!(token.listing_of(Id::U8(1)), Some(listing));
+            assert_eq!(token.active_listings(0, 10), vec![(Id::U8(1), listing)]);
+            // The listed token is locked.
+            assert_eq!(
+                token.transfer(accounts.bob, Id::U8(1), vec![]),
+                Err(PSP34Error::Custom(String::from(
+                    "Token is listed for sale."
+                )))
+            );
+
+            set_caller::<E>(accounts.bob);
+            pay(999);
+            assert_eq!(
+                token.buy(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from(
+                    "Payment does not match the price."
+                )))
+            );
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.alice));
+
+            let balance = |account| get_account_balance::<E>(account).unwrap();
+            let (seller, receiver) = (balance(accounts.alice), balance(accounts.charlie));
+            pay(1000);
+            assert_eq!(token.buy(Id::U8(1)), Ok(()));
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.bob));
+            assert_eq!(balance(accounts.alice), seller + 950);
+            assert_eq!(balance(accounts.charlie), receiver + 50);
+            assert_eq!(token.listing_of(Id::U8(1)), None);
+            assert_eq!(token.active_listings(0, 10), vec![]);
+            // The buyer gets the token unlocked.
+            assert_eq!(token.transfer(accounts.eve, Id::U8(1), vec![]), Ok(()));
+        }
+
+        #[ink::test]
+        fn split_royalty_is_distributed_with_rounding_remainders() {
+            let accounts = default_accounts::<E>();
+            set_callee::<E>(AccountId::from([0xff; 32]));
+            let contract = callee::<E>();
+            let balance = |account| get_account_balance::<E>(account).unwrap_or_default();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            let recipients = vec![
+                (accounts.bob, 3333),
+                (accounts.charlie, 3333),
+                (accounts.django, 3334),
+            ];
+            let balances = || {
+                recipients
+                    .iter()
+                    .map(|(account, _)| balance(*account))
+                    .collect::<Vec<_>>()
+            };
+            let received = |before: &[Balance]| {
+                balances()
+                    .iter()
+                    .zip(before)
+                    .map(|(after, before)| after - before)
+                    .collect::<Vec<_>>()
+            };
+
+            // The shares have to add up to the whole royalty.
+            assert_eq!(
+                token.set_royalty_split(1000, vec![(accounts.bob, 5000)]),
+                Err(PSP34Error::Custom(String::from(
+                    "Royalty shares must add up to the whole royalty."
+                )))
+            );
+            assert_eq!(
+                token.set_royalty_split(1000, vec![]),
+                Err(PSP34Error::Custom(String::from(
+                    "Invalid number of royalty recipients."
+                )))
+            );
+            assert_eq!(token.set_royalty_split(1000, recipients.clone()), Ok(()));
+            assert_eq!(token.royalty_recipients(), recipients);
+            // Marketplaces pay the royalty to the contract.
+            assert_eq!(token.royalty_info(1000), Some((contract, 100)));
+
+            // A royalty paid outside of the contract is split right away,
+            // the remainder going to the first recipient.
+            let before = balances();
+            set_caller::<E>(accounts.eve);
+            pay(100);
+            assert_eq!(token.pay_royalty(Id::U8(1)), Ok(()));
+            assert_eq!(received(&before), vec![34, 33, 33]);
+            assert_eq!(token.undistributed_royalties(), 0);
+            assert_eq!(
+                token.pay_royalty(Id::U8(2)),
+                Err(PSP34Error::TokenNotExists)
+            );
+
+            // A sale retains the royalty until it is distributed.
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.list(Id::U8(1), 1010), Ok(()));
+            let (seller, before) = (balance(accounts.alice), balances());
+            set_caller::<E>(accounts.eve);
+            pay(1010);
+            assert_eq!(token.buy(Id::U8(1)), Ok(()));
+            assert_eq!(balance(accounts.alice), seller + 909);
+            assert_eq!(received(&before), vec![0, 0, 0]);
+            assert_eq!(token.undistributed_royalties(), 101);
+            assert_eq!(balance(contract), 101);
+
+            set_caller::<E>(accounts.alice);
+            assert_eq!(
+                token.set_royalty(None),
+                Err(PSP34Error::Custom(String::from(
+                    "Undistributed royalties have to be distributed first."
+                )))
+            );
+            // Anyone can distribute them.
+            set_caller::<E>(accounts.eve);
+            assert_eq!(token.distribute(), Ok(()));
+            assert_eq!(received(&before), vec![35, 33, 33]);
+            assert_eq!(token.undistributed_royalties(), 0);
+            assert_eq!(balance(contract), 0);
+
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.set_royalty(None), Ok(()));
+            assert_eq!(token.royalty_recipients(), vec![]);
+            assert_eq!(
+                token.distribute(),
+                Err(PSP34Error::Custom(String::from("Royalty is not split.")))
+            );
+        }
+
+        #[ink::test]
+        fn timelock_delays_royalty_and_contract_uri_changes() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            let royalty = timelock::Action::SetRoyalty(Some((accounts.bob, 500)));
+            let uri = timelock::Action::SetContractUri(String::from("ipfs://new"));
+            assert_eq!(token.timelock_delay(), None);
+            assert_eq!(
+                token.schedule(royalty.clone()),
+                Err(PSP34Error::Custom(String::from("Timelock is not enabled.")))
+            );
+            assert_eq!(token.set_timelock_delay(Some(1000)), Ok(()));
+            assert_eq!(token.timelock_delay(), Some(1000));
+
+            // The changes cannot be made immediately anymore.
+            let scheduled_only = Err(PSP34Error::Custom(String::from(
+                "Action has to be scheduled in the timelock.",
+            )));
+            assert_eq!(token.set_royalty(Some((accounts.bob, 500))), scheduled_only);
+            assert_eq!(
+                token.set_royalty_split(500, vec![(accounts.bob, 10_000)]),
+                scheduled_only
+            );
+            assert_eq!(
+                token.set_contract_uri(String::from("ipfs://new")),
+                scheduled_only
+            );
+            assert_eq!(token.set_timelock_delay(None), scheduled_only);
+
+            set_block_timestamp::<E>(5000);
+            assert_eq!(token.schedule(royalty.clone()), Ok(0));
+            assert_eq!(token.schedule(uri.clone()), Ok(1));
+            assert_eq!(
+                token.scheduled_actions(),
+                vec![
+                    timelock::ScheduledAction {
+                        id: 0,
+                        action: royalty,
+                        eta: 6000,
+                    },
+                    timelock::ScheduledAction {
+                        id: 1,
+                        action: uri,
+                        eta: 6000,
+                    },
+                ]
+            );
+
+            // An action cannot be executed before its delay has passed.
+            set_block_timestamp::<E>(5999);
+            assert_eq!(
+                token.execute(0),
+                Err(PSP34Error::Custom(String::from(
+                    "Scheduled action is not executable yet."
+                )))
+            );
+            assert_eq!(token.royalty_info(1000), None);
+            set_block_timestamp::<E>(6000);
+            assert_eq!(token.execute(0), Ok(()));
+            assert_eq!(token.royalty_info(1000), Some((accounts.bob, 50)));
+            let missing = Err(PSP34Error::Custom(String::from(
+                "There is no such scheduled action.",
+            )));
+            assert_eq!(token.execute(0), missing);
+
+            // A cancelled action is never executed.
+            assert_eq!(token.cancel(1), Ok(()));
+            assert_eq!(token.execute(1), missing);
+            assert_eq!(token.cancel(1), missing);
+            assert_eq!(token.contract_uri(), None);
+            assert_eq!(token.scheduled_actions(), vec![]);
+
+            // Disabling the timelock is delayed as well.
+            assert_eq!(token.schedule(timelock::Action::SetDelay(None)), Ok(2));
+            set_block_timestamp::<E>(7000);
+            assert_eq!(token.execute(2), Ok(()));
+            assert_eq!(token.timelock_delay(), None);
+            assert_eq!(token.set_contract_uri(String::from("ipfs://new")), Ok(()));
+            assert_eq!(token.contract_uri(), Some(String::from("ipfs://new")));
+        }
+
+        #[ink::test]
+        fn marketplace_delisted_and_stale_listings_cannot_be_bought() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.mint(Id::U8(2)), Ok(()));
+            assert_eq!(token.list(Id::U8(1), 10), Ok(()));
+            assert_eq!(token.list(Id::U8(2), 20), Ok(()));
+
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.delist(Id::U8(1)), Err(PSP34Error::NotApproved));
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.delist(Id::U8(1)), Ok(()));
+            assert_eq!(token.listing_of(Id::U8(1)), None);
+            assert_eq!(token.transfer(accounts.charlie, Id::U8(1), vec![]), Ok(()));
+
+            // A listing is invalidated when its token changes owner without a sale.
+            assert!(token
+                .data
+                .transfer_unchecked(accounts.alice, accounts.eve, Id::U8(2))
+                .is_ok());
+            assert_eq!(token.listing_of(Id::U8(2)), None);
+            assert_eq!(token.active_listings(0, 10), vec![]);
+            set_caller::<E>(accounts.bob);
+            pay(20);
+            assert_eq!(
+                token.buy(Id::U8(2)),
+                Err(PSP34Error::Custom(String::from("Token is not listed.")))
+            );
+            set_caller::<E>(accounts.eve);
+            assert_eq!(token.transfer(accounts.bob, Id::U8(2), vec![]), Ok(()));
+
+            // The stale listing is still counted until anyone removes it.
+            assert_eq!(token.listed_count(), 1);
+            assert_eq!(
+                token.remove_stale_listing(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from("Token is not listed.")))
+            );
+            assert_eq!(token.remove_stale_listing(Id::U8(2)), Ok(()));
+            assert_eq!(token.listed_count(), 0);
+        }
+
+        #[ink::test]
+        fn marketplace_listings_are_paged_by_index() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            for id in 0..5 {
+                assert_eq!(token.mint(Id::U8(id)), Ok(()));
+                assert_eq!(token.list(Id::U8(id), 10), Ok(()));
+            }
+            let listing = marketplace::Listing {
+                seller: accounts.alice,
+                price: 10,
+            };
+            let ids = |listings: Vec<(Id, marketplace::Listing)>| {
+                assert!(listings.iter().all(|(_, l)| *l == listing));
+                listings.into_iter().map(|(id, _)| id).collect::<Vec<_>>()
+            };
+            assert_eq!(token.listed_count(), 5);
+            assert_eq!(ids(token.active_listings(1, 2)), vec![Id::U8(1), Id::U8(2)]);
+            assert_eq!(ids(token.active_listings(4, 10)), vec![Id::U8(4)]);
+            assert_eq!(token.active_listings(5, 10), vec![]);
+            assert_eq!(token.active_listings(u32::MAX, u32::MAX), vec![]);
+
+            // Delisting moves the last listed token to the freed index.
+            assert_eq!(token.delist(Id::U8(1)), Ok(()));
+            assert_eq!(token.listed_count(), 4);
+            assert_eq!(
+                ids(token.active_listings(0, 4)),
+                vec![Id::U8(0), Id::U8(4), Id::U8(2), Id::U8(3)]
+            );
+            // A page skips the stale listings it covers.
+            assert!(token
+                .data
+                .transfer_unchecked(accounts.alice, accounts.bob, Id::U8(4))
+                .is_ok());
+            assert_eq!(
+                token.remove_stale_listing(Id::U8(0)),
+                Err(PSP34Error::Custom(String::from("Listing is still valid.")))
+            );
+            assert_eq!(ids(token.active_listings(0, 2)), vec![Id::U8(0)]);
+            // A relisted token keeps its index.
+            assert_eq!(token.list(Id::U8(2), 20), Ok(()));
+            assert_eq!(token.listed_count(), 4);
+        }
+
+        #[ink::test]
+        fn collection_id_is_set_once() {
+            let mut token = Token::new();
+            let derived = token.collection_id();
+            assert_eq!(token.set_collection_id(Id::U8(7)), Ok(()));
+            assert_eq!(token.collection_id(), Id::U8(7));
+            assert!(token.set_collection_id(derived).is_err());
+            assert_eq!(token.collection_id(), Id::U8(7));
+        }
+
+        #[ink::test]
+        fn offers_are_escrowed_until_cancelled_or_accepted() {
+            let accounts = default_accounts::<E>();
+            set_callee::<E>(AccountId::from([0xff; 32]));
+            let contract = callee::<E>();
+            let balance = |account| get_account_balance::<E>(account).unwrap_or_default();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.set_royalty(Some((accounts.charlie, 1000))), Ok(()));
+            assert_eq!(token.list(Id::U8(1), 5000), Ok(()));
+            // A rejected offer is reverted together with its value.
+            set_value_transferred::<E>(100);
+            assert_eq!(
+                token.make_offer(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from(
+                    "Owner cannot make an offer for their own token."
+                )))
+            );
+
+            // Bob outbids Eve by raising his offer, both stay escrowed.
+            set_caller::<E>(accounts.bob);
+            pay(100);
+            assert_eq!(token.make_offer(Id::U8(1)), Ok(()));
+            set_caller::<E>(accounts.eve);
+            pay(150);
+            assert_eq!(token.make_offer(Id::U8(1)), Ok(()));
+            set_caller::<E>(accounts.bob);
+            pay(100);
+            assert_eq!(token.make_offer(Id::U8(1)), Ok(()));
+            assert_eq!(
+                token.offers_for(Id::U8(1)),
+                vec![(accounts.bob, 200), (accounts.eve, 150)]
+            );
+            assert_eq!(token.total_escrowed(), 350);
+            assert_eq!(balance(contract), 350);
+
+            // Offers survive transfers and can be accepted by the new owner.
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.delist(Id::U8(1)), Ok(()));
+            assert_eq!(token.transfer(accounts.django, Id::U8(1), vec![]), Ok(()));
+            assert_eq!(token.offers_for(Id::U8(1)).len(), 2);
+
+            // Eve cancels and gets her escrow back, but only once.
+            set_caller::<E>(accounts.eve);
+            let eve = balance(accounts.eve);
+            assert_eq!(token.cancel_offer(Id::U8(1)), Ok(()));
+            assert_eq!(balance(accounts.eve), eve + 150);
+            assert_eq!(
+                token.cancel_offer(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from("There is no such offer.")))
+            );
+            assert_eq!(token.total_escrowed(), 200);
+            assert_eq!(balance(contract), 200);
+
+            set_caller::<E>(accounts.alice);
+            assert_eq!(
+                token.accept_offer(Id::U8(1), accounts.bob),
+                Err(PSP34Error::NotApproved)
+            );
+            set_caller::<E>(accounts.django);
+            assert_eq!(
+                token.accept_offer(Id::U8(1), accounts.eve),
+                Err(PSP34Error::Custom(String::from("There is no such offer.")))
+            );
+            let (seller, receiver) = (balance(accounts.django), balance(accounts.charlie));
+            assert_eq!(token.accept_offer(Id::U8(1), accounts.bob), Ok(()));
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.bob));
+            assert_eq!(balance(accounts.django), seller + 180);
+            assert_eq!(balance(accounts.charlie), receiver + 20);
+            assert_eq!(token.offers_for(Id::U8(1)), vec![]);
+            assert_eq!(token.total_escrowed(), 0);
+            assert_eq!(balance(contract), 0);
+        }
+
+        #[ink::test]
+        fn offers_per_token_are_bounded() {
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            for bidder in 0..marketplace::MAX_OFFERS_PER_TOKEN as u8 {
+                set_caller::<E>(AccountId::from([0x10 + bidder; 32]));
+                pay(1);
+                assert_eq!(token.make_offer(Id::U8(1)), Ok(()));
+            }
+            set_caller::<E>(AccountId::from([0xee; 32]));
+            pay(1);
+            assert_eq!(
+                token.make_offer(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from(
+                    "Too many offers for the token."
+                )))
+            );
+            // Existing bidders can still raise their offers.
+            set_caller::<E>(AccountId::from([0x10; 32]));
+            pay(1);
+            assert_eq!(token.make_offer(Id::U8(1)), Ok(()));
+            assert_eq!(
+                token.offers_for(Id::U8(1))[0],
+                (AccountId::from([0x10; 32]), 2)
+            );
+            pay(0);
+            assert_eq!(
+                token.make_offer(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from(
+                    "Offer must carry a value."
+                )))
+            );
+        }
+
+        #[ink::test]
+        fn burn_for_refund_pays_out_of_the_free_balance() {
+            let accounts = default_accounts::<E>();
+            set_callee::<E>(AccountId::from([0xff; 32]));
+            let contract = callee::<E>();
+            let balance = |account| get_account_balance::<E>(account).unwrap_or_default();
+            set_account_balance::<E>(contract, 100);
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.mint(Id::U8(2)), Ok(()));
+            assert_eq!(token.set_refund_amount(60), Ok(()));
+            assert_eq!(token.refund_amount(), 60);
+
+            set_caller::<E>(accounts.bob);
+            assert_eq!(
+                token.burn_for_refund(Id::U8(1)),
+                Err(PSP34Error::NotApproved)
+            );
+
+            set_caller::<E>(accounts.alice);
+            let alice = balance(accounts.alice);
+            assert_eq!(token.burn_for_refund(Id::U8(1)), Ok(()));
+            assert_eq!(token.owner_of(Id::U8(1)), None);
+            assert_eq!(balance(accounts.alice), alice + 60);
+            assert_eq!(balance(contract), 40);
+
+            // The value escrowed by offers is not available for refunds.
+            set_caller::<E>(accounts.bob);
+            pay(30);
+            assert_eq!(token.make_offer(Id::U8(2)), Ok(()));
+            set_caller::<E>(accounts.alice);
+            assert_eq!(
+                token.burn_for_refund(Id::U8(2)),
+                Err(PSP34Error::Custom(String::from(
+                    "Insufficient balance for the refund."
+                )))
+            );
+            assert_eq!(token.owner_of(Id::U8(2)), Some(accounts.alice));
+            set_account_balance::<E>(contract, balance(contract) + 20);
+            assert_eq!(token.burn_for_refund(Id::U8(2)), Ok(()));
+            assert_eq!(balance(contract), 30);
+        }
+
+        #[ink::test]
+        fn auction_takes_increasing_bids_and_settles_once() {
+            let accounts = default_accounts::<E>();
+            set_callee::<E>(AccountId::from([0xff; 32]));
+            let contract = callee::<E>();
+            let balance = |account| get_account_balance::<E>(account).unwrap_or_default();
+            let minute = 60 * 1000;
+            set_block_timestamp::<E>(0);
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.set_royalty(Some((accounts.charlie, 1000))), Ok(()));
+            assert_eq!(
+                token.create_auction(Id::U8(1), 100, 10, 60 * minute),
+                Ok(())
+            );
+            // The auctioned token is locked.
+            assert_eq!(
+                token.transfer(accounts.bob, Id::U8(1), vec![]),
+                Err(PSP34Error::Custom(String::from("Token is auctioned.")))
+            );
+            assert_eq!(
+                token.list(Id::U8(1), 100),
+                Err(PSP34Error::Custom(String::from("Token is auctioned.")))
+            );
+            assert!(token.create_auction(Id::U8(1), 1, 1, 120 * minute).is_err());
+
+            set_caller::<E>(accounts.bob);
+            set_value_transferred::<E>(99);
+            assert_eq!(
+                token.bid(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from("Bid is too low.")))
+            );
+            let (bob, eve) = (balance(accounts.bob), balance(accounts.eve));
+            pay(100);
+            assert_eq!(token.bid(Id::U8(1)), Ok(()));
+            set_caller::<E>(accounts.eve);
+            set_value_transferred::<E>(109);
+            assert!(token.bid(Id::U8(1)).is_err());
+            pay(110);
+            assert_eq!(token.bid(Id::U8(1)), Ok(()));
+            // The outbid bidder is refunded, only the highest bid stays escrowed.
+            assert_eq!(balance(accounts.bob), bob + 100);
+            assert_eq!(token.total_escrowed(), 110);
+            assert_eq!(balance(contract), 110);
+            let auction = token.auction_of(Id::U8(1)).unwrap();
+            assert_eq!(auction.highest_bid, Some((accounts.eve, 110)));
+            assert_eq!(auction.end_time, 60 * minute);
+
+            // A bid in the final minutes extends the auction.
+            set_block_timestamp::<E>(58 * minute);
+            set_caller::<E>(accounts.bob);
+            pay(200);
+            assert_eq!(token.bid(Id::U8(1)), Ok(()));
+            assert_eq!(balance(accounts.eve), eve + 110);
+            let end_time = 58 * minute + marketplace::ANTI_SNIPING_WINDOW;
+            assert_eq!(token.auction_of(Id::U8(1)).unwrap().end_time, end_time);
+
+            set_block_timestamp::<E>(60 * minute);
+            assert_eq!(
+                token.settle(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from("Auction has not ended.")))
+            );
+            set_block_timestamp::<E>(end_time);
+            set_caller::<E>(accounts.eve);
+            set_value_transferred::<E>(300);
+            assert_eq!(
+                token.bid(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from("Auction has ended.")))
+            );
+
+            // Anyone can settle, but only once.
+            let (seller, receiver) = (balance(accounts.alice), balance(accounts.charlie));
+            assert_eq!(token.settle(Id::U8(1)), Ok(()));
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.bob));
+            assert_eq!(balance(accounts.alice), seller + 180);
+            assert_eq!(balance(accounts.charlie), receiver + 20);
+            assert_eq!(token.total_escrowed(), 0);
+            assert_eq!(balance(contract), 0);
+            assert_eq!(token.auction_of(Id::U8(1)), None);
+            assert_eq!(
+                token.settle(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from("Token is not auctioned.")))
+            );
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.transfer(accounts.eve, Id::U8(1), vec![]), Ok(()));
+        }
+
+        #[ink::test]
+        fn auction_without_bids_returns_the_token() {
+            let accounts = default_accounts::<E>();
+            set_block_timestamp::<E>(1000);
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(
+                token.create_auction(Id::U8(1), 100, 10, 1000),
+                Err(PSP34Error::Custom(String::from(
+                    "Auction must end in the future."
+                )))
+            );
+            set_caller::<E>(accounts.bob);
+            assert_eq!(
+                token.create_auction(Id::U8(1), 100, 10, 2000),
+                Err(PSP34Error::NotApproved)
+            );
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.create_auction(Id::U8(1), 100, 10, 2000), Ok(()));
+            set_value_transferred::<E>(100);
+            assert_eq!(
+                token.bid(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from(
+                    "Seller cannot bid on their own token."
+                )))
+            );
+
+            set_block_timestamp::<E>(2000);
+            set_caller::<E>(accounts.eve);
+            assert_eq!(token.settle(Id::U8(1)), Ok(()));
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.alice));
+            assert_eq!(token.auction_of(Id::U8(1)), None);
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.transfer(accounts.bob, Id::U8(1), vec![]), Ok(()));
+        }
+
+        #[ink::test]
+        fn collateral_is_released_or_seized_only_by_the_lender() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            assert_eq!(token.mint(Id::U8(1)), Ok(()));
+            assert_eq!(token.mint(Id::U8(2)), Ok(()));
+            let terms = b"repay 100 by block 1000".to_vec();
+            assert_eq!(
+                token.lock_as_collateral(Id::U8(1), accounts.bob, terms.clone()),
+                Ok(())
+            );
+            assert_eq!(
+                token.collateral_status(Id::U8(1)),
+                Some(collateral::Collateral {
+                    owner: accounts.alice,
+                    lender: accounts.bob,
+                    release_conditions: terms,
+                })
+            );
+            assert_eq!(token.collateral_status(Id::U8(2)), None);
+
+            // The owner can do nothing with the token until it is released.
+            let locked = || PSP34Error::Custom(String::from("Token is locked as collateral."));
+            assert_eq!(
+                token.lock_as_collateral(Id::U8(1), accounts.charlie, vec![]),
+                Err(locked())
+            );
+            assert_eq!(
+                token.transfer(accounts.charlie, Id::U8(1), vec![]),
+                Err(locked())
+            );
+            assert_eq!(token.list(Id::U8(1), 10), Err(locked()));
+            assert_eq!(token.release(Id::U8(1)), Err(PSP34Error::NotApproved));
+            assert_eq!(token.seize(Id::U8(1)), Err(PSP34Error::NotApproved));
+            set_caller::<E>(accounts.charlie);
+            assert_eq!(token.seize(Id::U8(1)), Err(PSP34Error::NotApproved));
+
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.release(Id::U8(1)), Ok(()));
+            assert_eq!(token.collateral_status(Id::U8(1)), None);
+            assert_eq!(
+                token.seize(Id::U8(1)),
+                Err(PSP34Error::Custom(String::from(
+                    "Token is not locked as collateral."
+                )))
+            );
+            set_caller::<E>(accounts.alice);
+            assert_eq!(token.transfer(accounts.charlie, Id::U8(1), vec![]), Ok(()));
+
+            assert_eq!(
+                token.lock_as_collateral(Id::U8(2), accounts.bob, vec![]),
+                Ok(())
+            );
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.seize(Id::U8(2)), Ok(()));
+            assert_eq!(token.owner_of(Id::U8(2)), Some(accounts.bob));
+            assert_eq!(token.collateral_status(Id::U8(2)), None);
+            assert_eq!(token.transfer(accounts.eve, Id::U8(2), vec![]), Ok(()));
+        }
+
+        #[cfg(feature = "enumerable")]
+        #[ink::test]
+        fn owned_tokens_are_filtered_by_attribute() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            let (background, hat) = (b"background".to_vec(), b"hat".to_vec());
+            let (gold, blue) = (b"gold".to_vec(), b"blue".to_vec());
+            for (id, key, value) in [
+                (1, &background, &gold),
+                (2, &background, &blue),
+                (3, &hat, &gold),
+                (4, &background, &gold),
+                (5, &background, &gold),
+            ] {
+                assert_eq!(token.mint(Id::U8(id)), Ok(()));
+                assert!(token
+                    .metadata
+                    .set_attribute(Id::U8(id), key.clone(), value.clone())
+                    .is_ok());
+            }
+            assert_eq!(token.mint(Id::U8(6)), Ok(()));
+            assert_eq!(token.transfer(accounts.bob, Id::U8(5), vec![]), Ok(()));
+
+            let owned_with = |owner, key: &Vec<u8>, value: &Vec<u8>| {
+                let mut ids = token.metadata.owned_tokens_with_attribute(
+                    &token.data,
+                    owner,
+                    key.clone(),
+                    value.clone(),
+                );
+                ids.sort();
+                ids
+            };
+            assert_eq!(
+                owned_with(accounts.alice, &background, &gold),
+                vec![Id::U8(1), Id::U8(4)]
+            );
+            assert_eq!(
+                owned_with(accounts.alice, &background, &blue),
+                vec![Id::U8(2)]
+            );
+            assert_eq!(owned_with(accounts.alice, &hat, &gold), vec![Id::U8(3)]);
+            assert_eq!(owned_with(accounts.alice, &hat, &blue), vec![]);
+            assert_eq!(
+                owned_with(accounts.bob, &background, &gold),
+                vec![Id::U8(5)]
+            );
+            assert_eq!(owned_with(accounts.charlie, &background, &gold), vec![]);
+        }
+
+        #[ink::test]
+        fn bundle_is_traded_as_one_token_and_unbundled_by_its_owner() {
+            let accounts = default_accounts::<E>();
+            set_callee::<E>(AccountId::from([0xff; 32]));
+            let contract = callee::<E>();
+            let mut token = Token::new();
+            for id in 1..=4 {
+                assert_eq!(token.mint(Id::U8(id)), Ok(()));
+            }
+            assert_eq!(token.transfer(accounts.bob, Id::U8(4), vec![]), Ok(()));
+            assert_eq!(
+                token.create_bundle(vec![Id::U8(1), Id::U8(4)]),
+                Err(PSP34Error::NotApproved)
+            );
+            assert_eq!(
+                token.create_bundle(vec![Id::U8(1), Id::U8(5)]),
+                Err(PSP34Error::TokenNotExists)
+            );
+            assert!(token.create_bundle(vec![]).is_err());
+            assert!(token.create_bundle(vec![Id::U8(1), Id::U8(1)]).is_err());
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.alice));
+
+            let bundle = token.create_bundle(vec![Id::U8(1), Id::U8(2)]).unwrap();
+            assert_eq!(token.owner_of(bundle.clone()), Some(accounts.alice));
+            assert_eq!(
+                token.bundle_contents(bundle.clone()),
+                Some(vec![Id::U8(1), Id::U8(2)])
+            );
+            assert_eq!(token.bundle_contents(Id::U8(3)), None);
+            assert_eq!(token.balance_of(accounts.alice), 2);
+
+            // The components stay in custody until unbundled.
+            assert_eq!(token.owner_of(Id::U8(1)), Some(contract));
+            assert_eq!(
+                token.transfer(accounts.bob, Id::U8(1), vec![]),
+                Err(PSP34Error::NotApproved)
+            );
+            assert_eq!(token.list(Id::U8(1), 10), Err(PSP34Error::NotApproved));
+            set_caller::<E>(contract);
+            assert_eq!(
+                token.transfer(accounts.bob, Id::U8(1), vec![]),
+                Err(PSP34Error::Custom(String::from("Token is bundled.")))
+            );
+            set_caller::<E>(accounts.alice);
+            assert_eq!(
+                token.rescue_token(Id::U8(1), accounts.alice),
+                Err(PSP34Error::Custom(String::from("Token is bundled.")))
+            );
+            assert_eq!(token.owner_of(Id::U8(1)), Some(contract));
+            assert_eq!(
+                token.create_bundle(vec![bundle.clone(), Id::U8(3)]),
+                Err(PSP34Error::Custom(String::from(
+                    "Bundles cannot be nested."
+                )))
+            );
+            assert_eq!(
+                token.burn(accounts.alice, bundle.clone()),
+                Err(PSP34Error::Custom(String::from(
+                    "Bundles can only be burned by unbundling."
+                )))
+            );
+
+            assert_eq!(token.transfer(accounts.bob, bundle.clone(), vec![]), Ok(()));
+            assert_eq!(token.unbundle(bundle.clone()), Err(PSP34Error::NotApproved));
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.unbundle(bundle.clone()), Ok(()));
+            assert_eq!(token.owner_of(bundle.clone()), None);
+            assert_eq!(token.bundle_contents(bundle.clone()), None);
+            assert_eq!(token.owner_of(Id::U8(1)), Some(accounts.bob));
+            assert_eq!(token.owner_of(Id::U8(2)), Some(accounts.bob));
+            assert_eq!(token.balance_of(accounts.bob), 3);
+            assert_eq!(token.balance_of(contract), 0);
+            assert_eq!(
+                token.unbundle(bundle),
+                Err(PSP34Error::Custom(String::from("Token is not a bundle.")))
+            );
+            assert_eq!(token.transfer(accounts.eve, Id::U8(1), vec![]), Ok(()));
+        }
+
+        #[ink::test]
+        fn crafting_burns_matching_inputs_only() {
+            let accounts = default_accounts::<E>();
+            let mut token = Token::new();
+            let kind = b"kind".to_vec();
+            for (id, value) in [(1, &b"sword"[..]), (2, b"gem"), (3, b"sword"), (4, b"gem")] {
+                assert_eq!(token.mint(Id::U8(id)), Ok(()));
+                assert!(token
+                    .metadata
+                    .set_attribute(Id::U8(id), kind.clone(), value.to_vec())
+                    .is_ok());
+            }
+            assert_eq!(token.transfer(accounts.bob, Id::U8(4), vec![]), Ok(()));
+            let input = |value: &[u8]| crafting::Input::Attribute {
+                key: kind.clone(),
+                value: value.to_vec(),
+            };
+            let recipe = crafting::Recipe {
+                inputs: vec![input(b"sword"), input(b"gem")],
+                output: vec![(kind.clone(), b"enchanted sword".to_vec())],
+            };
+            let recipe_id = token.register_recipe(recipe.clone()).unwrap();
+            assert_eq!(token.recipe(recipe_id), Some(recipe));
+            assert!(token
+                .register_recipe(crafting::Recipe {
+                    inputs: vec![],
+                    output: vec![],
+                })
+                .is_err());
+
+            let mismatch = || PSP34Error::Custom(String::from("Inputs do not match the recipe."));
+            assert_eq!(
+                token.craft(recipe_id, vec![Id::U8(2), Id::U8(1)]),
+                Err(mismatch())
+            );
+            assert_eq!(
+                token.craft(recipe_id, vec![Id::U8(1), Id::U8(3)]),
+                Err(mismatch())
+            );
+            assert_eq!(token.craft(recipe_id, vec![Id::U8(1)]), Err(mismatch()));
+            assert_eq!(
+                token.craft(recipe_id, vec![Id::U8(1), Id::U8(4)]),
+                Err(PSP34Error::NotApproved)
+            );
+            assert!(token
+                .craft(recipe_id + 1, vec![Id::U8(1), Id::U8(2)])
+                .is_err());
+            // Nothing is consumed by the failed attempts.
+            assert_eq!(token.total_supply(), 4);
+            assert_eq!(token.balance_of(accounts.alice), 3);
+
+            let output = token.craft(recipe_id, vec![Id::U8(1), Id::U8(2)]).unwrap();
+            assert_eq!(token.owner_of(output.clone()), Some(accounts.alice));
+            assert_eq!(
+                token.get_attribute(output, kind.clone()),
+                Some(b"enchanted sword".to_vec())
+            );
+            assert_eq!(token.owner_of(Id::U8(1)), None);
+            assert_eq!(token.owner_of(Id::U8(2)), None);
+            assert_eq!(token.total_supply(), 3);
+
+            // A recipe can also require specific tokens.
+            let recipe_id = token
+                .register_recipe(crafting::Recipe {
+                    inputs: vec![crafting::Input::Token(Id::U8(3))],
+                    output: vec![],
+                })
+                .unwrap();
+            set_caller::<E>(accounts.bob);
+            assert_eq!(token.craft(recipe_id, vec![Id::U8(4)]), Err(mismatch()));
+            assert_eq!(
+                token.craft(recipe_id, vec![Id::U8(3)]),
+                Err(PSP34Error::NotApproved)
+            );
+            set_caller::<E>(accounts.alice);
+            assert!(token.craft(recipe_id, vec![Id::U8(3)]).is_ok());
+            assert_eq!(token.owner_of(Id::U8(3)), None);
+        }
     }
 }
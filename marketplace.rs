@@ -0,0 +1,714 @@
+use crate::{
+    data::{EventList, Id, PSP34Data, PSP34Event, TokenHold},
+    PSP34Error,
+};
+use ink::{
+    prelude::{string::String, vec::Vec},
+    primitives::AccountId,
+    storage::{Lazy, Mapping},
+};
+
+/// Maximal royalty, in basis points of the price.
+pub const MAX_ROYALTY_BPS: u16 = 10_000;
+
+/// Sum of the shares of the royalty recipients, in basis points of the royalty.
+pub const ROYALTY_SHARES_TOTAL: u16 = 10_000;
+
+/// Maximal number of recipients a royalty can be split between.
+pub const MAX_ROYALTY_RECIPIENTS: usize = 16;
+
+/// Maximal number of concurrent offers for a single token.
+pub const MAX_OFFERS_PER_TOKEN: usize = 16;
+
+/// Length of the final period of an auction, in milliseconds, in which a bid extends
+/// the auction to end this long after the bid, so that late bids can be answered.
+pub const ANTI_SNIPING_WINDOW: u64 = 5 * 60 * 1000;
+
+/// A fixed-price sale of a token, valid while `seller` owns it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct Listing {
+    pub seller: AccountId,
+    pub price: u128,
+}
+
+/// An English auction of a token, open for bids until `end_time`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, scale::Encode, scale::Decode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct Auction {
+    pub seller: AccountId,
+    pub start_price: u128,
+    pub min_increment: u128,
+    /// Timestamp, in milliseconds, from which no bids are accepted.
+    pub end_time: u64,
+    /// The highest bidder with their bid, escrowed in the contract.
+    pub highest_bid: Option<(AccountId, u128)>,
+}
+
+/// The payouts of a completed sale, to be paid out of the transferred or escrowed value.
+/// A royalty split between recipients is not paid out, but `retained` in the contract
+/// until it is distributed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Payouts {
+    pub seller: (AccountId, u128),
+    pub royalty: Option<(AccountId, u128)>,
+    pub retained: u128,
+}
+
+impl Payouts {
+    /// Returns the accounts to pay with their non-zero amounts.
+    pub fn payments(&self) -> impl Iterator<Item = (AccountId, u128)> {
+        [Some(self.seller), self.royalty]
+            .into_iter()
+            .flatten()
+            .filter(|(_, amount)| *amount > 0)
+    }
+}
+
+/// Fixed-price listings of the tokens of a `PSP34Data`, paid with native tokens.
+///
+/// A listed token is held with `TokenHold::Listed`, so its owner cannot transfer or burn
+/// it until it is delisted or sold. A listing whose token changed owner anyway, e.g. with
+/// `PSP34Data::transfer_unchecked`, is no longer valid and is not reported by the queries.
+/// Anyone can remove such a stale listing with `remove_stale_listing`.
+///
+/// The listed tokens are kept in index-keyed mappings rather than a single list, so the
+/// number of listings is not limited by the size of a storage cell.
+///
+/// Buyers can also make offers for any token, escrowing the offered value in the contract.
+/// Offers belong to the token rather than to its owner, so they survive transfers and
+/// can be accepted by whoever owns the token.
+///
+/// Auctioned tokens are held with `TokenHold::Auctioned` until the auction is settled,
+/// which anyone can do once it has ended. The highest bid is escrowed like an offer.
+///
+/// A royalty can be split between several recipients by fixed shares. Its receiver is
+/// then the contract itself, which keeps the royalties of sales and the ones paid with
+/// `pay_royalty` as undistributed until `distribute` splits them.
+#[ink::storage_item]
+#[derive(Default, Debug)]
+pub struct Data {
+    listings: Mapping<Id, Listing>,
+    listed: Mapping<u32, Id>,
+    listed_index: Mapping<Id, u32>,
+    listed_count: Lazy<u32>,
+    royalty: Lazy<Option<(AccountId, u16)>>,
+    #[allow(clippy::type_complexity)]
+    offers: Mapping<Id, Vec<(AccountId, u128)>>,
+    escrowed: Lazy<u128>,
+    auctions: Mapping<Id, Auction>,
+    royalty_recipients: Lazy<Vec<(AccountId, u16)>>,
+    undistributed: Lazy<u128>,
+}
+
+impl Data {
+    /// Returns the valid listing of token `id`, if there is one.
+    pub fn listing_of(&self, token: &PSP34Data, id: &Id) -> Option<Listing> {
+        let id = &token.normalize_id(id.clone());
+        self.listings.get(id).filter(|listing| {
+            token.owner_of(id) == Some(listing.seller)
+                && token.hold_of(id) == Some(TokenHold::Listed)
+        })
+    }
+
+    /// Returns the valid listings among the `limit` listed tokens starting at index `offset`.
+    /// Stale listings are skipped, so a page can hold fewer than `limit` of them.
+    pub fn active_listings(
+        &self,
+        token: &PSP34Data,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<(Id, Listing)> {
+        let end = offset.saturating_add(limit).min(self.listed_count());
+        (offset..end)
+            .filter_map(|index| self.listed.get(index))
+            .filter_map(|id| self.listing_of(token, &id).map(|listing| (id, listing)))
+            .collect()
+    }
+
+    /// Returns the number of listed tokens, including stale listings, to page through
+    /// `active_listings`.
+    pub fn listed_count(&self) -> u32 {
+        self.listed_count.get_or_default()
+    }
+
+    /// Removes the listing of token `id` if it is no longer valid, which anyone can do.
+    pub fn remove_stale_listing(
+        &mut self,
+        token: &PSP34Data,
+        id: Id,
+    ) -> Result<EventList, PSP34Error> {
+        let id = token.normalize_id(id);
+        let listing = self
+            .listings
+            .get(&id)
+            .ok_or(PSP34Error::Custom(String::from("Token is not listed.")))?;
+        if self.listing_of(token, &id).is_some() {
+            return Err(PSP34Error::Custom(String::from("Listing is still valid.")));
+        }
+        self.remove_listing(&id);
+        Ok(EventList::from(PSP34Event::Delisted {
+            id,
+            seller: listing.seller,
+        }))
+    }
+
+    /// Returns the receiver of royalties and their share of each sale in basis points.
+    pub fn royalty(&self) -> Option<(AccountId, u16)> {
+        self.royalty.get_or_default()
+    }
+
+    /// Sets the receiver of royalties and their share of each sale in basis points,
+    /// or removes the royalty if `royalty` is `None`. Replaces a split royalty, which
+    /// has to be distributed first.
+    /// The caller is responsible for checking that the admin conducts it.
+    pub fn set_royalty(&mut self, royalty: Option<(AccountId, u16)>) -> Result<(), PSP34Error> {
+        if royalty.is_some_and(|(_, bps)| bps > MAX_ROYALTY_BPS) {
+            return Err(PSP34Error::Custom(String::from(
+                "Royalty cannot exceed the price.",
+            )));
+        }
+        self.ensure_distributed()?;
+        self.royalty.set(&royalty);
+        self.royalty_recipients.set(&Vec::new());
+        Ok(())
+    }
+
+    /// Returns the recipients a royalty is split between with their shares in basis
+    /// points of the royalty, empty if it is not split.
+    pub fn royalty_recipients(&self) -> Vec<(AccountId, u16)> {
+        self.royalty_recipients.get_or_default()
+    }
+
+    /// Sets a royalty of `bps` basis points of each sale, received by `contract` and split
+    /// between the `recipients` by their shares, which must add up to `ROYALTY_SHARES_TOTAL`.
+    /// Royalties received under the previous split have to be distributed first.
+    /// The caller is responsible for checking that the admin conducts it.
+    pub fn set_royalty_split(
+        &mut self,
+        contract: AccountId,
+        bps: u16,
+        recipients: Vec<(AccountId, u16)>,
+    ) -> Result<(), PSP34Error> {
+        if recipients.is_empty() || recipients.len() > MAX_ROYALTY_RECIPIENTS {
+            return Err(PSP34Error::Custom(String::from(
+                "Invalid number of royalty recipients.",
+            )));
+        }
+        let total: u32 = recipients.iter().map(|(_, share)| *share as u32).sum();
+        if total != ROYALTY_SHARES_TOTAL as u32 {
+            return Err(PSP34Error::Custom(String::from(
+                "Royalty shares must add up to the whole royalty.",
+            )));
+        }
+        self.set_royalty(Some((contract, bps)))?;
+        self.royalty_recipients.set(&recipients);
+        Ok(())
+    }
+
+    /// Returns the receiver and the amount of the royalty of a sale at `price`,
+    /// if there is a royalty. A split royalty is received by the contract.
+    pub fn royalty_info(&self, price: u128) -> Option<(AccountId, u128)> {
+        self.royalty()
+            .map(|(receiver, bps)| (receiver, share_of(price, bps)))
+    }
+
+    /// Returns the royalties received by the contract and not distributed yet.
+    pub fn undistributed(&self) -> u128 {
+        self.undistributed.get_or_default()
+    }
+
+    /// Splits `amount` paid as a royalty between the recipients, the remainder of the
+    /// rounding going to the first one. Returns the payments to make.
+    pub fn pay_royalty(&self, amount: u128) -> Result<Vec<(AccountId, u128)>, PSP34Error> {
+        let recipients = self.royalty_recipients();
+        if recipients.is_empty() {
+            return Err(PSP34Error::Custom(String::from("Royalty is not split.")));
+        }
+        Ok(split(amount, &recipients))
+    }
+
+    /// Splits the undistributed royalties between the recipients like `pay_royalty`,
+    /// which anyone can do. Returns the payments to make.
+    pub fn distribute(&mut self) -> Result<Vec<(AccountId, u128)>, PSP34Error> {
+        let payments = self.pay_royalty(self.undistributed())?;
+        self.undistributed.set(&0);
+        Ok(payments)
+    }
+
+    /// Lists token `id` of `caller` for sale at `price`, or changes the price
+    /// of its listing.
+    pub fn list(
+        &mut self,
+        token: &mut PSP34Data,
+        caller: AccountId,
+        id: Id,
+        price: u128,
+    ) -> Result<EventList, PSP34Error> {
+        let id = token.normalize_id(id);
+        if token.owner_of(&id).ok_or(PSP34Error::TokenNotExists)? != caller {
+            return Err(PSP34Error::NotApproved);
+        }
+        if self.listing_of(token, &id).is_none() {
+            token.hold(&id, TokenHold::Listed)?;
+            if !self.listed_index.contains(&id) {
+                let count = self.listed_count();
+                self.listed.insert(count, &id);
+                self.listed_index.insert(&id, &count);
+                self.listed_count.set(&count.saturating_add(1));
+            }
+        }
+        self.listings.insert(
+            &id,
+            &Listing {
+                seller: caller,
+                price,
+            },
+        );
+        Ok(EventList::from(PSP34Event::Listed {
+            id,
+            seller: caller,
+            price,
+        }))
+    }
+
+    /// Removes the listing of token `id`, conducted by its seller.
+    pub fn delist(
+        &mut self,
+        token: &mut PSP34Data,
+        caller: AccountId,
+        id: Id,
+    ) -> Result<EventList, PSP34Error> {
+        let id = token.normalize_id(id);
+        let listing = self.valid_listing(token, &id)?;
+        if listing.seller != caller {
+            return Err(PSP34Error::NotApproved);
+        }
+        token.release(&id);
+        self.remove_listing(&id);
+        Ok(EventList::from(PSP34Event::Delisted { id, seller: caller }))
+    }
+
+    /// Sells listed token `id` to `buyer`, who paid `paid`, which must be the price.
+    /// Returns how the payment is split between the seller and the royalty receiver.
+    pub fn buy(
+        &mut self,
+        token: &mut PSP34Data,
+        buyer: AccountId,
+        id: Id,
+        paid: u128,
+    ) -> Result<(Payouts, EventList), PSP34Error> {
+        let id = token.normalize_id(id);
+        let Listing { seller, price } = self.valid_listing(token, &id)?;
+        if paid != price {
+            return Err(PSP34Error::Custom(String::from(
+                "Payment does not match the price.",
+            )));
+        }
+        if buyer == seller {
+            return Err(PSP34Error::Custom(String::from(
+                "Seller cannot buy their own token.",
+            )));
+        }
+        self.remove_listing(&id);
+        let mut events = token.transfer_unchecked(seller, buyer, id.clone())?;
+        events.push(PSP34Event::Sold {
+            id,
+            seller,
+            buyer,
+            price,
+        });
+        Ok((self.payouts(seller, price), events))
+    }
+
+    /// Returns the offers for token `id` with their escrowed values, oldest first.
+    pub fn offers_for(&self, token: &PSP34Data, id: &Id) -> Vec<(AccountId, u128)> {
+        let id = &token.normalize_id(id.clone());
+        self.offers.get(id).unwrap_or_default()
+    }
+
+    /// Returns the total value escrowed by all offers and the highest bids of auctions.
+    pub fn total_escrowed(&self) -> u128 {
+        self.escrowed.get_or_default()
+    }
+
+    /// Records an offer of `bidder` for token `id`, escrowing `value`, which the contract
+    /// received with the call. An existing offer of `bidder` is raised by `value`.
+    pub fn make_offer(
+        &mut self,
+        token: &PSP34Data,
+        bidder: AccountId,
+        id: Id,
+        value: u128,
+    ) -> Result<EventList, PSP34Error> {
+        let id = token.normalize_id(id);
+        if token.owner_of(&id).ok_or(PSP34Error::TokenNotExists)? == bidder {
+            return Err(PSP34Error::Custom(String::from(
+                "Owner cannot make an offer for their own token.",
+            )));
+        }
+        if value == 0 {
+            return Err(PSP34Error::Custom(String::from(
+                "Offer must carry a value.",
+            )));
+        }
+        let mut offers = self.offers.get(&id).unwrap_or_default();
+        let amount = match offers.iter().position(|(account, _)| *account == bidder) {
+            Some(pos) => {
+                let amount = &mut offers[pos].1;
+                *amount = amount.saturating_add(value);
+                *amount
+            }
+            None if offers.len() >= MAX_OFFERS_PER_TOKEN => {
+                return Err(PSP34Error::Custom(String::from(
+                    "Too many offers for the token.",
+                )))
+            }
+            None => {
+                offers.push((bidder, value));
+                value
+            }
+        };
+        self.offers.insert(&id, &offers);
+        self.escrowed
+            .set(&self.total_escrowed().saturating_add(value));
+        Ok(EventList::from(PSP34Event::OfferMade {
+            id,
+            bidder,
+            amount,
+        }))
+    }
+
+    /// Withdraws the offer of `bidder` for token `id`.
+    /// Returns the escrowed value, which the contract has to refund to `bidder`.
+    pub fn cancel_offer(
+        &mut self,
+        token: &PSP34Data,
+        bidder: AccountId,
+        id: Id,
+    ) -> Result<(u128, EventList), PSP34Error> {
+        let id = token.normalize_id(id);
+        let amount = self.take_offer(&id, bidder)?;
+        Ok((
+            amount,
+            EventList::from(PSP34Event::OfferCancelled { id, bidder, amount }),
+        ))
+    }
+
+    /// Cancels all offers for token `id`, e.g. when it is burned. Returns the escrowed
+    /// values, which the contract has to refund to the bidders.
+    pub fn cancel_offers(
+        &mut self,
+        token: &PSP34Data,
+        id: Id,
+    ) -> (Vec<(AccountId, u128)>, EventList) {
+        let id = token.normalize_id(id);
+        let offers = self.offers.take(&id).unwrap_or_default();
+        let total = offers.iter().map(|(_, amount)| amount).sum::<u128>();
+        self.escrowed
+            .set(&self.total_escrowed().saturating_sub(total));
+        let mut events = EventList::new();
+        for (bidder, amount) in &offers {
+            events.push(PSP34Event::OfferCancelled {
+                id: id.clone(),
+                bidder: *bidder,
+                amount: *amount,
+            });
+        }
+        (offers, events)
+    }
+
+    /// Accepts the offer of `bidder` for token `id`, conducted by the token's owner `caller`.
+    /// The token is moved to `bidder`, ending its listing if there is one. Returns how the
+    /// escrowed value is split between the owner and the royalty receiver.
+    pub fn accept_offer(
+        &mut self,
+        token: &mut PSP34Data,
+        caller: AccountId,
+        id: Id,
+        bidder: AccountId,
+    ) -> Result<(Payouts, EventList), PSP34Error> {
+        let id = token.normalize_id(id);
+        if token.owner_of(&id).ok_or(PSP34Error::TokenNotExists)? != caller {
+            return Err(PSP34Error::NotApproved);
+        }
+        if bidder == caller {
+            return Err(PSP34Error::Custom(String::from(
+                "Owner cannot accept their own offer.",
+            )));
+        }
+        if token
+            .hold_of(&id)
+            .is_some_and(|hold| hold != TokenHold::Listed)
+        {
+            return Err(PSP34Error::Custom(String::from(
+                "Token is held by another extension.",
+            )));
+        }
+        let amount = self.take_offer(&id, bidder)?;
+        if self.listings.contains(&id) {
+            self.remove_listing(&id);
+        }
+        let mut events = token.transfer_unchecked(caller, bidder, id.clone())?;
+        events.push(PSP34Event::OfferAccepted {
+            id,
+            seller: caller,
+            bidder,
+            amount,
+        });
+        Ok((self.payouts(caller, amount), events))
+    }
+
+    /// Returns the auction of token `id`, if there is one which has not been settled.
+    pub fn auction_of(&self, token: &PSP34Data, id: &Id) -> Option<Auction> {
+        let id = &token.normalize_id(id.clone());
+        self.auctions.get(id)
+    }
+
+    /// Auctions token `id` of `caller`, starting at `start_price`, with each next bid
+    /// higher by at least `min_increment`. The token is held until the auction is settled.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_auction(
+        &mut self,
+        token: &mut PSP34Data,
+        caller: AccountId,
+        id: Id,
+        start_price: u128,
+        min_increment: u128,
+        end_time: u64,
+        now: u64,
+    ) -> Result<EventList, PSP34Error> {
+        let id = token.normalize_id(id);
+        if token.owner_of(&id).ok_or(PSP34Error::TokenNotExists)? != caller {
+            return Err(PSP34Error::NotApproved);
+        }
+        if end_time <= now {
+            return Err(PSP34Error::Custom(String::from(
+                "Auction must end in the future.",
+            )));
+        }
+        token.hold(&id, TokenHold::Auctioned)?;
+        self.auctions.insert(
+            &id,
+            &Auction {
+                seller: caller,
+                start_price,
+                min_increment,
+                end_time,
+                highest_bid: None,
+            },
+        );
+        Ok(EventList::from(PSP34Event::AuctionCreated {
+            id,
+            seller: caller,
+            start_price,
+            min_increment,
+            end_time,
+        }))
+    }
+
+    /// Bids `value`, which the contract received with the call, for auctioned token `id`.
+    /// A bid in the last `ANTI_SNIPING_WINDOW` of the auction extends it. Returns the
+    /// previous highest bid, which the contract has to refund.
+    pub fn bid(
+        &mut self,
+        token: &PSP34Data,
+        bidder: AccountId,
+        id: Id,
+        value: u128,
+        now: u64,
+    ) -> Result<(Option<(AccountId, u128)>, EventList), PSP34Error> {
+        let id = token.normalize_id(id);
+        let mut auction = self.valid_auction(token, &id)?;
+        if now >= auction.end_time {
+            return Err(PSP34Error::Custom(String::from("Auction has ended.")));
+        }
+        if bidder == auction.seller {
+            return Err(PSP34Error::Custom(String::from(
+                "Seller cannot bid on their own token.",
+            )));
+        }
+        let min_bid = match auction.highest_bid {
+            Some((_, amount)) => amount.saturating_add(auction.min_increment.max(1)),
+            None => auction.start_price,
+        };
+        if value < min_bid {
+            return Err(PSP34Error::Custom(String::from("Bid is too low.")));
+        }
+        auction.end_time = auction
+            .end_time
+            .max(now.saturating_add(ANTI_SNIPING_WINDOW));
+        let outbid = auction.highest_bid.replace((bidder, value));
+        self.auctions.insert(&id, &auction);
+        self.escrowed.set(
+            &self
+                .total_escrowed()
+                .saturating_add(value)
+                .saturating_sub(outbid.map_or(0, |(_, amount)| amount)),
+        );
+        Ok((
+            outbid,
+            EventList::from(PSP34Event::BidPlaced {
+                id,
+                bidder,
+                amount: value,
+                end_time: auction.end_time,
+            }),
+        ))
+    }
+
+    /// Settles the auction of token `id` after it has ended, moving the token to the
+    /// highest bidder, or releasing it to the seller if there were no bids. Returns the
+    /// payments the contract has to make out of the escrowed bid.
+    ///
+    /// If the token changed owner in the meantime, e.g. with `transfer_unchecked`,
+    /// the highest bid is refunded instead.
+    pub fn settle(
+        &mut self,
+        token: &mut PSP34Data,
+        id: Id,
+        now: u64,
+    ) -> Result<(Vec<(AccountId, u128)>, EventList), PSP34Error> {
+        let id = token.normalize_id(id);
+        let auction = self
+            .auctions
+            .get(&id)
+            .ok_or(PSP34Error::Custom(String::from("Token is not auctioned.")))?;
+        if now < auction.end_time {
+            return Err(PSP34Error::Custom(String::from("Auction has not ended.")));
+        }
+        let valid = self.valid_auction(token, &id).is_ok();
+        self.auctions.remove(&id);
+        if let Some((_, amount)) = auction.highest_bid {
+            self.escrowed
+                .set(&self.total_escrowed().saturating_sub(amount));
+        }
+        let seller = auction.seller;
+        let (winner, price) = match auction.highest_bid {
+            Some((winner, price)) if valid => (Some(winner), price),
+            _ => (None, 0),
+        };
+        let (payments, mut events) = match winner {
+            Some(winner) => (
+                self.payouts(seller, price).payments().collect(),
+                token.transfer_unchecked(seller, winner, id.clone())?,
+            ),
+            None => {
+                if valid {
+                    token.release(&id);
+                }
+                (auction.highest_bid.into_iter().collect(), EventList::new())
+            }
+        };
+        events.push(PSP34Event::AuctionSettled {
+            id,
+            seller,
+            winner,
+            price,
+        });
+        Ok((payments, events))
+    }
+
+    // Returns the auction of `id` if its seller still owns the auctioned token.
+    fn valid_auction(&self, token: &PSP34Data, id: &Id) -> Result<Auction, PSP34Error> {
+        self.auctions
+            .get(id)
+            .filter(|auction| {
+                token.owner_of(id) == Some(auction.seller)
+                    && token.hold_of(id) == Some(TokenHold::Auctioned)
+            })
+            .ok_or(PSP34Error::Custom(String::from("Token is not auctioned.")))
+    }
+
+    // Removes the offer of `bidder` for `id` from the escrow, returning its value.
+    fn take_offer(&mut self, id: &Id, bidder: AccountId) -> Result<u128, PSP34Error> {
+        let mut offers = self.offers.get(id).unwrap_or_default();
+        let pos = offers
+            .iter()
+            .position(|(account, _)| *account == bidder)
+            .ok_or(PSP34Error::Custom(String::from("There is no such offer.")))?;
+        let (_, amount) = offers.remove(pos);
+        if offers.is_empty() {
+            self.offers.remove(id);
+        } else {
+            self.offers.insert(id, &offers);
+        }
+        self.escrowed
+            .set(&self.total_escrowed().saturating_sub(amount));
+        Ok(amount)
+    }
+
+    // Splits `price` between the seller and the royalty, keeping a split royalty
+    // as undistributed.
+    fn payouts(&mut self, seller: AccountId, price: u128) -> Payouts {
+        let mut royalty = self.royalty_info(price);
+        let seller = (seller, price - royalty.map_or(0, |(_, amount)| amount));
+        let mut retained = 0;
+        if !self.royalty_recipients().is_empty() {
+            retained = royalty.take().map_or(0, |(_, amount)| amount);
+            self.undistributed
+                .set(&self.undistributed().saturating_add(retained));
+        }
+        Payouts {
+            seller,
+            royalty,
+            retained,
+        }
+    }
+
+    fn ensure_distributed(&self) -> Result<(), PSP34Error> {
+        if self.undistributed() > 0 {
+            return Err(PSP34Error::Custom(String::from(
+                "Undistributed royalties have to be distributed first.",
+            )));
+        }
+        Ok(())
+    }
+
+    fn valid_listing(&self, token: &PSP34Data, id: &Id) -> Result<Listing, PSP34Error> {
+        self.listing_of(token, id)
+            .ok_or(PSP34Error::Custom(String::from("Token is not listed.")))
+    }
+
+    // Removes the listing of `id`, moving the last listed token to its index.
+    fn remove_listing(&mut self, id: &Id) {
+        self.listings.remove(id);
+        let Some(index) = self.listed_index.take(id) else {
+            return;
+        };
+        let last = self.listed_count().saturating_sub(1);
+        if index != last {
+            if let Some(moved) = self.listed.get(last) {
+                self.listed.insert(index, &moved);
+                self.listed_index.insert(&moved, &index);
+            }
+        }
+        self.listed.remove(last);
+        self.listed_count.set(&last);
+    }
+}
+
+// Returns `bps` basis points of `amount`, splitting it to avoid overflowing the multiplication.
+fn share_of(amount: u128, bps: u16) -> u128 {
+    let (bps, max) = (bps as u128, 10_000);
+    amount / max * bps + amount % max * bps / max
+}
+
+// Splits `amount` between the `recipients` by their shares, the remainder going to the first.
+fn split(amount: u128, recipients: &[(AccountId, u16)]) -> Vec<(AccountId, u128)> {
+    let mut payments: Vec<_> = recipients
+        .iter()
+        .map(|(account, share)| (*account, share_of(amount, *share)))
+        .collect();
+    let paid: u128 = payments.iter().map(|(_, amount)| amount).sum();
+    if let Some((_, first)) = payments.first_mut() {
+        *first += amount - paid;
+    }
+    payments
+}
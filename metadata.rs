@@ -1,13 +1,55 @@
 use crate::{
-    data::{Id, PSP34Event},
+    data::{EventList, Id, PSP34Event},
     PSP34Error,
 };
-use ink::{prelude::{vec::Vec, vec}, storage::Mapping};
+use ink::{
+    prelude::{string::String, vec::Vec},
+    storage::{Lazy, Mapping},
+};
+
+#[cfg(all(feature = "std", feature = "enumerable"))]
+use crate::PSP34Data;
+#[cfg(all(feature = "std", feature = "enumerable"))]
+use ink::primitives::AccountId;
+
+/// Key of the collection attribute holding the collection name.
+pub const NAME_KEY: &[u8] = b"name";
+/// Key of the collection attribute holding the collection symbol.
+pub const SYMBOL_KEY: &[u8] = b"symbol";
+/// Key of the collection attribute holding the base URI of the tokens' metadata.
+pub const BASE_URI_KEY: &[u8] = b"baseUri";
+/// Key of the collection attribute holding the URI of the collection-level metadata.
+pub const CONTRACT_URI_KEY: &[u8] = b"contractUri";
+
+/// Prefix of the attribute keys which can be written by the token owner with
+/// `set_user_attribute`. All other keys are meant to be written by the admin.
+pub const USER_KEY_PREFIX: &[u8] = b"user:";
+
+/// Maximal number of keys read by a single `get_attributes` call.
+pub const MAX_ATTRIBUTE_KEYS: usize = 64;
+
+/// The well-known attributes of the collection, `None` where not set.
+#[derive(Debug, Default, PartialEq, Eq, Clone, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub struct CollectionMetadata {
+    pub name: Option<Vec<u8>>,
+    pub symbol: Option<Vec<u8>>,
+    pub base_uri: Option<Vec<u8>>,
+}
 
 #[ink::storage_item]
 #[derive(Default, Debug)]
 pub struct Data {
+    #[allow(clippy::type_complexity)]
     attributes: Mapping<(Id, Vec<u8>), Vec<u8>>,
+    #[allow(clippy::type_complexity)]
+    tokens_by_attribute: Mapping<(Vec<u8>, Vec<u8>), Vec<Id>>,
+    indexed_keys: Mapping<Id, Vec<Vec<u8>>>,
+    attribute_count: Mapping<Id, u32>,
+    total_attributes: Lazy<u128>,
+    user_keys: Mapping<Id, Vec<Vec<u8>>>,
+    #[allow(clippy::type_complexity)]
+    value_count: Mapping<(Vec<u8>, Vec<u8>), u128>,
 }
 
 impl Data {
@@ -15,17 +57,226 @@ impl Data {
         self.attributes.get((&id, &key))
     }
 
+    /// Returns the values of the attributes of `id` for the given `keys`, in the same order.
+    /// Only the first `MAX_ATTRIBUTE_KEYS` keys are read, the rest is ignored.
+    pub fn get_attributes(&self, id: Id, keys: Vec<Vec<u8>>) -> Vec<Option<Vec<u8>>> {
+        keys.into_iter()
+            .take(MAX_ATTRIBUTE_KEYS)
+            .map(|key| self.attributes.get((&id, &key)))
+            .collect()
+    }
+
+    /// Returns the attributes stored under `NAME_KEY`, `SYMBOL_KEY` and `BASE_URI_KEY`
+    /// for the collection with the given `collection_id`.
+    pub fn collection_metadata(&self, collection_id: Id) -> CollectionMetadata {
+        let get = |key: &[u8]| self.attributes.get((&collection_id, &key.to_vec()));
+        CollectionMetadata {
+            name: get(NAME_KEY),
+            symbol: get(SYMBOL_KEY),
+            base_uri: get(BASE_URI_KEY),
+        }
+    }
+
+    /// Returns the number of attributes set for all tokens and the collection.
+    pub fn total_attributes(&self) -> u128 {
+        self.total_attributes.get_or_default()
+    }
+
+    /// Returns the number of attributes set for `id`.
+    pub fn attribute_count(&self, id: Id) -> u32 {
+        self.attribute_count.get(&id).unwrap_or(0)
+    }
+
+    /// Sets the attribute of `id` for the given `key` to `value`.
+    /// Writing the value which is already stored changes nothing and emits no event.
     pub fn set_attribute(
         &mut self,
         id: Id,
         key: Vec<u8>,
         value: Vec<u8>,
-    ) -> Result<Vec<PSP34Event>, PSP34Error> {
+    ) -> Result<EventList, PSP34Error> {
+        let old_value = self.attributes.get((&id, &key));
+        if old_value.as_ref() == Some(&value) {
+            return Ok(EventList::new());
+        }
         self.attributes.insert((&id, &key), &value);
-        Ok(vec![PSP34Event::AttributeSet {
+        match old_value {
+            Some(old_value) => self.decrement_value_count(&key, &old_value),
+            None => {
+                self.attribute_count
+                    .insert(&id, &(self.attribute_count(id.clone()) + 1));
+                self.total_attributes.set(&(self.total_attributes() + 1));
+            }
+        }
+        self.value_count.insert(
+            (&key, &value),
+            &(self.count_with_attribute(key.clone(), value.clone()) + 1),
+        );
+        Ok(EventList::from(PSP34Event::AttributeSet {
             id,
             key,
             data: value,
-        }])
+        }))
+    }
+
+    /// Returns whether `key` belongs to the owner-writable namespace.
+    pub fn is_user_key(key: &[u8]) -> bool {
+        key.starts_with(USER_KEY_PREFIX)
+    }
+
+    /// Sets the attribute like `set_attribute`, for a key in the owner-writable
+    /// namespace. The caller is responsible for checking that the token owner conducts it.
+    pub fn set_user_attribute(
+        &mut self,
+        id: Id,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> Result<EventList, PSP34Error> {
+        if !Self::is_user_key(&key) {
+            return Err(PSP34Error::Custom(String::from(
+                "Attribute key is not in the user namespace.",
+            )));
+        }
+        if self.attributes.get((&id, &key)).is_none() {
+            let mut keys = self.user_keys.get(&id).unwrap_or_default();
+            keys.push(key.clone());
+            self.user_keys.insert(&id, &keys);
+        }
+        self.set_attribute(id, key, value)
+    }
+
+    /// Removes all the attributes of token `id` set with `set_user_attribute`.
+    pub fn clear_user_attributes(&mut self, id: &Id) {
+        for key in self.user_keys.take(id).unwrap_or_default() {
+            self.remove_attribute(id.clone(), key);
+        }
+    }
+
+    /// Removes the attribute of `id` for the given `key`, returning its value if it was set.
+    pub fn remove_attribute(&mut self, id: Id, key: Vec<u8>) -> Option<Vec<u8>> {
+        let value = self.attributes.take((&id, &key))?;
+        match self.attribute_count(id.clone()) {
+            0 | 1 => self.attribute_count.remove(&id),
+            count => {
+                self.attribute_count.insert(&id, &(count - 1));
+            }
+        }
+        self.total_attributes.set(&(self.total_attributes() - 1));
+        self.decrement_value_count(&key, &value);
+        Some(value)
     }
-}
\ No newline at end of file
+
+    /// Returns the number of tokens, including the collection, with the attribute
+    /// `key` set to `value`. Unlike `count_by_attribute`, counts attributes written
+    /// with any of the setters.
+    pub fn count_with_attribute(&self, key: Vec<u8>, value: Vec<u8>) -> u128 {
+        self.value_count.get((&key, &value)).unwrap_or(0)
+    }
+
+    fn decrement_value_count(&mut self, key: &Vec<u8>, value: &Vec<u8>) {
+        match self.value_count.get((key, value)).unwrap_or(0) {
+            0 | 1 => self.value_count.remove((key, value)),
+            count => {
+                self.value_count.insert((key, value), &(count - 1));
+            }
+        }
+    }
+
+    /// Sets the attribute like `set_attribute` and additionally indexes token `id`
+    /// under its `(key, value)` pair, so it can be found with `tokens_by_attribute`.
+    ///
+    /// Overwriting the value moves `id` to the new pair. Keys written with this method
+    /// should not be written with `set_attribute`, which does not update the index.
+    pub fn set_indexed_attribute(
+        &mut self,
+        id: Id,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> Result<EventList, PSP34Error> {
+        match self.attributes.get((&id, &key)) {
+            Some(old_value) if old_value == value => return Ok(EventList::new()),
+            Some(old_value) => self.unindex(&id, &key, &old_value),
+            None => {
+                let mut keys = self.indexed_keys.get(&id).unwrap_or_default();
+                keys.push(key.clone());
+                self.indexed_keys.insert(&id, &keys);
+            }
+        }
+        let mut ids = self
+            .tokens_by_attribute
+            .get((&key, &value))
+            .unwrap_or_default();
+        ids.push(id.clone());
+        self.tokens_by_attribute.insert((&key, &value), &ids);
+
+        self.set_attribute(id, key, value)
+    }
+
+    /// Removes all the indexed attributes of token `id` together with their index entries.
+    /// Should be called when the token is burned.
+    pub fn clear_indexed_attributes(&mut self, id: &Id) {
+        for key in self.indexed_keys.take(id).unwrap_or_default() {
+            if let Some(value) = self.remove_attribute(id.clone(), key.clone()) {
+                self.unindex(id, &key, &value);
+            }
+        }
+    }
+
+    /// Returns at most `limit` tokens with the indexed attribute `key` set to `value`,
+    /// skipping the first `offset` of them.
+    pub fn tokens_by_attribute(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<Id> {
+        self.tokens_by_attribute
+            .get((&key, &value))
+            .unwrap_or_default()
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect()
+    }
+
+    /// Returns the number of tokens with the indexed attribute `key` set to `value`.
+    pub fn count_by_attribute(&self, key: Vec<u8>, value: Vec<u8>) -> u32 {
+        self.tokens_by_attribute
+            .get((&key, &value))
+            .map_or(0, |ids| ids.len() as u32)
+    }
+
+    /// Returns the tokens of `owner` in `token` with the attribute `key` set to `value`,
+    /// whether indexed or not.
+    ///
+    /// Reads all of `owner`'s tokens and one attribute per token, so the cost is unbounded.
+    /// Meant for off-chain tooling and tests only, never call it from a message.
+    #[cfg(all(feature = "std", feature = "enumerable"))]
+    pub fn owned_tokens_with_attribute(
+        &self,
+        token: &PSP34Data,
+        owner: AccountId,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> Vec<Id> {
+        token
+            .tokens_of_owner(owner)
+            .into_iter()
+            .filter(|id| self.get_attribute(id.clone(), key.clone()).as_ref() == Some(&value))
+            .collect()
+    }
+
+    fn unindex(&mut self, id: &Id, key: &Vec<u8>, value: &Vec<u8>) {
+        if let Some(mut ids) = self.tokens_by_attribute.get((key, value)) {
+            if let Some(pos) = ids.iter().position(|v| v == id) {
+                ids.swap_remove(pos);
+            }
+            if ids.is_empty() {
+                self.tokens_by_attribute.remove((key, value));
+            } else {
+                self.tokens_by_attribute.insert((key, value), &ids);
+            }
+        }
+    }
+}
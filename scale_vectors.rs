@@ -0,0 +1,440 @@
+//! Golden SCALE encodings of `Id`, `PSP34Error` and every `PSP34Event`.
+//!
+//! Off-chain decoders are written against these byte layouts, so a change of a
+//! variant index or of the field order makes the tests below fail. Such a change
+//! has to be deliberate: update the vectors and regenerate the JSON fixture for
+//! external SDKs with `PSP34_UPDATE_FIXTURES=1 cargo test scale_vectors`.
+
+use crate::{Id, PSP34Error, PSP34Event};
+use core::fmt::Debug;
+use ink::{
+    prelude::{format, string::String, vec::Vec},
+    primitives::AccountId,
+};
+use scale::{Decode, DecodeAll, Encode};
+
+macro_rules! account {
+    (1) => {
+        "0101010101010101010101010101010101010101010101010101010101010101"
+    };
+    (2) => {
+        "0202020202020202020202020202020202020202020202020202020202020202"
+    };
+}
+
+fn alice() -> AccountId {
+    AccountId::from([1; 32])
+}
+
+fn bob() -> AccountId {
+    AccountId::from([2; 32])
+}
+
+fn id_vectors() -> Vec<(Id, &'static str)> {
+    let mut u256 = [0; 32];
+    u256[15] = 1;
+    u256[31] = 42;
+    vec![
+        (Id::U8(1), "0001"),
+        (Id::U16(0x0102), "010201"),
+        (Id::U32(0x01020304), "0204030201"),
+        (Id::U64(0x0102030405060708), "030807060504030201"),
+        (Id::U128(1), "0401000000000000000000000000000000"),
+        (Id::Bytes(vec![0xde, 0xad]), "0508dead"),
+        (
+            Id::U256(u256),
+            "06000000000000000000000000000000010000000000000000000000000000002a",
+        ),
+    ]
+}
+
+fn error_vectors() -> Vec<(PSP34Error, &'static str)> {
+    vec![
+        (PSP34Error::Custom(String::from("boom")), "0010626f6f6d"),
+        (PSP34Error::SelfApprove, "01"),
+        (PSP34Error::NotApproved, "02"),
+        (PSP34Error::TokenExists, "03"),
+        (PSP34Error::TokenNotExists, "04"),
+        (
+            PSP34Error::SafeTransferCheckFailed(String::from("boom")),
+            "0510626f6f6d",
+        ),
+        (PSP34Error::MaxBalanceExceeded, "06"),
+        (PSP34Error::MaxSupplyExceeded, "07"),
+        (PSP34Error::Expired, "08"),
+        (PSP34Error::TransfersDisabled, "09"),
+        (PSP34Error::BurningDisabled, "0a"),
+        (PSP34Error::ApprovedForAll, "0b"),
+    ]
+}
+
+fn event_vectors() -> Vec<(PSP34Event, &'static str)> {
+    vec![
+        (
+            PSP34Event::Transfer {
+                from: None,
+                to: Some(alice()),
+                id: Id::U8(1),
+            },
+            concat!("00", "00", "01", account!(1), "0001"),
+        ),
+        (
+            PSP34Event::Approval {
+                owner: alice(),
+                operator: bob(),
+                id: Some(Id::U8(1)),
+                approved: true,
+            },
+            concat!("01", account!(1), account!(2), "01", "0001", "01"),
+        ),
+        (
+            PSP34Event::AttributeSet {
+                id: Id::U8(1),
+                key: b"name".to_vec(),
+                data: b"PSP".to_vec(),
+            },
+            concat!("02", "0001", "106e616d65", "0c505350"),
+        ),
+        (
+            PSP34Event::TransferOffered {
+                from: alice(),
+                to: bob(),
+                id: Id::U8(1),
+                deadline: Some(100),
+            },
+            concat!("03", account!(1), account!(2), "0001", "01", "64000000"),
+        ),
+        (
+            PSP34Event::TransferClaimed {
+                from: alice(),
+                to: bob(),
+                id: Id::U8(1),
+            },
+            concat!("04", account!(1), account!(2), "0001"),
+        ),
+        (
+            PSP34Event::TransferCancelled {
+                from: alice(),
+                to: bob(),
+                id: Id::U8(1),
+            },
+            concat!("05", account!(1), account!(2), "0001"),
+        ),
+        (
+            PSP34Event::ProvenanceSet { hash: [1; 32] },
+            concat!("06", account!(1)),
+        ),
+        (
+            PSP34Event::Minted {
+                to: alice(),
+                id: Id::U8(1),
+            },
+            concat!("07", account!(1), "0001"),
+        ),
+        (
+            PSP34Event::Burned {
+                from: alice(),
+                caller: bob(),
+                id: Id::U8(1),
+            },
+            concat!("08", account!(1), account!(2), "0001"),
+        ),
+        (
+            PSP34Event::TransferMemo {
+                id: Id::U8(1),
+                sequence: 2,
+                memo: b"hi".to_vec(),
+            },
+            concat!("09", "0001", "02000000", "086869"),
+        ),
+        (
+            PSP34Event::TokenRescued {
+                id: Id::U8(1),
+                to: bob(),
+            },
+            concat!("0a", "0001", account!(2)),
+        ),
+        (
+            PSP34Event::TeleportedOut {
+                id: Id::U8(1),
+                from: alice(),
+                dest_chain: 1000,
+                dest_account: vec![0xab, 0xcd],
+                nonce: 7,
+            },
+            concat!(
+                "0b",
+                "0001",
+                account!(1),
+                "e8030000",
+                "08abcd",
+                "0700000000000000"
+            ),
+        ),
+        (
+            PSP34Event::TeleportedIn {
+                id: Id::U8(1),
+                to: alice(),
+            },
+            concat!("0c", "0001", account!(1)),
+        ),
+        (
+            PSP34Event::BalanceChanged {
+                account: alice(),
+                new_balance: 3,
+            },
+            concat!("0d", account!(1), "03000000"),
+        ),
+        (
+            PSP34Event::Listed {
+                id: Id::U8(1),
+                seller: alice(),
+                price: 1000,
+            },
+            concat!(
+                "0e",
+                "0001",
+                account!(1),
+                "e8030000000000000000000000000000"
+            ),
+        ),
+        (
+            PSP34Event::Delisted {
+                id: Id::U8(1),
+                seller: alice(),
+            },
+            concat!("0f", "0001", account!(1)),
+        ),
+        (
+            PSP34Event::Sold {
+                id: Id::U8(1),
+                seller: alice(),
+                buyer: bob(),
+                price: 1000,
+            },
+            concat!(
+                "10",
+                "0001",
+                account!(1),
+                account!(2),
+                "e8030000000000000000000000000000"
+            ),
+        ),
+        (
+            PSP34Event::OfferMade {
+                id: Id::U8(1),
+                bidder: bob(),
+                amount: 1000,
+            },
+            concat!(
+                "11",
+                "0001",
+                account!(2),
+                "e8030000000000000000000000000000"
+            ),
+        ),
+        (
+            PSP34Event::OfferCancelled {
+                id: Id::U8(1),
+                bidder: bob(),
+                amount: 1000,
+            },
+            concat!(
+                "12",
+                "0001",
+                account!(2),
+                "e8030000000000000000000000000000"
+            ),
+        ),
+        (
+            PSP34Event::OfferAccepted {
+                id: Id::U8(1),
+                seller: alice(),
+                bidder: bob(),
+                amount: 1000,
+            },
+            concat!(
+                "13",
+                "0001",
+                account!(1),
+                account!(2),
+                "e8030000000000000000000000000000"
+            ),
+        ),
+        (
+            PSP34Event::AuctionCreated {
+                id: Id::U8(1),
+                seller: alice(),
+                start_price: 1000,
+                min_increment: 100,
+                end_time: 5,
+            },
+            concat!(
+                "14",
+                "0001",
+                account!(1),
+                "e8030000000000000000000000000000",
+                "64000000000000000000000000000000",
+                "0500000000000000"
+            ),
+        ),
+        (
+            PSP34Event::BidPlaced {
+                id: Id::U8(1),
+                bidder: bob(),
+                amount: 1000,
+                end_time: 5,
+            },
+            concat!(
+                "15",
+                "0001",
+                account!(2),
+                "e8030000000000000000000000000000",
+                "0500000000000000"
+            ),
+        ),
+        (
+            PSP34Event::AuctionSettled {
+                id: Id::U8(1),
+                seller: alice(),
+                winner: Some(bob()),
+                price: 1000,
+            },
+            concat!(
+                "16",
+                "0001",
+                account!(1),
+                "01",
+                account!(2),
+                "e8030000000000000000000000000000"
+            ),
+        ),
+        (
+            PSP34Event::CollateralLocked {
+                id: Id::U8(1),
+                owner: alice(),
+                lender: bob(),
+            },
+            concat!("17", "0001", account!(1), account!(2)),
+        ),
+        (
+            PSP34Event::CollateralReleased {
+                id: Id::U8(1),
+                owner: alice(),
+                lender: bob(),
+            },
+            concat!("18", "0001", account!(1), account!(2)),
+        ),
+        (
+            PSP34Event::CollateralSeized {
+                id: Id::U8(1),
+                owner: alice(),
+                lender: bob(),
+            },
+            concat!("19", "0001", account!(1), account!(2)),
+        ),
+        (
+            PSP34Event::BundleCreated {
+                id: Id::U8(1),
+                owner: alice(),
+                components: vec![Id::U8(2), Id::U8(3)],
+            },
+            concat!("1a", "0001", account!(1), "08", "0002", "0003"),
+        ),
+        (
+            PSP34Event::Unbundled {
+                id: Id::U8(1),
+                owner: alice(),
+                components: vec![Id::U8(2), Id::U8(3)],
+            },
+            concat!("1b", "0001", account!(1), "08", "0002", "0003"),
+        ),
+        (
+            PSP34Event::Crafted {
+                recipe_id: 1,
+                owner: alice(),
+                inputs: vec![Id::U8(2), Id::U8(3)],
+                output: Id::U8(4),
+            },
+            concat!("1c", "01000000", account!(1), "08", "0002", "0003", "0004"),
+        ),
+    ]
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+// Checks both directions for every vector, and that the vectors are listed
+// in the order of the variant indices, one per variant.
+fn check_vectors<T: Encode + Decode + PartialEq + Debug>(vectors: Vec<(T, &str)>) {
+    for (index, (value, hex)) in vectors.into_iter().enumerate() {
+        assert_eq!(to_hex(&value.encode()), hex, "encoding of {:?}", value);
+        assert_eq!(from_hex(hex)[0] as usize, index, "index of {:?}", value);
+        assert_eq!(T::decode_all(&mut &from_hex(hex)[..]), Ok(value));
+    }
+}
+
+#[test]
+fn id_encoding_matches_golden_vectors() {
+    check_vectors(id_vectors());
+}
+
+#[test]
+fn error_encoding_matches_golden_vectors() {
+    check_vectors(error_vectors());
+}
+
+#[test]
+fn event_encoding_matches_golden_vectors() {
+    check_vectors(event_vectors());
+}
+
+#[cfg(feature = "std")]
+fn fixture_json() -> String {
+    fn entries<T: Debug>(vectors: Vec<(T, &str)>) -> String {
+        let entries: Vec<String> = vectors
+            .into_iter()
+            .map(|(value, hex)| {
+                let value = format!("{:?}", value);
+                let variant = value.split(['(', ' ']).next().unwrap_or_default();
+                format!(
+                    "    {{ \"variant\": \"{}\", \"index\": {}, \"value\": \"{}\", \"hex\": \"0x{}\" }}",
+                    variant,
+                    from_hex(hex)[0],
+                    value.replace('\\', "\\\\").replace('"', "\\\""),
+                    hex
+                )
+            })
+            .collect();
+        entries.join(",\n")
+    }
+    format!(
+        "{{\n  \"Id\": [\n{}\n  ],\n  \"PSP34Error\": [\n{}\n  ],\n  \"PSP34Event\": [\n{}\n  ]\n}}\n",
+        entries(id_vectors()),
+        entries(error_vectors()),
+        entries(event_vectors())
+    )
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn json_fixture_matches_golden_vectors() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/scale_vectors.json");
+    if std::env::var_os("PSP34_UPDATE_FIXTURES").is_some() {
+        std::fs::write(path, fixture_json()).unwrap();
+    }
+    assert_eq!(
+        std::fs::read_to_string(path).unwrap_or_default(),
+        fixture_json(),
+        "{} is out of date, regenerate it with PSP34_UPDATE_FIXTURES=1",
+        path
+    );
+}
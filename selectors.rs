@@ -0,0 +1,57 @@
+//! Selectors of all the messages defined by the PSP34 traits.
+//!
+//! Each selector is the first four bytes of the BLAKE2b-256 hash of
+//! `"<TraitName>::<message_name>"`, which is how ink! derives selectors for
+//! trait messages without a custom namespace. They can be used to build raw
+//! cross-contract calls to any contract implementing the PSP34 standard,
+//! e.g. with `Selector::new(TRANSFER.to_be_bytes())`.
+
+// PSP34
+pub const COLLECTION_ID: u32 = 0xffa27a5f;
+pub const TOTAL_SUPPLY: u32 = 0x628413fe;
+pub const BALANCE_OF: u32 = 0xcde7e55f;
+pub const ALLOWANCE: u32 = 0x4790f55a;
+pub const TRANSFER: u32 = 0x3128d61b;
+pub const APPROVE: u32 = 0x1932a8b0;
+pub const OWNER_OF: u32 = 0x1168624d;
+
+// PSP34Metadata
+pub const GET_ATTRIBUTE: u32 = 0xf19d48d1;
+
+// PSP34Mintable
+pub const MINT: u32 = 0x6c41f2ec;
+
+// PSP34Burnable
+pub const BURN: u32 = 0x63c9877a;
+
+// PSP34Enumerable
+pub const OWNERS_TOKEN_BY_INDEX: u32 = 0x3bcfb511;
+pub const TOKEN_BY_INDEX: u32 = 0xcd0340d0;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ink::selector_id;
+
+    #[test]
+    fn selectors_follow_trait_definition_rules() {
+        assert_eq!(COLLECTION_ID, selector_id!("PSP34::collection_id"));
+        assert_eq!(TOTAL_SUPPLY, selector_id!("PSP34::total_supply"));
+        assert_eq!(BALANCE_OF, selector_id!("PSP34::balance_of"));
+        assert_eq!(ALLOWANCE, selector_id!("PSP34::allowance"));
+        assert_eq!(TRANSFER, selector_id!("PSP34::transfer"));
+        assert_eq!(APPROVE, selector_id!("PSP34::approve"));
+        assert_eq!(OWNER_OF, selector_id!("PSP34::owner_of"));
+        assert_eq!(GET_ATTRIBUTE, selector_id!("PSP34Metadata::get_attribute"));
+        assert_eq!(MINT, selector_id!("PSP34Mintable::mint"));
+        assert_eq!(BURN, selector_id!("PSP34Burnable::burn"));
+        assert_eq!(
+            OWNERS_TOKEN_BY_INDEX,
+            selector_id!("PSP34Enumerable::owners_token_by_index")
+        );
+        assert_eq!(
+            TOKEN_BY_INDEX,
+            selector_id!("PSP34Enumerable::token_by_index")
+        );
+    }
+}
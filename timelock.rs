@@ -0,0 +1,162 @@
+use crate::PSP34Error;
+use ink::{
+    prelude::{string::String, vec::Vec},
+    primitives::AccountId,
+    storage::{Lazy, Mapping},
+};
+
+/// Maximal number of actions scheduled at once.
+pub const MAX_SCHEDULED_ACTIONS: usize = 16;
+
+/// A change of the admin configuration which the timelock delays.
+#[derive(Debug, PartialEq, Eq, Clone, scale::Encode, scale::Decode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum Action {
+    /// Sets the royalty receiver and their share in basis points, or removes the royalty.
+    SetRoyalty(Option<(AccountId, u16)>),
+    /// Splits a royalty of the given basis points between the recipients by their shares.
+    SetRoyaltySplit(u16, Vec<(AccountId, u16)>),
+    /// Sets the URI of the collection-level metadata.
+    SetContractUri(String),
+    /// Changes the delay of the timelock, or disables it with `None`.
+    SetDelay(Option<u64>),
+}
+
+/// An action waiting in the timelock until `eta`.
+#[derive(Debug, PartialEq, Eq, Clone, scale::Encode, scale::Decode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct ScheduledAction {
+    pub id: u32,
+    pub action: Action,
+    /// Timestamp, in milliseconds, from which the action can be executed.
+    pub eta: u64,
+}
+
+/// A delay on changes of the admin configuration, giving holders advance notice.
+///
+/// While a delay is set, the `Action`s can only be scheduled and executed once the delay
+/// has passed, and the contract has to reject their immediate execution, see
+/// `ensure_unlocked`. Changing or removing the delay is an `Action` itself.
+#[ink::storage_item]
+#[derive(Default, Debug)]
+pub struct Data {
+    delay: Lazy<Option<u64>>,
+    next_id: Lazy<u32>,
+    // The ids of the scheduled actions, at most `MAX_SCHEDULED_ACTIONS`.
+    scheduled_ids: Lazy<Vec<u32>>,
+    scheduled: Mapping<u32, ScheduledAction>,
+}
+
+impl Data {
+    /// Returns the delay of the scheduled actions in milliseconds, `None` if the timelock
+    /// is disabled.
+    pub fn delay(&self) -> Option<u64> {
+        self.delay.get_or_default()
+    }
+
+    /// Sets the delay of the scheduled actions in milliseconds, or disables the timelock
+    /// if `delay` is `None`. Fails while the timelock is enabled, the delay then has to
+    /// be changed with a scheduled `Action::SetDelay`.
+    /// The caller is responsible for checking that the admin conducts it.
+    pub fn set_delay(&mut self, delay: Option<u64>) -> Result<(), PSP34Error> {
+        self.ensure_unlocked()?;
+        self.delay.set(&delay);
+        Ok(())
+    }
+
+    /// Checks that the timelock is disabled, so the actions can be executed immediately.
+    pub fn ensure_unlocked(&self) -> Result<(), PSP34Error> {
+        if self.delay().is_some() {
+            return Err(PSP34Error::Custom(String::from(
+                "Action has to be scheduled in the timelock.",
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the scheduled actions with the time from which they can be executed.
+    pub fn scheduled_actions(&self) -> Vec<ScheduledAction> {
+        self.scheduled_ids
+            .get_or_default()
+            .into_iter()
+            .filter_map(|id| self.scheduled.get(id))
+            .collect()
+    }
+
+    /// Schedules `action` at timestamp `now`, to be executed once the delay has passed.
+    /// Returns the id of the scheduled action.
+    /// The caller is responsible for checking that the admin conducts it.
+    pub fn schedule(&mut self, action: Action, now: u64) -> Result<u32, PSP34Error> {
+        let delay = self
+            .delay()
+            .ok_or(PSP34Error::Custom(String::from("Timelock is not enabled.")))?;
+        let mut ids = self.scheduled_ids.get_or_default();
+        if ids.len() >= MAX_SCHEDULED_ACTIONS {
+            return Err(PSP34Error::Custom(String::from(
+                "Too many scheduled actions.",
+            )));
+        }
+        let id = self.next_id.get_or_default();
+        self.next_id.set(&id.wrapping_add(1));
+        ids.push(id);
+        self.scheduled_ids.set(&ids);
+        self.scheduled.insert(
+            id,
+            &ScheduledAction {
+                id,
+                action,
+                eta: now.saturating_add(delay),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Removes the scheduled action `id` at timestamp `now` and returns it for the contract
+    /// to execute. Fails if its delay has not passed yet.
+    /// The caller is responsible for checking that the admin conducts it.
+    pub fn take_executable(&mut self, id: u32, now: u64) -> Result<Action, PSP34Error> {
+        let scheduled = self
+            .scheduled
+            .get(id)
+            .ok_or(PSP34Error::Custom(String::from(
+                "There is no such scheduled action.",
+            )))?;
+        if now < scheduled.eta {
+            return Err(PSP34Error::Custom(String::from(
+                "Scheduled action is not executable yet.",
+            )));
+        }
+        self.remove(id);
+        Ok(scheduled.action)
+    }
+
+    /// Cancels the scheduled action `id`.
+    /// The caller is responsible for checking that the admin conducts it.
+    pub fn cancel(&mut self, id: u32) -> Result<(), PSP34Error> {
+        if !self.scheduled.contains(id) {
+            return Err(PSP34Error::Custom(String::from(
+                "There is no such scheduled action.",
+            )));
+        }
+        self.remove(id);
+        Ok(())
+    }
+
+    /// Sets the delay when a scheduled `Action::SetDelay` is executed.
+    pub fn apply_delay(&mut self, delay: Option<u64>) {
+        self.delay.set(&delay);
+    }
+
+    fn remove(&mut self, id: u32) {
+        self.scheduled.remove(id);
+        let mut ids = self.scheduled_ids.get_or_default();
+        ids.retain(|scheduled| *scheduled != id);
+        self.scheduled_ids.set(&ids);
+    }
+}
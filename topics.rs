@@ -0,0 +1,62 @@
+//! Topics of the standard events emitted by the example contract, for indexers.
+//!
+//! Every event has the signature topic first, followed by one topic per field marked
+//! with `#[ink(topic)]`, in the order of declaration:
+//!
+//! | Event          | Topics                                       |
+//! |----------------|----------------------------------------------|
+//! | `Transfer`     | signature, `from`, `to`, `id`                |
+//! | `Approval`     | signature, `owner`, `operator`, `id`         |
+//! | `AttributeSet` | signature                                    |
+//!
+//! A topic is the SCALE encoding of a byte string prefix followed by the SCALE encoding
+//! of the value, zero-padded to 32 bytes if it fits, or its BLAKE2b-256 hash otherwise,
+//! see `topic`. The signature topic has an empty prefix and the bytes of
+//! `"<Contract>::<Event>"` as the value, and a field topic has the prefix
+//! `"<Contract>::<Event>::<field>"`. The constants below are for
+//! a contract named `Token`, like the example; ink! derives them from the name of the
+//! contract, so a contract named differently has to replace `Token` accordingly.
+
+use ink::env::hash::Blake2x256;
+use scale::Encode;
+
+pub const TRANSFER_SIGNATURE: &[u8] = b"Token::Transfer";
+pub const APPROVAL_SIGNATURE: &[u8] = b"Token::Approval";
+pub const ATTRIBUTE_SET_SIGNATURE: &[u8] = b"Token::AttributeSet";
+
+pub const TRANSFER_SIGNATURE_TOPIC: [u8; 32] = signature_topic(TRANSFER_SIGNATURE);
+pub const APPROVAL_SIGNATURE_TOPIC: [u8; 32] = signature_topic(APPROVAL_SIGNATURE);
+pub const ATTRIBUTE_SET_SIGNATURE_TOPIC: [u8; 32] = signature_topic(ATTRIBUTE_SET_SIGNATURE);
+
+pub const TRANSFER_FROM: &[u8] = b"Token::Transfer::from";
+pub const TRANSFER_TO: &[u8] = b"Token::Transfer::to";
+pub const TRANSFER_ID: &[u8] = b"Token::Transfer::id";
+pub const APPROVAL_OWNER: &[u8] = b"Token::Approval::owner";
+pub const APPROVAL_OPERATOR: &[u8] = b"Token::Approval::operator";
+pub const APPROVAL_ID: &[u8] = b"Token::Approval::id";
+
+/// Returns the topic of `value` with the given `prefix`, e.g.
+/// `topic(TRANSFER_TO, &Some(account))` for the recipient of a transfer.
+pub fn topic<T: Encode>(prefix: &[u8], value: &T) -> [u8; 32] {
+    let mut encoded = prefix.encode();
+    value.encode_to(&mut encoded);
+    let mut topic = [0; 32];
+    if encoded.len() <= topic.len() {
+        topic[..encoded.len()].copy_from_slice(&encoded);
+    } else {
+        ink::env::hash_bytes::<Blake2x256>(&encoded, &mut topic);
+    }
+    topic
+}
+
+// The same as `topic` with an empty prefix, which is encoded as a single zero byte,
+// for a signature that fits in 31 bytes.
+const fn signature_topic(signature: &[u8]) -> [u8; 32] {
+    let mut topic = [0; 32];
+    let mut i = 0;
+    while i < signature.len() {
+        topic[i + 1] = signature[i];
+        i += 1;
+    }
+    topic
+}
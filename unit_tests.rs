@@ -58,6 +58,22 @@ macro_rules! tests {
                 ink::env::test::set_caller::<E>(sender);
             }
 
+            // Asserts that the total supply equals the number of existing tokens among `ids`
+            // and the balances of `holders` sum up to it, matching the owners of the tokens.
+            fn check_invariants(token: &$contract, holders: &[AccountId], ids: &[Id]) {
+                let owners: Vec<AccountId> =
+                    ids.iter().filter_map(|id| token.owner_of(id.clone())).collect();
+                assert_eq!(token.total_supply(), owners.len() as u128);
+                let mut balances_sum = 0;
+                for holder in holders {
+                    let balance = token.balance_of(*holder);
+                    let owned = owners.iter().filter(|owner| *owner == holder).count();
+                    assert_eq!(balance as usize, owned, "Balance mismatch");
+                    balances_sum += balance as u128;
+                }
+                assert_eq!(balances_sum, token.total_supply(), "Supply mismatch");
+            }
+
             #[ink::test]
             fn mint_works() {
                 let accounts = default_accounts::<E>();
@@ -256,6 +272,101 @@ macro_rules! tests {
                 assert!(!token.allowance(accounts.alice, accounts.bob, None));
             }
 
+            #[ink::test]
+            fn repeated_approve_for_all_emits_single_event() {
+                let accounts = default_accounts::<E>();
+                // Create a new contract instance.
+                let mut token = $constructor();
+                let start = recorded_events().count();
+                // Approve all tokens transfer for Bob on behalf of Alice, twice.
+                assert_eq!(token.approve(accounts.bob, None, true), Ok(()));
+                assert_eq!(token.approve(accounts.bob, None, true), Ok(()));
+                // Only the first approval emits an event.
+                let events = decode_events(start);
+                assert_eq!(events.len(), 1);
+                assert_approval(&events[0], accounts.alice, accounts.bob, None, true);
+                // Bob is still an approved operator for Alice.
+                assert!(token.allowance(accounts.alice, accounts.bob, None));
+            }
+
+            #[ink::test]
+            fn repeated_approve_for_token_emits_single_event() {
+                let accounts = default_accounts::<E>();
+                // Create a new contract instance.
+                let mut token = $constructor();
+                // Create token Id 1.
+                assert_eq!(token.mint(Id::U8(1)), Ok(()));
+                let start = recorded_events().count();
+                // Approve token Id 1 transfer for Bob on behalf of Alice, twice.
+                assert_eq!(token.approve(accounts.bob, Some(Id::U8(1)), true), Ok(()));
+                assert_eq!(token.approve(accounts.bob, Some(Id::U8(1)), true), Ok(()));
+                // Only the first approval emits an event.
+                let events = decode_events(start);
+                assert_eq!(events.len(), 1);
+                assert_approval(&events[0], accounts.alice, accounts.bob, Some(Id::U8(1)), true);
+                assert!(token.allowance(accounts.alice, accounts.bob, Some(Id::U8(1))));
+            }
+
+            #[ink::test]
+            fn revoking_missing_approval_emits_no_event() {
+                let accounts = default_accounts::<E>();
+                // Create a new contract instance.
+                let mut token = $constructor();
+                // Create token Id 1.
+                assert_eq!(token.mint(Id::U8(1)), Ok(()));
+                let start = recorded_events().count();
+                // Bob was never approved, neither for token Id 1 nor for all tokens.
+                assert_eq!(token.approve(accounts.bob, Some(Id::U8(1)), false), Ok(()));
+                assert_eq!(token.approve(accounts.bob, None, false), Ok(()));
+                assert_eq!(decode_events(start).len(), 0);
+                // Revoking an existing approval still emits an event, but only once.
+                assert_eq!(token.approve(accounts.bob, Some(Id::U8(1)), true), Ok(()));
+                let start = recorded_events().count();
+                assert_eq!(token.approve(accounts.bob, Some(Id::U8(1)), false), Ok(()));
+                assert_eq!(token.approve(accounts.bob, Some(Id::U8(1)), false), Ok(()));
+                let events = decode_events(start);
+                assert_eq!(events.len(), 1);
+                assert_approval(&events[0], accounts.alice, accounts.bob, Some(Id::U8(1)), false);
+            }
+
+            #[ink::test]
+            fn operator_approves_for_all_on_own_behalf() {
+                let accounts = default_accounts::<E>();
+                // Create a new contract instance.
+                let mut token = $constructor();
+                // Approve all tokens transfer for Bob on behalf of Alice.
+                assert_eq!(token.approve(accounts.bob, None, true), Ok(()));
+                // Bob approves Charlie for all tokens, which covers only Bob's tokens.
+                set_caller(accounts.bob);
+                let start = recorded_events().count();
+                assert_eq!(token.approve(accounts.charlie, None, true), Ok(()));
+                let events = decode_events(start);
+                assert_eq!(events.len(), 1);
+                assert_approval(&events[0], accounts.bob, accounts.charlie, None, true);
+                assert!(token.allowance(accounts.bob, accounts.charlie, None));
+                assert!(!token.allowance(accounts.alice, accounts.charlie, None));
+            }
+
+            #[ink::test]
+            fn approved_for_all_covers_only_owned_tokens() {
+                let accounts = default_accounts::<E>();
+                // Create a new contract instance.
+                let mut token = $constructor();
+                // Create token Id 1 and 2, and give token Id 2 to Charlie.
+                assert_eq!(token.mint(Id::U8(1)), Ok(()));
+                assert_eq!(token.mint(Id::U8(2)), Ok(()));
+                assert_eq!(token.transfer(accounts.charlie, Id::U8(2), vec![]), Ok(()));
+                // Approve all tokens transfer for Bob on behalf of Alice.
+                assert_eq!(token.approve(accounts.bob, None, true), Ok(()));
+                assert!(token.allowance(accounts.alice, accounts.bob, Some(Id::U8(1))));
+                // Tokens which do not exist or belong to someone else are not covered.
+                assert!(!token.allowance(accounts.alice, accounts.bob, Some(Id::U8(3))));
+                assert!(!token.allowance(accounts.alice, accounts.bob, Some(Id::U8(2))));
+                assert_eq!(token.burn(accounts.alice, Id::U8(1)), Ok(()));
+                assert!(!token.allowance(accounts.alice, accounts.bob, Some(Id::U8(1))));
+                assert!(token.allowance(accounts.alice, accounts.bob, None));
+            }
+
             #[ink::test]
             fn approved_for_all_revoke_single_approval_should_fail() {
                 let accounts = default_accounts::<E>();
@@ -351,6 +462,36 @@ macro_rules! tests {
                     Err(PSP34Error::NotApproved)
                 );
             }
+
+            #[ink::test]
+            fn invariants_hold_after_random_operations() {
+                let accounts = default_accounts::<E>();
+                let holders = [accounts.alice, accounts.bob, accounts.charlie, accounts.django];
+                let ids: Vec<Id> = (0..12).map(Id::U8).collect();
+                // Create a new contract instance.
+                let mut token = $constructor();
+                // A simple deterministic pseudo-random generator.
+                let mut seed: u64 = 42;
+                let mut next = |bound: usize| {
+                    seed = seed
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    (seed >> 33) as usize % bound
+                };
+                for _ in 0..300 {
+                    let caller = holders[next(holders.len())];
+                    let id = ids[next(ids.len())].clone();
+                    set_caller(caller);
+                    // Operations may fail, the state must stay consistent either way.
+                    let _ = match next(4) {
+                        0 => token.mint(id),
+                        1 => token.transfer(holders[next(holders.len())], id, vec![]),
+                        2 => token.burn(holders[next(holders.len())], id),
+                        _ => token.approve(holders[next(holders.len())], None, next(2) == 0),
+                    };
+                    check_invariants(&token, &holders, &ids);
+                }
+            }
         }
     };
 }